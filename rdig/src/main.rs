@@ -1,21 +1,80 @@
-use base64ct::{Base64, Encoding};
+use std::net::{IpAddr, SocketAddr};
+use std::process::ExitCode;
 
+use rdns_lib::client::Client;
+use rdns_lib::edns::Edns;
 use rdns_lib::message::Message;
+use rdns_lib::question::Question;
+use rdns_lib::resolver::{ResolvConf, RESOLV_CONF};
 
-fn main() {
+const USAGE: &str = "Usage: rdig [@server] [+tcp] name [type] [class]";
+
+fn main() -> ExitCode {
     tracing_subscriber::fmt::init();
 
-    let response = Base64::decode_vec("MRiBoAABAAIAAAAABnByb3RvbgJtZQAADwABwAwADwABAAACigAWAAoEbWFpbApwcm90b25tYWlsAmNoAMAMAA8AAQAAAooADAAUB21haWxzZWPALg==").unwrap();
+    let mut server = None;
+    let mut tcp = false;
+    let mut question = Vec::new();
+    for arg in std::env::args().skip(1) {
+        if arg == "+tcp" {
+            tcp = true;
+            continue;
+        }
+        match arg.strip_prefix('@') {
+            Some(address) => match parse_server(address) {
+                Some(address) => server = Some(address),
+                None => {
+                    eprintln!("Invalid server address {address}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            None => question.push(arg),
+        }
+    }
+    if question.is_empty() {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+    // The type defaults to A, like dig.
+    if question.len() == 1 {
+        question.push("A".to_string());
+    }
+    let question: Question = match question.join(" ").parse() {
+        Ok(question) => question,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
 
-    let response_message = Message::try_from(&response[..]).expect("Couldn't parse");
+    let query = Message::builder()
+        .with_recursion_desired(true)
+        .with_edns(Edns::new(1232))
+        .add_question(question)
+        .build();
+    // Without a server, the ones in resolv.conf are used, like dig does.
+    let client = match server {
+        Some(server) => Client::new(server),
+        None => ResolvConf::read(RESOLV_CONF).unwrap_or_default().client(),
+    }
+    .with_tcp(tcp);
+    let response_message = match client.query(&query) {
+        Ok(response) => response,
+        Err(e) => {
+            let servers: Vec<String> = client.servers().iter().map(|s| s.to_string()).collect();
+            let servers = servers.join(", ");
+            eprintln!("Couldn't query {servers}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
 
     if response_message.is_answer() {
         println!(
             "Response from {} with id 0x{:04x?}:",
-            response_message.header().opcode(),
+            response_message.header().opcode().mnemonic(),
             response_message.header().id()
         );
-        print!("{}, ", response_message.header().response_code());
+        print!("{}, ", response_message.response_code().mnemonic());
         if response_message.header().authoritive_answer() {
             print!("Authoritative, ");
         } else {
@@ -27,9 +86,17 @@ fn main() {
             print!("Not truncated, ");
         }
         if response_message.header().recursion_available() {
-            println!("Recursion is available");
+            print!("Recursion is available, ");
+        } else {
+            print!("No recursion available, ");
+        }
+        if response_message.header().authentic_data() {
+            println!("Authentic data");
         } else {
-            println!("No recursion available");
+            println!("Not authenticated");
+        }
+        if let Some(edns) = response_message.edns() {
+            println!("{edns}");
         }
         println!("\nQuestions:");
         for q in response_message.questions().iter() {
@@ -48,4 +115,14 @@ fn main() {
             println!("{a}");
         }
     }
+
+    ExitCode::SUCCESS
+}
+
+/// Reads a server given as an IP address, with or without a port.
+fn parse_server(address: &str) -> Option<SocketAddr> {
+    address
+        .parse()
+        .ok()
+        .or_else(|| Some(SocketAddr::new(address.parse::<IpAddr>().ok()?, 53)))
 }
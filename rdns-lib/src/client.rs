@@ -0,0 +1,252 @@
+//! Sending queries to name servers and waiting for their responses.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::error::ClientError;
+use crate::header::ReturnCode;
+use crate::message::Message;
+
+#[cfg(feature = "tokio")]
+pub use asynchronous::AsyncClient;
+#[cfg(feature = "https")]
+pub use https::{HttpMethod, HttpsClient, HttpsResponse};
+#[cfg(feature = "quic")]
+pub use quic::QuicClient;
+use tcp::SharedConnection;
+pub use tcp::{Responses, TcpConnection};
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+
+#[cfg(feature = "tokio")]
+mod asynchronous;
+#[cfg(feature = "https")]
+mod https;
+#[cfg(feature = "quic")]
+mod quic;
+mod tcp;
+#[cfg(feature = "tls")]
+mod tls;
+mod udp;
+
+/// A blocking stub resolver client, which sends queries to one or more name servers.
+///
+/// Each server is tried in turn, waiting up to the timeout for each, and the whole list is gone
+/// through as many times as there are attempts. Queries go over UDP, and are sent again over TCP
+/// if the response was truncated. TCP connections are kept open and reused for later queries to
+/// the same server, and queries from different threads share them, with their responses read in
+/// whatever order the server sends them.
+#[derive(Clone, Debug)]
+pub struct Client {
+    servers: Vec<SocketAddr>,
+    timeout: Duration,
+    attempts: usize,
+    tcp: bool,
+    rotate: bool,
+    next_server: Arc<AtomicUsize>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConfig>,
+    connections: Arc<Mutex<HashMap<SocketAddr, Arc<SharedConnection>>>>,
+}
+
+impl Client {
+    /// Creates a client that sends queries to `server`, waiting five seconds for a response and
+    /// trying twice, like the defaults of resolv.conf.
+    pub fn new(server: SocketAddr) -> Self {
+        Self {
+            servers: vec![server],
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+            tcp: false,
+            rotate: false,
+            next_server: Arc::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+            connections: Arc::default(),
+        }
+    }
+
+    /// Adds a server to try after the ones already added.
+    pub fn with_server(mut self, server: SocketAddr) -> Self {
+        self.servers.push(server);
+        self
+    }
+
+    /// Sets how long to wait for each server to respond.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many times to go through the list of servers before giving up. Anything less
+    /// than one counts as one.
+    pub fn with_attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Sends every query over TCP, rather than only the ones whose UDP responses were truncated.
+    pub fn with_tcp(mut self, tcp: bool) -> Self {
+        self.tcp = tcp;
+        self
+    }
+
+    /// Starts each query at the server after the one the last query started at, spreading queries
+    /// across the servers rather than always trying the first one first.
+    pub fn with_rotate(mut self, rotate: bool) -> Self {
+        self.rotate = rotate;
+        self
+    }
+
+    /// Sends every query over TLS (RFC 7858), authenticating servers with `tls`. DNS over TLS
+    /// servers listen on port 853.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    pub fn servers(&self) -> &[SocketAddr] {
+        &self.servers
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    pub fn tcp(&self) -> bool {
+        self.tcp
+    }
+
+    pub fn rotate(&self) -> bool {
+        self.rotate
+    }
+
+    /// Sends `query` and returns the first response to it. If no server responds, the error from
+    /// the last one tried is returned.
+    #[tracing::instrument(skip_all, fields(id = query.header().id()))]
+    pub fn query(&self, query: &Message) -> Result<Message, ClientError> {
+        let bytes = Vec::try_from(query)?;
+        let mut last_error = ClientError::Timeout;
+        let first = match self.rotate {
+            true => self.next_server.fetch_add(1, Ordering::Relaxed) % self.servers.len(),
+            false => 0,
+        };
+        for _ in 0..self.attempts {
+            let servers = self.servers.iter().cycle().skip(first);
+            for server in servers.take(self.servers.len()) {
+                let result = match self.tcp || self.tls() {
+                    true => self.query_tcp(*server, query),
+                    false => udp::exchange(*server, &bytes, query, self.timeout).and_then(
+                        |response| match response.header().truncation() {
+                            true => {
+                                tracing::debug!(%server, "response truncated, retrying over TCP");
+                                self.query_tcp(*server, query)
+                            }
+                            false => Ok(response),
+                        },
+                    ),
+                };
+                match result {
+                    Ok(response) => return Ok(response),
+                    Err(error) => {
+                        tracing::debug!(%server, %error, "no response");
+                        last_error = error;
+                    }
+                }
+            }
+        }
+        Err(last_error)
+    }
+}
+
+impl Client {
+    /// Sends `query` over the open connection to `server`, or a new one if there isn't one or the
+    /// server has closed it since it was last used (RFC 7766 §6.2.3).
+    fn query_tcp(&self, server: SocketAddr, query: &Message) -> Result<Message, ClientError> {
+        let (connection, reused) = self.connection(server)?;
+        match connection.query(query) {
+            Err(ClientError::Io(error)) if reused => {
+                tracing::debug!(%server, %error, "reconnecting");
+                self.connection(server)?.0.query(query)
+            }
+            result => result,
+        }
+    }
+
+    /// The open connection to `server`, connecting if there isn't one, and whether it was already
+    /// open. Threads that need a connection at the same time wait for the same one rather than
+    /// each opening their own.
+    fn connection(&self, server: SocketAddr) -> Result<(Arc<SharedConnection>, bool), ClientError> {
+        let mut connections = self
+            .connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(connection) = connections.get(&server) {
+            if !connection.is_closed() {
+                return Ok((connection.clone(), true));
+            }
+        }
+        let connection = Arc::new(self.connect(server)?);
+        connections.insert(server, connection.clone());
+        Ok((connection, false))
+    }
+
+    fn connect(&self, server: SocketAddr) -> Result<SharedConnection, ClientError> {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            return SharedConnection::connect_tls(server, self.timeout, tls);
+        }
+        SharedConnection::connect(server, self.timeout)
+    }
+
+    fn tls(&self) -> bool {
+        #[cfg(feature = "tls")]
+        return self.tls.is_some();
+        #[cfg(not(feature = "tls"))]
+        false
+    }
+}
+
+/// Turns the errors reads and writes give when their timeout runs out into [`ClientError::Timeout`].
+pub(crate) fn io_error(error: std::io::Error) -> ClientError {
+    match error.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => ClientError::Timeout,
+        _ => ClientError::Io(error),
+    }
+}
+
+/// Whether `response` answers `query`: it has to be a response with the same id and the same
+/// questions (RFC 5452 §9.1). Servers that can't make sense of a query may not repeat the
+/// questions, so those responses are let through without them.
+pub(crate) fn is_response_to(response: &Message, query: &Message) -> bool {
+    if !response.is_answer() || response.header().id() != query.header().id() {
+        return false;
+    }
+    if response.questions().is_empty()
+        && matches!(
+            response.response_code(),
+            ReturnCode::FormatError | ReturnCode::NotImplemented
+        )
+    {
+        return true;
+    }
+    response.questions().len() == query.questions().len()
+        && response
+            .questions()
+            .iter()
+            .zip(query.questions().iter())
+            .all(|(a, b)| {
+                a.name() == b.name()
+                    && u16::from(a.question_type()) == u16::from(b.question_type())
+                    && u16::from(a.class()) == u16::from(b.class())
+            })
+}
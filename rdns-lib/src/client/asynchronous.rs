@@ -0,0 +1,618 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::error::ClientError;
+use crate::message::Message;
+
+/// The largest UDP payload there can be.
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+/// How many queries go out from one UDP socket before the next ones get a new socket, and so a
+/// new source port.
+const QUERIES_PER_SOCKET: usize = 64;
+
+/// An async version of [`Client`](super::Client) for tokio.
+///
+/// Queries to a server share a UDP socket, and one TCP connection once one is needed, with
+/// responses matched up to queries by id and question as they arrive, so any number of queries
+/// can be waiting at once. A query is cancelled by dropping its future.
+///
+/// Every query goes out with a random id, and a UDP socket is replaced with a new one after a fixed
+/// number of queries, so that a spoofed response has to guess both the id and the
+/// source port as it would with [`Client`](super::Client).
+#[derive(Clone, Debug)]
+pub struct AsyncClient {
+    servers: Vec<SocketAddr>,
+    timeout: Duration,
+    attempts: usize,
+    tcp: bool,
+    udp_channels: Arc<Mutex<HashMap<SocketAddr, Arc<Channel>>>>,
+    tcp_channels: Arc<Mutex<HashMap<SocketAddr, Arc<Channel>>>>,
+}
+
+impl AsyncClient {
+    /// Creates a client that sends queries to `server`, with the same defaults as
+    /// [`Client::new`](super::Client::new).
+    pub fn new(server: SocketAddr) -> Self {
+        Self {
+            servers: vec![server],
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+            tcp: false,
+            udp_channels: Arc::default(),
+            tcp_channels: Arc::default(),
+        }
+    }
+
+    /// Adds a server to try after the ones already added.
+    pub fn with_server(mut self, server: SocketAddr) -> Self {
+        self.servers.push(server);
+        self
+    }
+
+    /// Sets how long to wait for each server to respond.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many times to go through the list of servers before giving up. Anything less
+    /// than one counts as one.
+    pub fn with_attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Sends every query over TCP, rather than only the ones whose UDP responses were truncated.
+    pub fn with_tcp(mut self, tcp: bool) -> Self {
+        self.tcp = tcp;
+        self
+    }
+
+    pub fn servers(&self) -> &[SocketAddr] {
+        &self.servers
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    pub fn tcp(&self) -> bool {
+        self.tcp
+    }
+
+    /// Sends `query` and returns the first response to it.
+    ///
+    /// The query is sent with a random id that no other query to the same server is waiting
+    /// with, and the response is given the query's own id.
+    pub async fn query(&self, query: &Message) -> Result<Message, ClientError> {
+        self.query_until(query, None).await
+    }
+
+    /// Like [`query`](Self::query), but gives up at `deadline` even if there are servers and
+    /// attempts left.
+    pub async fn query_with_deadline(
+        &self,
+        query: &Message,
+        deadline: Instant,
+    ) -> Result<Message, ClientError> {
+        self.query_until(query, Some(deadline)).await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = query.header().id()))]
+    async fn query_until(
+        &self,
+        query: &Message,
+        deadline: Option<Instant>,
+    ) -> Result<Message, ClientError> {
+        let attempt_deadline = || {
+            let end = Instant::now() + self.timeout;
+            deadline.map_or(end, |deadline| deadline.min(end))
+        };
+
+        let mut last_error = ClientError::Timeout;
+        for _ in 0..self.attempts {
+            for server in self.servers.iter() {
+                let result = match self.tcp {
+                    true => {
+                        self.exchange(*server, true, query, attempt_deadline())
+                            .await
+                    }
+                    false => match self
+                        .exchange(*server, false, query, attempt_deadline())
+                        .await
+                    {
+                        Ok(response) if response.header().truncation() => {
+                            tracing::debug!(%server, "response truncated, retrying over TCP");
+                            self.exchange(*server, true, query, attempt_deadline())
+                                .await
+                        }
+                        result => result,
+                    },
+                };
+                match result {
+                    Ok(response) => return Ok(response),
+                    Err(error) => {
+                        tracing::debug!(%server, %error, "no response");
+                        last_error = error;
+                    }
+                }
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return Err(last_error);
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn exchange(
+        &self,
+        server: SocketAddr,
+        tcp: bool,
+        query: &Message,
+        deadline: Instant,
+    ) -> Result<Message, ClientError> {
+        let channels = match tcp {
+            true => &self.tcp_channels,
+            false => &self.udp_channels,
+        };
+        let open = lock(channels)
+            .get(&server)
+            .filter(|channel| !channel.is_closed() && !channel.is_used_up())
+            .cloned();
+        let channel = match open {
+            Some(channel) => channel,
+            None => {
+                let channel = match tcp {
+                    true => tokio::time::timeout_at(deadline, Channel::tcp(server))
+                        .await
+                        .map_err(|_| ClientError::Timeout)??,
+                    false => Channel::udp(server).await?,
+                };
+                let channel = Arc::new(channel);
+                lock(channels).insert(server, channel.clone());
+                channel
+            }
+        };
+        channel.exchange(query, deadline).await
+    }
+}
+
+/// A socket or connection to one server, which any number of queries can be waiting on.
+#[derive(Debug)]
+struct Channel {
+    sender: Sender,
+    pending: Arc<Mutex<HashMap<u16, Waiter>>>,
+    next_token: AtomicU64,
+    /// How many more queries can be sent, for UDP sockets.
+    remaining: Option<AtomicUsize>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+enum Sender {
+    Udp(Arc<UdpSocket>),
+    /// Messages are written by a task of their own, so that a query being cancelled can't leave
+    /// half a message on the connection.
+    Tcp(mpsc::UnboundedSender<Vec<u8>>),
+}
+
+#[derive(Debug)]
+struct Waiter {
+    query: Message,
+    /// Tells this query's waiter apart from a later one that ends up with the same id.
+    token: u64,
+    response: oneshot::Sender<Result<Message, ClientError>>,
+}
+
+impl Channel {
+    async fn udp(server: SocketAddr) -> Result<Self, ClientError> {
+        let local: SocketAddr = match server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        // Connecting means datagrams from any other address are dropped.
+        socket.connect(server).await?;
+        let socket = Arc::new(socket);
+
+        let pending = Arc::default();
+        let reader = tokio::spawn(read_datagrams(socket.clone(), Arc::clone(&pending)));
+        Ok(Self {
+            sender: Sender::Udp(socket),
+            pending,
+            next_token: AtomicU64::new(0),
+            remaining: Some(AtomicUsize::new(QUERIES_PER_SOCKET)),
+            tasks: vec![reader],
+        })
+    }
+
+    async fn tcp(server: SocketAddr) -> Result<Self, ClientError> {
+        let stream = TcpStream::connect(server).await?;
+        stream.set_nodelay(true)?;
+        let (read_half, write_half) = stream.into_split();
+
+        let pending = Arc::default();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let reader = tokio::spawn(read_stream(read_half, Arc::clone(&pending)));
+        let writer = tokio::spawn(write_stream(write_half, receiver));
+        Ok(Self {
+            sender: Sender::Tcp(sender),
+            pending,
+            next_token: AtomicU64::new(0),
+            remaining: None,
+            tasks: vec![reader, writer],
+        })
+    }
+
+    /// Whether the connection has been closed, by either end.
+    fn is_closed(&self) -> bool {
+        self.tasks.iter().any(JoinHandle::is_finished)
+    }
+
+    /// Whether this socket has sent all the queries it should, so later ones should get a new
+    /// one. Queries already sent still get their responses.
+    fn is_used_up(&self) -> bool {
+        self.remaining
+            .as_ref()
+            .is_some_and(|remaining| remaining.load(Ordering::Relaxed) == 0)
+    }
+
+    async fn exchange(&self, query: &Message, deadline: Instant) -> Result<Message, ClientError> {
+        if let Some(remaining) = &self.remaining {
+            let _ =
+                remaining.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        }
+        let id = query.header().id();
+        let mut query = query.clone();
+        query.set_id(rand::random());
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut pending = lock(&self.pending);
+            if pending.len() > u16::MAX as usize {
+                return Err(ClientError::IdInUse(query.header().id()));
+            }
+            while pending.contains_key(&query.header().id()) {
+                query.set_id(rand::random());
+            }
+            pending.insert(
+                query.header().id(),
+                Waiter {
+                    query: query.clone(),
+                    token,
+                    response: sender,
+                },
+            );
+        }
+        let _registration = Registration {
+            pending: &self.pending,
+            id: query.header().id(),
+            token,
+        };
+
+        let bytes = Vec::try_from(&query)?;
+        let response = async {
+            match &self.sender {
+                Sender::Udp(socket) => {
+                    socket.send(&bytes).await?;
+                }
+                Sender::Tcp(sender) => sender
+                    .send(bytes)
+                    .map_err(|_| ClientError::Io(ErrorKind::BrokenPipe.into()))?,
+            }
+            receiver
+                .await
+                .unwrap_or_else(|_| Err(ClientError::Io(ErrorKind::ConnectionAborted.into())))
+        };
+        let mut response = tokio::time::timeout_at(deadline, response)
+            .await
+            .unwrap_or(Err(ClientError::Timeout))?;
+        response.set_id(id);
+        Ok(response)
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        for task in self.tasks.iter() {
+            task.abort();
+        }
+    }
+}
+
+/// Takes a query's waiter back out once the query is over, whether it got a response, ran out
+/// of time or had its future dropped.
+struct Registration<'p> {
+    pending: &'p Mutex<HashMap<u16, Waiter>>,
+    id: u16,
+    token: u64,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let mut pending = lock(self.pending);
+        if pending.get(&self.id).is_some_and(|w| w.token == self.token) {
+            pending.remove(&self.id);
+        }
+    }
+}
+
+async fn read_datagrams(socket: Arc<UdpSocket>, pending: Arc<Mutex<HashMap<u16, Waiter>>>) {
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+    loop {
+        match socket.recv(&mut buffer).await {
+            Ok(length) => dispatch(&pending, &buffer[..length]),
+            // An ICMP error, most likely that nothing is listening on the server's port.
+            Err(error) => fail_all(&pending, error.kind()),
+        }
+    }
+}
+
+async fn read_stream(
+    mut stream: impl AsyncRead + Unpin,
+    pending: Arc<Mutex<HashMap<u16, Waiter>>>,
+) {
+    loop {
+        match read_message(&mut stream).await {
+            Ok(Some(bytes)) => dispatch(&pending, &bytes),
+            Ok(None) => return fail_all(&pending, ErrorKind::UnexpectedEof),
+            Err(error) => return fail_all(&pending, error.kind()),
+        }
+    }
+}
+
+async fn write_stream(
+    mut stream: impl AsyncWrite + Unpin,
+    mut messages: mpsc::UnboundedReceiver<Vec<u8>>,
+) {
+    while let Some(bytes) = messages.recv().await {
+        if let Err(error) = write_message(&mut stream, &bytes).await {
+            tracing::debug!(%error, "couldn't write to the connection");
+            return;
+        }
+    }
+}
+
+/// Hands a response to the query waiting for it, if there is one.
+fn dispatch(pending: &Mutex<HashMap<u16, Waiter>>, bytes: &[u8]) {
+    let response = match Message::try_from(bytes) {
+        Ok(response) => response,
+        Err(error) => return tracing::debug!(%error, "ignoring response that doesn't parse"),
+    };
+    let id = response.header().id();
+    let mut pending = lock(pending);
+    match pending.get(&id) {
+        Some(waiter) if super::is_response_to(&response, &waiter.query) => {
+            let waiter = pending.remove(&id).unwrap();
+            let _ = waiter.response.send(Ok(response));
+        }
+        _ => tracing::debug!(id, "ignoring unrelated response"),
+    }
+}
+
+fn fail_all(pending: &Mutex<HashMap<u16, Waiter>>, kind: ErrorKind) {
+    for (_, waiter) in lock(pending).drain() {
+        let _ = waiter.response.send(Err(ClientError::Io(kind.into())));
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The async version of [`tcp::write_message`](super::tcp::write_message).
+pub(crate) async fn write_message(
+    stream: &mut (impl AsyncWrite + Unpin),
+    bytes: &[u8],
+) -> std::io::Result<()> {
+    let length = u16::try_from(bytes.len())
+        .map_err(|_| std::io::Error::new(ErrorKind::InvalidInput, "message is too long to send"))?;
+    let mut framed = Vec::with_capacity(bytes.len() + 2);
+    framed.extend_from_slice(&length.to_be_bytes());
+    framed.extend_from_slice(bytes);
+    stream.write_all(&framed).await?;
+    stream.flush().await
+}
+
+/// The async version of [`tcp::read_message`](super::tcp::read_message).
+pub(crate) async fn read_message(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> std::io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 2];
+    let mut read = 0;
+    while read < length.len() {
+        match stream.read(&mut length[read..]).await? {
+            0 if read == 0 => return Ok(None),
+            0 => return Err(ErrorKind::UnexpectedEof.into()),
+            n => read += n,
+        }
+    }
+
+    let mut message = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut message).await?;
+    Ok(Some(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    async fn responder() -> (UdpSocket, SocketAddr) {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = socket.local_addr().unwrap();
+        (socket, address)
+    }
+
+    async fn receive(socket: &UdpSocket) -> (Message, SocketAddr) {
+        let mut buffer = [0; 512];
+        let (length, source) = socket.recv_from(&mut buffer).await.unwrap();
+        (Message::try_from(&buffer[..length]).unwrap(), source)
+    }
+
+    async fn answer(socket: &UdpSocket, query: &Message, client: SocketAddr) {
+        let name = query.questions()[0].name();
+        let response = Message::response_to(query)
+            .add_answer(format!("{name} 60 A 192.0.2.1").parse().unwrap())
+            .into_bytes()
+            .unwrap();
+        socket.send_to(&response, client).await.unwrap();
+    }
+
+    fn query(id: u16, name: &str) -> Message {
+        Message::builder()
+            .with_id(id)
+            .add_question(format!("{name} A").parse().unwrap())
+            .build()
+    }
+
+    fn pending(client: &AsyncClient, server: SocketAddr) -> usize {
+        lock(&lock(&client.udp_channels)[&server].pending).len()
+    }
+
+    #[tokio::test]
+    async fn concurrent_queries_share_a_socket_with_random_ids() {
+        let (server, address) = responder().await;
+        let responder = tokio::spawn(async move {
+            let mut queries = Vec::new();
+            for _ in 0..3 {
+                queries.push(receive(&server).await);
+            }
+            // Answered in the opposite order to how they were sent.
+            for (query, client) in queries.iter().rev() {
+                answer(&server, query, *client).await;
+            }
+            queries
+        });
+
+        let client = AsyncClient::new(address);
+        let (a, b, c) = (
+            query(7, "a.example."),
+            query(7, "b.example."),
+            query(7, "c.example."),
+        );
+        let responses = tokio::join!(client.query(&a), client.query(&b), client.query(&c));
+        for (response, name) in [
+            (responses.0, "a.example."),
+            (responses.1, "b.example."),
+            (responses.2, "c.example."),
+        ] {
+            let response = response.unwrap();
+            assert_eq!(response.header().id(), 7);
+            assert_eq!(response.answers()[0].name().to_string(), name);
+        }
+
+        let queries = responder.await.unwrap();
+        let ids: HashSet<u16> = queries.iter().map(|(q, _)| q.header().id()).collect();
+        let sources: HashSet<SocketAddr> = queries.iter().map(|(_, source)| *source).collect();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(pending(&client, address), 0);
+    }
+
+    #[tokio::test]
+    async fn dropping_a_query_cancels_it() {
+        let (server, address) = responder().await;
+        let client = AsyncClient::new(address);
+
+        let dropped = query(7, "a.example.");
+        let result = tokio::time::timeout(Duration::from_millis(50), client.query(&dropped)).await;
+        assert!(result.is_err());
+        assert_eq!(pending(&client, address), 0);
+
+        // A late response to the dropped query isn't taken for the response to the new one,
+        // even if it has the new one's id.
+        let (mut first, _) = receive(&server).await;
+        let responder = tokio::spawn(async move {
+            let (second, client) = receive(&server).await;
+            first.set_id(second.header().id());
+            answer(&server, &first, client).await;
+            answer(&server, &second, client).await;
+        });
+        let response = client.query(&query(7, "b.example.")).await.unwrap();
+        assert_eq!(response.header().id(), 7);
+        assert_eq!(response.answers()[0].name().to_string(), "b.example.");
+        responder.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn sockets_are_replaced_after_enough_queries() {
+        let (server, address) = responder().await;
+        let responder = tokio::spawn(async move {
+            let mut sources = Vec::new();
+            let mut ids = HashSet::new();
+            for _ in 0..QUERIES_PER_SOCKET + 1 {
+                let (query, client) = receive(&server).await;
+                answer(&server, &query, client).await;
+                sources.push(client);
+                ids.insert(query.header().id());
+            }
+            (sources, ids)
+        });
+
+        let client = AsyncClient::new(address);
+        for _ in 0..QUERIES_PER_SOCKET + 1 {
+            let response = client.query(&query(7, "example.")).await.unwrap();
+            assert_eq!(response.header().id(), 7);
+        }
+
+        let (sources, ids) = responder.await.unwrap();
+        assert!(sources[..QUERIES_PER_SOCKET]
+            .iter()
+            .all(|s| *s == sources[0]));
+        assert_ne!(sources[QUERIES_PER_SOCKET], sources[0]);
+        // Random ids can collide, but not this often.
+        assert!(ids.len() > QUERIES_PER_SOCKET / 2);
+    }
+
+    #[tokio::test]
+    async fn deadline_cuts_attempts_short() {
+        let (_first, first) = responder().await;
+        let (_second, second) = responder().await;
+        let client = AsyncClient::new(first)
+            .with_server(second)
+            .with_timeout(Duration::from_secs(10))
+            .with_attempts(3);
+
+        let started = Instant::now();
+        let deadline = started + Duration::from_millis(100);
+        let result = client
+            .query_with_deadline(&query(1, "example."), deadline)
+            .await;
+        assert!(matches!(result, Err(ClientError::Timeout)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn moves_on_to_the_next_server_after_the_timeout() {
+        let (_silent, silent) = responder().await;
+        let (server, address) = responder().await;
+        let responder = tokio::spawn(async move {
+            let (query, client) = receive(&server).await;
+            answer(&server, &query, client).await;
+        });
+
+        let client = AsyncClient::new(silent)
+            .with_server(address)
+            .with_timeout(Duration::from_millis(100));
+        let response = client.query(&query(1, "example.")).await.unwrap();
+        assert_eq!(response.answers().len(), 1);
+        responder.await.unwrap();
+    }
+}
@@ -0,0 +1,491 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::client::conn::http2::SendRequest;
+use hyper::{header, HeaderMap, Request, Uri};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use super::TlsConfig;
+use crate::error::ClientError;
+use crate::message::Message;
+
+/// The media type of DNS messages sent over HTTP (RFC 8484 §6).
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// How queries are put in HTTP requests (RFC 8484 §4.1).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum HttpMethod {
+    /// The query is the body of a POST request.
+    #[default]
+    Post,
+    /// The query goes in the `dns` parameter of the URI, base64url encoded, which lets HTTP
+    /// caches answer it.
+    Get,
+}
+
+/// A DNS over HTTPS client (RFC 8484), which sends queries over HTTP/2.
+///
+/// Queries are sent with an id of 0, as RFC 8484 §4.1 recommends so that the same query always
+/// makes the same request, and the responses are given the id of the query back. Every query
+/// shares one connection, each in its own HTTP/2 stream.
+///
+/// `http` URIs are sent unencrypted, with HTTP/2 from the start, which is only meant for testing
+/// and servers behind a local proxy.
+#[derive(Clone, Debug)]
+pub struct HttpsClient {
+    uri: Uri,
+    method: HttpMethod,
+    tls: TlsConfig,
+    timeout: Duration,
+    connection: Arc<Mutex<Option<SendRequest<Full<Bytes>>>>>,
+}
+
+impl HttpsClient {
+    /// Creates a client for the server at `uri`, such as `https://dns.example/dns-query`, which
+    /// sends queries with POST and waits five seconds for each.
+    pub fn new(uri: &str) -> Result<Self, ClientError> {
+        let bad_uri = || ClientError::BadUri(uri.to_string());
+        let uri: Uri = uri.parse().map_err(|_| bad_uri())?;
+        if !matches!(uri.scheme_str(), Some("https" | "http")) || uri.host().is_none() {
+            return Err(bad_uri());
+        }
+        Ok(Self {
+            uri,
+            method: HttpMethod::Post,
+            tls: TlsConfig::new(),
+            timeout: Duration::from_secs(5),
+            connection: Arc::default(),
+        })
+    }
+
+    pub fn with_method(mut self, method: HttpMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sets how the server is authenticated. Without trust roots or SPKI pins, `https` URIs can't
+    /// be connected to.
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Sets how long to wait for each response, including connecting if need be.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    pub fn method(&self) -> HttpMethod {
+        self.method
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sends `query` and returns the response along with the HTTP details that go with it.
+    #[tracing::instrument(skip_all, fields(id = query.header().id()))]
+    pub async fn query(&self, query: &Message) -> Result<HttpsResponse, ClientError> {
+        tokio::time::timeout(self.timeout, self.exchange(query))
+            .await
+            .unwrap_or(Err(ClientError::Timeout))
+    }
+
+    async fn exchange(&self, query: &Message) -> Result<HttpsResponse, ClientError> {
+        let mut sent = query.clone();
+        sent.set_id(0);
+        let bytes = Vec::try_from(&sent)?;
+
+        let request = match self.method {
+            HttpMethod::Post => Request::post(self.uri.clone())
+                .header(header::CONTENT_TYPE, DNS_MESSAGE)
+                .header(header::ACCEPT, DNS_MESSAGE)
+                .body(Full::from(bytes)),
+            HttpMethod::Get => {
+                let separator = match self.uri.query() {
+                    Some(_) => '&',
+                    None => '?',
+                };
+                let uri = format!(
+                    "{}{separator}dns={}",
+                    self.uri,
+                    Base64UrlUnpadded::encode_string(&bytes)
+                );
+                Request::get(uri)
+                    .header(header::ACCEPT, DNS_MESSAGE)
+                    .body(Full::default())
+            }
+        }
+        .map_err(|e| ClientError::BadUri(e.to_string()))?;
+
+        let mut sender = self.sender().await?;
+        sender.ready().await?;
+        let response = sender.send_request(request).await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.into_body().collect().await?.to_bytes();
+        if !status.is_success() {
+            return Err(ClientError::HttpStatus(status.as_u16()));
+        }
+
+        let mut message = Message::try_from(&body[..])?;
+        if !super::is_response_to(&message, &sent) {
+            return Err(ClientError::WrongResponse);
+        }
+        message.set_id(query.header().id());
+        Ok(HttpsResponse::new(message, status.as_u16(), &headers))
+    }
+
+    /// The open connection, or a new one if there isn't one or the server has closed it.
+    async fn sender(&self) -> Result<SendRequest<Full<Bytes>>, ClientError> {
+        let mut connection = self.connection.lock().await;
+        if let Some(sender) = connection.as_ref().filter(|s| !s.is_closed()) {
+            return Ok(sender.clone());
+        }
+        let sender = self.connect().await?;
+        *connection = Some(sender.clone());
+        Ok(sender)
+    }
+
+    async fn connect(&self) -> Result<SendRequest<Full<Bytes>>, ClientError> {
+        let https = self.uri.scheme_str() == Some("https");
+        // IPv6 addresses in URIs are in brackets, which neither lookups nor certificates want.
+        let host = self
+            .uri
+            .host()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let port = self.uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+
+        // Work out the TLS configuration first, so a bad one fails without connecting.
+        let tls = match https {
+            true => Some((
+                tokio_rustls::TlsConnector::from(self.tls.client_config(b"h2")?),
+                self.tls.server_name_or(host)?,
+            )),
+            false => None,
+        };
+
+        let socket = TcpStream::connect((host, port)).await?;
+        socket.set_nodelay(true)?;
+        match tls {
+            Some((connector, server_name)) => {
+                let stream = connector
+                    .connect(server_name, socket)
+                    .await
+                    .map_err(super::tls::tls_error)?;
+                handshake(stream).await
+            }
+            None => handshake(socket).await,
+        }
+    }
+}
+
+async fn handshake(
+    stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+) -> Result<SendRequest<Full<Bytes>>, ClientError> {
+    let (sender, connection) =
+        hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream)).await?;
+    tokio::spawn(async move {
+        if let Err(error) = connection.await {
+            tracing::debug!(%error, "HTTP/2 connection failed");
+        }
+    });
+    Ok(sender)
+}
+
+/// A response from [`HttpsClient::query`], with the HTTP status and the headers that say how long
+/// it can be cached for.
+#[derive(Clone, Debug)]
+pub struct HttpsResponse {
+    message: Message,
+    status: u16,
+    cache_control: Option<String>,
+    age: Option<u64>,
+    expires: Option<String>,
+}
+
+impl HttpsResponse {
+    fn new(message: Message, status: u16, headers: &HeaderMap) -> Self {
+        let text = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            message,
+            status,
+            cache_control: text(header::CACHE_CONTROL),
+            age: text(header::AGE).and_then(|age| age.trim().parse().ok()),
+            expires: text(header::EXPIRES),
+        }
+    }
+
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// The `Cache-Control` header, which RFC 8484 §5.1 says should have a `max-age` no longer than
+    /// the smallest TTL in the response.
+    pub fn cache_control(&self) -> Option<&str> {
+        self.cache_control.as_deref()
+    }
+
+    /// The `Age` header, in seconds, if the response came out of an HTTP cache.
+    pub fn age(&self) -> Option<u64> {
+        self.age
+    }
+
+    pub fn expires(&self) -> Option<&str> {
+        self.expires.as_deref()
+    }
+
+    /// How much longer the response can be cached for: the `max-age` less the `Age`
+    /// (RFC 9111 §4.2.1). The TTLs in the message should be lowered by the age too (RFC 8484 §5.1).
+    pub fn freshness(&self) -> Option<Duration> {
+        let max_age: u64 = self
+            .cache_control
+            .as_deref()?
+            .split(',')
+            .find_map(|directive| directive.trim().strip_prefix("max-age="))?
+            .parse()
+            .ok()?;
+        Some(Duration::from_secs(
+            max_age.saturating_sub(self.age.unwrap_or(0)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex as SyncMutex;
+
+    use hyper::body::Incoming;
+    use hyper::server::conn::http2;
+    use hyper::service::service_fn;
+    use hyper::{Method, Response};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::client::tls::tests::{certificate, server_config};
+
+    /// The method and id of each query a server got.
+    type Seen = Arc<SyncMutex<Vec<(Method, u16)>>>;
+
+    /// A local server, with the queries it got and how many connections it accepted.
+    struct Server {
+        address: SocketAddr,
+        seen: Seen,
+        connections: Arc<AtomicUsize>,
+    }
+
+    /// Answers queries at `/dns-query`, and fails everything else with a 503.
+    async fn handle(
+        request: Request<Incoming>,
+        seen: Seen,
+    ) -> Result<Response<Full<Bytes>>, hyper::Error> {
+        if request.uri().path() != "/dns-query" {
+            return Ok(Response::builder()
+                .status(503)
+                .body(Full::default())
+                .unwrap());
+        }
+        let method = request.method().clone();
+        let bytes = match method {
+            Method::GET => {
+                let query = request.uri().query().unwrap();
+                let dns = query
+                    .split('&')
+                    .find_map(|p| p.strip_prefix("dns="))
+                    .unwrap();
+                Base64UrlUnpadded::decode_vec(dns).unwrap()
+            }
+            _ => {
+                assert_eq!(request.headers()[header::CONTENT_TYPE], DNS_MESSAGE);
+                request.into_body().collect().await?.to_bytes().to_vec()
+            }
+        };
+        let query = Message::try_from(&bytes[..]).unwrap();
+        seen.lock().unwrap().push((method, query.header().id()));
+
+        let response = Message::response_to(&query)
+            .add_answer("example. 60 A 192.0.2.1".parse().unwrap())
+            .into_bytes()
+            .unwrap();
+        Ok(Response::builder()
+            .header(header::CONTENT_TYPE, DNS_MESSAGE)
+            .header(header::CACHE_CONTROL, "max-age=60")
+            .header(header::AGE, "10")
+            .body(Full::from(response))
+            .unwrap())
+    }
+
+    /// Starts an HTTP/2 server, over TLS if `tls` is given.
+    async fn serve(tls: Option<rustls::ServerConfig>) -> Server {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let server = Server {
+            address: listener.local_addr().unwrap(),
+            seen: Seen::default(),
+            connections: Arc::default(),
+        };
+        let acceptor = tls.map(|tls| tokio_rustls::TlsAcceptor::from(Arc::new(tls)));
+        let (seen, connections) = (server.seen.clone(), server.connections.clone());
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                connections.fetch_add(1, Ordering::Relaxed);
+                let seen = seen.clone();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |request| handle(request, seen.clone()));
+                    let server = http2::Builder::new(TokioExecutor::new());
+                    match acceptor {
+                        Some(acceptor) => {
+                            let stream = acceptor.accept(stream).await.unwrap();
+                            server.serve_connection(TokioIo::new(stream), service).await
+                        }
+                        None => server.serve_connection(TokioIo::new(stream), service).await,
+                    }
+                });
+            }
+        });
+        server
+    }
+
+    fn query() -> Message {
+        Message::builder()
+            .with_id(0xbeef)
+            .add_question("example. A".parse().unwrap())
+            .build()
+    }
+
+    #[tokio::test]
+    async fn posts_queries_with_id_zero() {
+        let server = serve(None).await;
+        let client = HttpsClient::new(&format!("http://{}/dns-query", server.address)).unwrap();
+
+        let response = client.query(&query()).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.message().header().id(), 0xbeef);
+        assert_eq!(response.message().answers().len(), 1);
+        assert_eq!(*server.seen.lock().unwrap(), [(Method::POST, 0)]);
+    }
+
+    #[tokio::test]
+    async fn gets_queries_in_the_dns_parameter() {
+        let server = serve(None).await;
+        let client = HttpsClient::new(&format!("http://{}/dns-query?ct", server.address))
+            .unwrap()
+            .with_method(HttpMethod::Get);
+
+        let response = client.query(&query()).await.unwrap();
+        assert_eq!(response.message().header().id(), 0xbeef);
+        assert_eq!(*server.seen.lock().unwrap(), [(Method::GET, 0)]);
+    }
+
+    #[tokio::test]
+    async fn queries_share_a_connection() {
+        let server = serve(None).await;
+        let client = HttpsClient::new(&format!("http://{}/dns-query", server.address)).unwrap();
+
+        let query = query();
+        let (first, second) = tokio::join!(client.query(&query), client.query(&query));
+        first.unwrap();
+        second.unwrap();
+        client.query(&query).await.unwrap();
+        assert_eq!(server.seen.lock().unwrap().len(), 3);
+        assert_eq!(server.connections.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn reports_error_statuses() {
+        let server = serve(None).await;
+        let client = HttpsClient::new(&format!("http://{}/unavailable", server.address)).unwrap();
+
+        let result = client.query(&query()).await;
+        assert!(matches!(result, Err(ClientError::HttpStatus(503))));
+        assert!(server.seen.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn authenticates_the_server_over_tls() {
+        let (certificate, key) = certificate("doh");
+        let server = serve(Some(server_config(&certificate, &key, b"h2"))).await;
+        let uri = format!("https://{}/dns-query", server.address);
+
+        let tls = TlsConfig::new()
+            .with_root_certificate(certificate.to_vec())
+            .with_server_name("dns.example");
+        let client = HttpsClient::new(&uri).unwrap().with_tls(tls);
+        assert_eq!(client.query(&query()).await.unwrap().status(), 200);
+
+        let tls = TlsConfig::new().with_spki_pin([0; 32]);
+        let client = HttpsClient::new(&uri).unwrap().with_tls(tls);
+        assert!(matches!(
+            client.query(&query()).await,
+            Err(ClientError::Tls(rustls::Error::InvalidCertificate(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn freshness_is_max_age_less_age() {
+        let server = serve(None).await;
+        let client = HttpsClient::new(&format!("http://{}/dns-query", server.address)).unwrap();
+
+        let response = client.query(&query()).await.unwrap();
+        assert_eq!(response.cache_control(), Some("max-age=60"));
+        assert_eq!(response.age(), Some(10));
+        assert_eq!(response.freshness(), Some(Duration::from_secs(50)));
+    }
+
+    #[test]
+    fn freshness_from_headers() {
+        let freshness = |headers: &[(header::HeaderName, &'static str)]| {
+            let mut map = HeaderMap::new();
+            for (name, value) in headers {
+                map.insert(name, value.parse().unwrap());
+            }
+            HttpsResponse::new(query(), 200, &map).freshness()
+        };
+        assert_eq!(freshness(&[]), None);
+        assert_eq!(
+            freshness(&[(header::CACHE_CONTROL, "no-transform, max-age=30")]),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            freshness(&[(header::CACHE_CONTROL, "max-age=30"), (header::AGE, "45")]),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(freshness(&[(header::CACHE_CONTROL, "no-store")]), None);
+    }
+
+    #[test]
+    fn rejects_uris_that_are_not_http() {
+        for uri in ["ftp://dns.example/", "/dns-query", "not a uri"] {
+            assert!(matches!(HttpsClient::new(uri), Err(ClientError::BadUri(_))));
+        }
+    }
+}
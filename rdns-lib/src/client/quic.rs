@@ -0,0 +1,429 @@
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::{Connection, Endpoint, ReadError, WriteError};
+use tokio::sync::{watch, Mutex};
+
+use super::asynchronous::{read_message, write_message};
+use super::TlsConfig;
+use crate::error::ClientError;
+use crate::header::Opcode;
+use crate::message::Message;
+
+/// A DNS over QUIC client (RFC 9250).
+///
+/// Every query goes in its own bidirectional stream of one connection, which is kept open for
+/// later queries and replaced if the server closes it. Queries are sent with an id of 0, as
+/// RFC 9250 §4.2.1 requires, and the responses are given the id of the query back.
+#[derive(Clone, Debug)]
+pub struct QuicClient {
+    server: SocketAddr,
+    tls: TlsConfig,
+    timeout: Duration,
+    zero_rtt: bool,
+    state: Arc<Mutex<Option<State>>>,
+}
+
+/// The endpoint, created on the first query, and the connection it has open, if any.
+#[derive(Debug)]
+struct State {
+    endpoint: Endpoint,
+    session: Option<Session>,
+}
+
+/// An open connection, with whether its handshake has finished, which is only false while
+/// 0-RTT data is being sent.
+#[derive(Clone, Debug)]
+struct Session {
+    connection: Connection,
+    handshake: watch::Receiver<bool>,
+}
+
+impl QuicClient {
+    /// Creates a client that sends queries to `server`, authenticated by `tls`, waiting five
+    /// seconds for each response.
+    pub fn new(server: SocketAddr, tls: TlsConfig) -> Self {
+        Self {
+            server,
+            tls,
+            timeout: Duration::from_secs(5),
+            zero_rtt: false,
+            state: Arc::default(),
+        }
+    }
+
+    /// Sets how long to wait for each response, including connecting if need be.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends queries in 0-RTT data when reconnecting to a server that gave out a session ticket
+    /// earlier, saving a round trip. 0-RTT data can be replayed by an attacker, so only QUERY and
+    /// NOTIFY messages are sent in it (RFC 9250 §4.5), and anything else waits for the handshake.
+    pub fn with_zero_rtt(mut self, zero_rtt: bool) -> Self {
+        self.zero_rtt = zero_rtt;
+        self
+    }
+
+    pub fn server(&self) -> SocketAddr {
+        self.server
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn zero_rtt(&self) -> bool {
+        self.zero_rtt
+    }
+
+    /// Sends `query` and waits for the response.
+    #[tracing::instrument(skip_all, fields(id = query.header().id()))]
+    pub async fn query(&self, query: &Message) -> Result<Message, ClientError> {
+        tokio::time::timeout(self.timeout, self.exchange(query))
+            .await
+            .unwrap_or(Err(ClientError::Timeout))
+    }
+
+    async fn exchange(&self, query: &Message) -> Result<Message, ClientError> {
+        let mut sent = query.clone();
+        sent.set_id(0);
+        let bytes = Vec::try_from(&sent)?;
+        let replayable = matches!(query.header().opcode(), Opcode::Query | Opcode::Notify);
+
+        // A kept connection may have been closed by the server without us noticing yet, so that
+        // one gets a second try on a new connection.
+        let (session, reused) = self.session(replayable).await?;
+        let response = match session.query(&bytes, replayable).await {
+            Err(_) if reused && session.connection.close_reason().is_some() => {
+                let (session, _) = self.session(replayable).await?;
+                session.query(&bytes, replayable).await
+            }
+            result => result,
+        }?;
+
+        let mut message = Message::try_from(&response[..])?;
+        if !super::is_response_to(&message, &sent) {
+            return Err(ClientError::WrongResponse);
+        }
+        message.set_id(query.header().id());
+        Ok(message)
+    }
+
+    /// The open connection and true, or a new one and false if there isn't one or it's closed.
+    async fn session(&self, replayable: bool) -> Result<(Session, bool), ClientError> {
+        let mut state = self.state.lock().await;
+        let state = match &mut *state {
+            Some(state) => state,
+            None => state.insert(State {
+                endpoint: self.endpoint()?,
+                session: None,
+            }),
+        };
+        if let Some(session) = state
+            .session
+            .as_ref()
+            .filter(|s| s.connection.close_reason().is_none())
+        {
+            return Ok((session.clone(), true));
+        }
+        let session = self.connect(&state.endpoint, replayable).await?;
+        state.session = Some(session.clone());
+        Ok((session, false))
+    }
+
+    /// Creates the endpoint that every connection is made from. It keeps the session tickets
+    /// that 0-RTT needs, so it lasts as long as the client.
+    fn endpoint(&self) -> Result<Endpoint, ClientError> {
+        let mut tls = (*self.tls.client_config(b"doq")?).clone();
+        tls.enable_early_data = self.zero_rtt;
+        let crypto = QuicClientConfig::try_from(Arc::new(tls))
+            .map_err(|e| rustls::Error::General(e.to_string()))?;
+
+        let local: SocketAddr = match self.server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let mut endpoint = Endpoint::client(local)?;
+        endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+        Ok(endpoint)
+    }
+
+    async fn connect(&self, endpoint: &Endpoint, replayable: bool) -> Result<Session, ClientError> {
+        let server_name = self.tls.server_name(self.server)?;
+        let mut connecting = endpoint.connect(self.server, &server_name.to_str())?;
+        if self.zero_rtt && replayable {
+            match connecting.into_0rtt() {
+                Ok((connection, accepted)) => {
+                    let (done, handshake) = watch::channel(false);
+                    tokio::spawn(async move {
+                        if !accepted.await {
+                            tracing::debug!("0-RTT data rejected");
+                        }
+                        let _ = done.send(true);
+                    });
+                    return Ok(Session {
+                        connection,
+                        handshake,
+                    });
+                }
+                // There's no session ticket for the server yet.
+                Err(full) => connecting = full,
+            }
+        }
+        Ok(Session {
+            connection: connecting.await?,
+            handshake: watch::channel(true).1,
+        })
+    }
+}
+
+impl Session {
+    /// Sends a query on a new stream, sending it again once the handshake is done if the server
+    /// rejected it as 0-RTT data.
+    async fn query(&self, bytes: &[u8], replayable: bool) -> Result<Vec<u8>, ClientError> {
+        if !replayable {
+            self.handshake_done().await;
+        }
+        match self.exchange(bytes).await {
+            Err(ClientError::Io(error)) if is_0rtt_rejected(&error) => {
+                self.handshake_done().await;
+                self.exchange(bytes).await
+            }
+            result => result,
+        }
+    }
+
+    /// Writes the query and the end of the stream, then reads the response, each with the
+    /// two-byte length in front used over TCP (RFC 9250 §4.2).
+    async fn exchange(&self, bytes: &[u8]) -> Result<Vec<u8>, ClientError> {
+        let (mut send, mut recv) = self.connection.open_bi().await?;
+        write_message(&mut send, bytes).await?;
+        send.finish()
+            .map_err(|e| std::io::Error::new(ErrorKind::NotConnected, e))?;
+        let response = read_message(&mut recv)
+            .await?
+            .ok_or_else(|| std::io::Error::from(ErrorKind::UnexpectedEof))?;
+        Ok(response)
+    }
+
+    async fn handshake_done(&self) {
+        // The sender only goes away after saying the handshake is done, or with the connection.
+        let _ = self.handshake.clone().wait_for(|done| *done).await;
+    }
+}
+
+fn is_0rtt_rejected(error: &std::io::Error) -> bool {
+    let inner = error.get_ref();
+    matches!(
+        inner.and_then(|e| e.downcast_ref::<WriteError>()),
+        Some(WriteError::ZeroRttRejected)
+    ) || matches!(
+        inner.and_then(|e| e.downcast_ref::<ReadError>()),
+        Some(ReadError::ZeroRttRejected)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex as SyncMutex;
+
+    use quinn::crypto::rustls::QuicServerConfig;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+    use super::*;
+    use crate::client::tls::tests::{certificate, server_config};
+
+    /// A local DNS over QUIC server, counting what it gets.
+    struct Server {
+        endpoint: Endpoint,
+        address: SocketAddr,
+        connections: Arc<AtomicUsize>,
+        open: Arc<SyncMutex<Vec<Connection>>>,
+        /// The ids of the queries, one per stream.
+        ids: Arc<SyncMutex<Vec<u16>>>,
+        /// How many queries came in 0-RTT data.
+        early: Arc<AtomicUsize>,
+    }
+
+    impl Server {
+        async fn start(
+            certificate: &CertificateDer<'static>,
+            key: &PrivateKeyDer<'static>,
+            address: SocketAddr,
+        ) -> Self {
+            let mut tls = server_config(certificate, key, b"doq");
+            tls.max_early_data_size = u32::MAX;
+            let crypto = QuicServerConfig::try_from(Arc::new(tls)).unwrap();
+            let config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+            // The address of a server that was just stopped takes a moment to be free.
+            let mut tries = 0;
+            let endpoint = loop {
+                match Endpoint::server(config.clone(), address) {
+                    Ok(endpoint) => break endpoint,
+                    Err(error) if error.kind() == ErrorKind::AddrInUse && tries < 100 => {
+                        tries += 1;
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                    Err(error) => panic!("couldn't bind {address}: {error}"),
+                }
+            };
+            let server = Server {
+                address: endpoint.local_addr().unwrap(),
+                endpoint: endpoint.clone(),
+                connections: Arc::default(),
+                open: Arc::default(),
+                ids: Arc::default(),
+                early: Arc::default(),
+            };
+
+            let (connections, open, ids, early) = (
+                server.connections.clone(),
+                server.open.clone(),
+                server.ids.clone(),
+                server.early.clone(),
+            );
+            tokio::spawn(async move {
+                while let Some(incoming) = endpoint.accept().await {
+                    connections.fetch_add(1, Ordering::Relaxed);
+                    let (connection, _) = incoming.accept().unwrap().into_0rtt().unwrap();
+                    open.lock().unwrap().push(connection.clone());
+                    let (ids, early) = (ids.clone(), early.clone());
+                    tokio::spawn(async move {
+                        while let Ok((send, recv)) = connection.accept_bi().await {
+                            if recv.is_0rtt() {
+                                early.fetch_add(1, Ordering::Relaxed);
+                            }
+                            tokio::spawn(answer(send, recv, ids.clone()));
+                        }
+                    });
+                }
+            });
+            server
+        }
+
+        /// Closes every connection, as a server does with idle ones.
+        async fn close_connections(&self) {
+            for connection in self.open.lock().unwrap().drain(..) {
+                connection.close(0_u32.into(), b"idle");
+            }
+            // Long enough for the client to hear about it.
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        /// Stops the server, freeing its address for another.
+        async fn stop(self) -> SocketAddr {
+            self.endpoint.close(0_u32.into(), b"stopping");
+            self.endpoint.wait_idle().await;
+            self.address
+        }
+    }
+
+    async fn answer(
+        mut send: quinn::SendStream,
+        mut recv: quinn::RecvStream,
+        ids: Arc<SyncMutex<Vec<u16>>>,
+    ) {
+        let bytes = read_message(&mut recv).await.unwrap().unwrap();
+        // The client has to end its side of the stream after the query (RFC 9250 §4.2).
+        assert!(recv.read_to_end(1).await.unwrap().is_empty());
+        let query = Message::try_from(&bytes[..]).unwrap();
+        ids.lock().unwrap().push(query.header().id());
+
+        let response = Message::response_to(&query)
+            .add_answer("example. 60 A 192.0.2.1".parse().unwrap())
+            .into_bytes()
+            .unwrap();
+        write_message(&mut send, &response).await.unwrap();
+        send.finish().unwrap();
+        let _ = send.stopped().await;
+    }
+
+    fn query(opcode: Opcode) -> Message {
+        Message::builder()
+            .with_id(0xbeef)
+            .with_opcode(opcode)
+            .add_question("example. A".parse().unwrap())
+            .build()
+    }
+
+    fn client(certificate: &CertificateDer<'static>, server: &Server) -> QuicClient {
+        let tls = TlsConfig::new()
+            .with_root_certificate(certificate.to_vec())
+            .with_server_name("dns.example");
+        QuicClient::new(server.address, tls)
+    }
+
+    #[tokio::test]
+    async fn sends_each_query_on_its_own_stream_with_id_zero() {
+        let (certificate, key) = certificate("doq");
+        let server = Server::start(&certificate, &key, (Ipv4Addr::LOCALHOST, 0).into()).await;
+        let client = client(&certificate, &server);
+
+        let query = query(Opcode::Query);
+        client.query(&query).await.unwrap();
+        let responses = tokio::join!(
+            client.query(&query),
+            client.query(&query),
+            client.query(&query)
+        );
+        for response in [responses.0, responses.1, responses.2] {
+            let response = response.unwrap();
+            assert_eq!(response.header().id(), 0xbeef);
+            assert_eq!(response.answers().len(), 1);
+        }
+        assert_eq!(*server.ids.lock().unwrap(), [0; 4]);
+        assert_eq!(server.connections.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_the_server_closes_the_connection() {
+        let (certificate, key) = certificate("doq");
+        let server = Server::start(&certificate, &key, (Ipv4Addr::LOCALHOST, 0).into()).await;
+        let client = client(&certificate, &server);
+
+        client.query(&query(Opcode::Query)).await.unwrap();
+        server.close_connections().await;
+        client.query(&query(Opcode::Query)).await.unwrap();
+        assert_eq!(server.connections.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn sends_only_queries_and_notifies_in_0rtt_data() {
+        let (certificate, key) = certificate("doq");
+        let server = Server::start(&certificate, &key, (Ipv4Addr::LOCALHOST, 0).into()).await;
+        let client = client(&certificate, &server).with_zero_rtt(true);
+
+        // The first connection gets the session ticket that 0-RTT needs.
+        client.query(&query(Opcode::Query)).await.unwrap();
+        assert_eq!(server.early.load(Ordering::Relaxed), 0);
+
+        for (opcode, early) in [(Opcode::Query, 1), (Opcode::Update, 1)] {
+            server.close_connections().await;
+            let response = client.query(&query(opcode)).await.unwrap();
+            assert_eq!(response.header().id(), 0xbeef);
+            assert_eq!(server.early.load(Ordering::Relaxed), early, "{opcode:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_again_once_0rtt_data_is_rejected() {
+        let (certificate, key) = certificate("doq");
+        let first = Server::start(&certificate, &key, (Ipv4Addr::LOCALHOST, 0).into()).await;
+        let client = client(&certificate, &first).with_zero_rtt(true);
+        client.query(&query(Opcode::Query)).await.unwrap();
+
+        // A new server on the same address doesn't know the session ticket the client has, so
+        // it rejects the 0-RTT data and the query has to be sent again after the handshake.
+        let second = Server::start(&certificate, &key, first.stop().await).await;
+        let response = client.query(&query(Opcode::Query)).await.unwrap();
+        assert_eq!(response.header().id(), 0xbeef);
+        assert_eq!(second.early.load(Ordering::Relaxed), 0);
+        assert_eq!(*second.ids.lock().unwrap(), [0]);
+    }
+}
@@ -0,0 +1,750 @@
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError, TryLockError};
+use std::time::{Duration, Instant};
+
+use crate::error::ClientError;
+use crate::message::Message;
+use crate::resource_record::RecordData;
+use crate::Type;
+
+/// Writes a message with the two-byte length in front of it that streams need (RFC 1035 §4.2.2).
+/// Both go in a single write so they can go out in one segment (RFC 7766 §8).
+pub(crate) fn write_message(stream: &mut impl Write, bytes: &[u8]) -> std::io::Result<()> {
+    let length = u16::try_from(bytes.len())
+        .map_err(|_| std::io::Error::new(ErrorKind::InvalidInput, "message is too long to send"))?;
+    let mut framed = Vec::with_capacity(bytes.len() + 2);
+    framed.extend_from_slice(&length.to_be_bytes());
+    framed.extend_from_slice(bytes);
+    stream.write_all(&framed)?;
+    stream.flush()
+}
+
+/// Reads the next length-prefixed message, or `None` if the stream ended cleanly between messages.
+pub(crate) fn read_message(stream: &mut impl Read) -> std::io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 2];
+    let mut read = 0;
+    while read < length.len() {
+        match stream.read(&mut length[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut message = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+/// A TCP connection to a name server, which can be used for any number of queries.
+///
+/// Queries can be pipelined: several can be sent before any responses are read, and the server may
+/// answer them in any order (RFC 7766 §6.2.1.1). Responses are matched up by id, so the queries in
+/// flight on a connection need different ones.
+#[derive(Debug)]
+pub struct TcpConnection {
+    stream: Stream,
+    /// Queries that have been sent and not answered yet, by id.
+    outstanding: HashMap<u16, Message>,
+    /// Responses that were read while waiting for a different one.
+    received: HashMap<u16, Message>,
+}
+
+impl TcpConnection {
+    /// Connects to `server`. The timeout applies to connecting and to each read and write after.
+    pub fn connect(server: SocketAddr, timeout: Duration) -> Result<Self, ClientError> {
+        Ok(Self::new(Stream::Tcp(open_socket(server, timeout)?)))
+    }
+
+    /// Connects to a DNS over TLS server (RFC 7858) and does the TLS handshake, authenticating
+    /// the server as `tls` says to.
+    #[cfg(feature = "tls")]
+    pub fn connect_tls(
+        server: SocketAddr,
+        timeout: Duration,
+        tls: &super::TlsConfig,
+    ) -> Result<Self, ClientError> {
+        let (connection, socket) = handshake(server, timeout, tls)?;
+        let stream = rustls::StreamOwned::new(connection, socket);
+        Ok(Self::new(Stream::Tls(Box::new(stream))))
+    }
+
+    fn new(stream: Stream) -> Self {
+        Self {
+            stream,
+            outstanding: HashMap::new(),
+            received: HashMap::new(),
+        }
+    }
+
+    /// Sends a query without waiting for its response.
+    pub fn send(&mut self, query: &Message) -> Result<(), ClientError> {
+        let id = query.header().id();
+        if self.outstanding.contains_key(&id) {
+            return Err(ClientError::IdInUse(id));
+        }
+        write_message(&mut self.stream, &Vec::try_from(query)?).map_err(super::io_error)?;
+        self.outstanding.insert(id, query.clone());
+        Ok(())
+    }
+
+    /// Waits for the response to any of the queries that have been sent.
+    pub fn receive(&mut self) -> Result<Message, ClientError> {
+        if let Some(id) = self.received.keys().next().copied() {
+            return Ok(self.received.remove(&id).unwrap());
+        }
+        self.read_response()
+    }
+
+    /// Sends `query` and waits for its response. Responses to other queries that arrive first are
+    /// kept for [`receive`](Self::receive).
+    pub fn query(&mut self, query: &Message) -> Result<Message, ClientError> {
+        self.send(query)?;
+        self.receive_for(query.header().id())
+    }
+
+    /// Sends `query` and returns the messages its response is split over, for zone transfers
+    /// (RFC 5936 §2.2) and anything else that answers with more than one message.
+    ///
+    /// For AXFR the messages end with the one holding the closing SOA record. Otherwise they go
+    /// on until the server closes the connection, so it's up to the caller to stop when it has
+    /// what it needs.
+    pub fn query_stream(&mut self, query: &Message) -> Result<Responses<'_>, ClientError> {
+        self.send(query)?;
+        Ok(Responses {
+            query: query.clone(),
+            connection: self,
+            first: true,
+            soa_count: 0,
+            done: false,
+        })
+    }
+
+    fn receive_for(&mut self, id: u16) -> Result<Message, ClientError> {
+        if let Some(response) = self.received.remove(&id) {
+            return Ok(response);
+        }
+        loop {
+            let response = self.read_response()?;
+            if response.header().id() == id {
+                return Ok(response);
+            }
+            self.received.insert(response.header().id(), response);
+        }
+    }
+
+    /// Reads messages until one answers an outstanding query, which then stops being outstanding.
+    fn read_response(&mut self) -> Result<Message, ClientError> {
+        loop {
+            let response = self
+                .read()?
+                .ok_or_else(|| ClientError::Io(ErrorKind::UnexpectedEof.into()))?;
+            let id = response.header().id();
+            match self.outstanding.get(&id) {
+                Some(query) if super::is_response_to(&response, query) => {
+                    self.outstanding.remove(&id);
+                    return Ok(response);
+                }
+                _ => tracing::debug!(id, "ignoring unrelated response"),
+            }
+        }
+    }
+
+    fn read(&mut self) -> Result<Option<Message>, ClientError> {
+        match read_message(&mut self.stream).map_err(super::io_error)? {
+            Some(bytes) => Ok(Some(Message::try_from(&bytes[..])?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A connection that several threads can send queries over at once, which is how
+/// [`Client`](super::Client) shares its connections.
+///
+/// Writing and reading are locked separately, so a query can be sent while other threads are
+/// waiting for their responses (RFC 7766 §6.2.1.1). One of the waiting threads reads the next
+/// response and hands it to whichever thread it's for.
+#[derive(Debug)]
+pub(crate) struct SharedConnection {
+    writer: Mutex<Stream>,
+    reader: Mutex<Stream>,
+    pending: Mutex<Pending>,
+    /// Signalled whenever a response has been read, or reading has failed.
+    arrived: Condvar,
+    timeout: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+    /// Queries that have been sent and not answered yet, by the id they were sent with.
+    outstanding: HashMap<u16, Message>,
+    /// Responses waiting for their thread to take them.
+    received: HashMap<u16, Message>,
+    /// Why the connection can't be used any more, once it can't.
+    closed: Option<ErrorKind>,
+}
+
+impl SharedConnection {
+    /// Connects to `server`. The timeout applies to connecting and to waiting for each response.
+    pub(crate) fn connect(server: SocketAddr, timeout: Duration) -> Result<Self, ClientError> {
+        let socket = open_socket(server, timeout)?;
+        let writer = socket.try_clone()?;
+        Ok(Self::new(Stream::Tcp(writer), Stream::Tcp(socket), timeout))
+    }
+
+    /// Connects to a DNS over TLS server (RFC 7858), like
+    /// [`TcpConnection::connect_tls`].
+    #[cfg(feature = "tls")]
+    pub(crate) fn connect_tls(
+        server: SocketAddr,
+        timeout: Duration,
+        tls: &super::TlsConfig,
+    ) -> Result<Self, ClientError> {
+        let (connection, socket) = handshake(server, timeout, tls)?;
+        let stream = super::tls::SharedTls::new(connection, socket);
+        Ok(Self::new(
+            Stream::SharedTls(stream.clone()),
+            Stream::SharedTls(stream),
+            timeout,
+        ))
+    }
+
+    fn new(writer: Stream, reader: Stream, timeout: Duration) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            reader: Mutex::new(reader),
+            pending: Mutex::default(),
+            arrived: Condvar::new(),
+            timeout,
+        }
+    }
+
+    /// Whether reading or writing has failed, or the server has closed the connection.
+    pub(crate) fn is_closed(&self) -> bool {
+        lock(&self.pending).closed.is_some()
+    }
+
+    /// Sends `query` and waits for its response. If another query on the connection is already
+    /// waiting with the same id, this one is sent with an unused one, and the response is given
+    /// the query's own id.
+    pub(crate) fn query(&self, query: &Message) -> Result<Message, ClientError> {
+        let id = query.header().id();
+        let mut query = query.clone();
+        {
+            let mut pending = lock(&self.pending);
+            if let Some(kind) = pending.closed {
+                return Err(super::io_error(kind.into()));
+            }
+            if pending.outstanding.len() > u16::MAX as usize {
+                return Err(ClientError::IdInUse(id));
+            }
+            while pending.outstanding.contains_key(&query.header().id()) {
+                query.set_id(rand::random());
+            }
+            pending
+                .outstanding
+                .insert(query.header().id(), query.clone());
+        }
+
+        let sent_id = query.header().id();
+        if let Err(error) = self.send(&query) {
+            let mut pending = lock(&self.pending);
+            pending.outstanding.remove(&sent_id);
+            return Err(match error {
+                ClientError::Io(error) => {
+                    // Part of the message may have been written, so nothing else can follow it.
+                    pending.closed = Some(error.kind());
+                    self.arrived.notify_all();
+                    super::io_error(error)
+                }
+                error => error,
+            });
+        }
+        let mut response = self.receive_for(sent_id)?;
+        response.set_id(id);
+        Ok(response)
+    }
+
+    fn send(&self, query: &Message) -> Result<(), ClientError> {
+        let bytes = Vec::try_from(query)?;
+        write_message(&mut *lock(&self.writer), &bytes).map_err(ClientError::Io)
+    }
+
+    /// Waits for the response to the query sent with `id`, reading responses from the connection
+    /// if no other thread is.
+    fn receive_for(&self, id: u16) -> Result<Message, ClientError> {
+        let deadline = Instant::now() + self.timeout;
+        let mut pending = lock(&self.pending);
+        loop {
+            if let Some(response) = pending.received.remove(&id) {
+                return Ok(response);
+            }
+            if let Some(kind) = pending.closed {
+                pending.outstanding.remove(&id);
+                return Err(super::io_error(kind.into()));
+            }
+
+            let reader = match self.reader.try_lock() {
+                Ok(reader) => Some(reader),
+                Err(TryLockError::Poisoned(error)) => Some(error.into_inner()),
+                Err(TryLockError::WouldBlock) => None,
+            };
+            match reader {
+                Some(mut reader) => {
+                    drop(pending);
+                    let result = read_message(&mut *reader);
+                    pending = lock(&self.pending);
+                    match result {
+                        Ok(Some(bytes)) => pending.deliver(&bytes),
+                        Ok(None) => pending.closed = Some(ErrorKind::UnexpectedEof),
+                        Err(error) => pending.closed = Some(error.kind()),
+                    }
+                    // Let go of the reader first, so a woken thread can take over reading.
+                    drop(reader);
+                    self.arrived.notify_all();
+                }
+                None => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        pending.outstanding.remove(&id);
+                        return Err(ClientError::Timeout);
+                    }
+                    pending = self
+                        .arrived
+                        .wait_timeout(pending, deadline - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+            }
+        }
+    }
+}
+
+impl Pending {
+    /// Keeps a response for the thread whose query it answers.
+    fn deliver(&mut self, bytes: &[u8]) {
+        let response = match Message::try_from(bytes) {
+            Ok(response) => response,
+            Err(error) => return tracing::debug!(%error, "ignoring response that doesn't parse"),
+        };
+        let id = response.header().id();
+        match self.outstanding.get(&id) {
+            Some(query) if super::is_response_to(&response, query) => {
+                self.outstanding.remove(&id);
+                self.received.insert(id, response);
+            }
+            _ => tracing::debug!(id, "ignoring unrelated response"),
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn open_socket(server: SocketAddr, timeout: Duration) -> Result<TcpStream, ClientError> {
+    let socket = TcpStream::connect_timeout(&server, timeout).map_err(super::io_error)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.set_write_timeout(Some(timeout))?;
+    socket.set_nodelay(true)?;
+    Ok(socket)
+}
+
+/// Connects to a DNS over TLS server and does the handshake.
+#[cfg(feature = "tls")]
+fn handshake(
+    server: SocketAddr,
+    timeout: Duration,
+    tls: &super::TlsConfig,
+) -> Result<(rustls::ClientConnection, TcpStream), ClientError> {
+    let mut socket = open_socket(server, timeout)?;
+    let mut connection =
+        rustls::ClientConnection::new(tls.client_config(b"dot")?, tls.server_name(server)?)?;
+    while connection.is_handshaking() {
+        connection
+            .complete_io(&mut socket)
+            .map_err(super::tls::tls_error)?;
+    }
+    Ok((connection, socket))
+}
+
+/// The connection underneath a [`TcpConnection`], which is the same length-prefixed messages
+/// whether or not it's encrypted.
+#[derive(Debug)]
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+    /// One side of a TLS connection a [`SharedConnection`] reads and writes from different
+    /// threads.
+    #[cfg(feature = "tls")]
+    SharedTls(super::tls::SharedTls),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            Stream::SharedTls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            Stream::SharedTls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            Stream::SharedTls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// The messages of a response that spans several, from [`TcpConnection::query_stream`].
+#[derive(Debug)]
+pub struct Responses<'c> {
+    connection: &'c mut TcpConnection,
+    query: Message,
+    first: bool,
+    soa_count: usize,
+    done: bool,
+}
+
+impl Responses<'_> {
+    /// Reads the next message with the query's id. Only the first has to repeat the question
+    /// (RFC 5936 §2.2.1), so the rest are matched on id alone.
+    fn next_message(&mut self) -> Result<Option<Message>, ClientError> {
+        let id = self.query.header().id();
+        if self.first {
+            return self.connection.receive_for(id).map(Some);
+        }
+        loop {
+            let Some(message) = self.connection.read()? else {
+                return Ok(None);
+            };
+            let other_id = message.header().id();
+            if other_id == id {
+                return Ok(Some(message));
+            }
+            match self.connection.outstanding.get(&other_id) {
+                Some(query) if super::is_response_to(&message, query) => {
+                    self.connection.outstanding.remove(&other_id);
+                    self.connection.received.insert(other_id, message);
+                }
+                _ => tracing::debug!(id = other_id, "ignoring unrelated response"),
+            }
+        }
+    }
+}
+
+impl Iterator for Responses<'_> {
+    type Item = Result<Message, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let message = match self.next_message() {
+            Ok(Some(message)) => message,
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(error) => {
+                self.done = true;
+                return Some(Err(error));
+            }
+        };
+        self.first = false;
+
+        let is_axfr = self
+            .query
+            .questions()
+            .first()
+            .is_some_and(|q| matches!(q.question_type(), Type::AXFR));
+        self.soa_count += message
+            .answers()
+            .iter()
+            .filter(|r| matches!(r.rdata(), RecordData::SOA(_)))
+            .count();
+        if !matches!(message.response_code(), crate::header::ReturnCode::NoError)
+            || (is_axfr && self.soa_count >= 2)
+        {
+            self.done = true;
+        }
+        Some(Ok(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, TcpListener, UdpSocket};
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::message::MessageBuilder;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn query(id: u16, question: &str) -> Message {
+        Message::builder()
+            .with_id(id)
+            .add_question(question.parse().unwrap())
+            .build()
+    }
+
+    fn listener() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        (listener, address)
+    }
+
+    fn receive(stream: &mut TcpStream) -> Message {
+        let bytes = read_message(stream).unwrap().unwrap();
+        Message::try_from(&bytes[..]).unwrap()
+    }
+
+    fn send(stream: &mut TcpStream, message: MessageBuilder) {
+        write_message(stream, &message.into_bytes().unwrap()).unwrap();
+    }
+
+    fn answer(query: &Message, record: &str) -> MessageBuilder {
+        Message::response_to(query).add_answer(record.parse().unwrap())
+    }
+
+    #[test]
+    fn matches_responses_that_arrive_out_of_order() {
+        let (listener, address) = listener();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let queries: Vec<Message> = (0..3).map(|_| receive(&mut stream)).collect();
+            for query in queries.iter().rev() {
+                let name = query.questions()[0].name();
+                send(
+                    &mut stream,
+                    answer(query, &format!("{name} 60 A 192.0.2.1")),
+                );
+            }
+        });
+
+        let mut connection = TcpConnection::connect(address, TIMEOUT).unwrap();
+        connection.send(&query(1, "one.example. A")).unwrap();
+        connection.send(&query(2, "two.example. A")).unwrap();
+        let response = connection.query(&query(3, "three.example. A")).unwrap();
+        assert_eq!(response.header().id(), 3);
+        assert_eq!(response.answers()[0].name().to_string(), "three.example.");
+
+        let mut ids: Vec<u16> = (0..2)
+            .map(|_| connection.receive().unwrap().header().id())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, [1, 2]);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn rejects_ids_already_in_flight() {
+        let (_listener, address) = listener();
+        let mut connection = TcpConnection::connect(address, TIMEOUT).unwrap();
+        connection.send(&query(1, "one.example. A")).unwrap();
+        assert!(matches!(
+            connection.send(&query(1, "two.example. A")),
+            Err(ClientError::IdInUse(1))
+        ));
+    }
+
+    #[test]
+    fn ignores_responses_to_other_questions() {
+        let (listener, address) = listener();
+        let other = query(1, "other.example. A");
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let query = receive(&mut stream);
+            send(
+                &mut stream,
+                answer(&other, "other.example. 60 A 192.0.2.66"),
+            );
+            send(&mut stream, answer(&query, "one.example. 60 A 192.0.2.1"));
+        });
+
+        let mut connection = TcpConnection::connect(address, TIMEOUT).unwrap();
+        let response = connection.query(&query(1, "one.example. A")).unwrap();
+        assert_eq!(response.answers()[0].rdata().to_string(), "192.0.2.1");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn retries_truncated_responses_over_tcp() {
+        let (listener, address) = listener();
+        let udp = UdpSocket::bind(address).unwrap();
+        let udp_server = thread::spawn(move || {
+            let mut buffer = [0; 512];
+            let (length, client) = udp.recv_from(&mut buffer).unwrap();
+            let query = Message::try_from(&buffer[..length]).unwrap();
+            let truncated = Message::response_to(&query).with_truncation(true);
+            udp.send_to(&truncated.into_bytes().unwrap(), client)
+                .unwrap();
+        });
+        let tcp_server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let query = receive(&mut stream);
+            send(&mut stream, answer(&query, "example. 60 TXT \"long\""));
+        });
+
+        let response = Client::new(address)
+            .with_timeout(TIMEOUT)
+            .query(&query(1, "example. TXT"))
+            .unwrap();
+        assert!(!response.header().truncation());
+        assert_eq!(response.answers().len(), 1);
+        udp_server.join().unwrap();
+        tcp_server.join().unwrap();
+    }
+
+    #[test]
+    fn reuses_connections_and_reconnects_once_closed() {
+        let (listener, address) = listener();
+        let server = thread::spawn(move || {
+            // The first connection answers two queries and is then closed, as servers do with
+            // idle connections.
+            for queries in [2, 1] {
+                let (mut stream, _) = listener.accept().unwrap();
+                for _ in 0..queries {
+                    let query = receive(&mut stream);
+                    send(&mut stream, answer(&query, "example. 60 A 192.0.2.1"));
+                }
+            }
+        });
+
+        let client = Client::new(address).with_timeout(TIMEOUT).with_tcp(true);
+        for id in 1..=3 {
+            let response = client.query(&query(id, "example. A")).unwrap();
+            assert_eq!(response.header().id(), id);
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn concurrent_queries_are_pipelined_on_one_connection() {
+        let (listener, address) = listener();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Neither query is answered until both have arrived, and then in reverse order.
+            let queries: Vec<Message> = (0..2).map(|_| receive(&mut stream)).collect();
+            for query in queries.iter().rev() {
+                let name = query.questions()[0].name();
+                send(
+                    &mut stream,
+                    answer(query, &format!("{name} 60 A 192.0.2.1")),
+                );
+            }
+            listener.set_nonblocking(true).unwrap();
+            assert!(listener.accept().is_err());
+            queries.iter().map(|q| q.header().id()).collect::<Vec<_>>()
+        });
+
+        let client = Client::new(address).with_timeout(TIMEOUT).with_tcp(true);
+        let threads: Vec<_> = ["one.example.", "two.example."]
+            .into_iter()
+            .map(|name| {
+                let client = client.clone();
+                thread::spawn(move || {
+                    let response = client.query(&query(7, &format!("{name} A"))).unwrap();
+                    assert_eq!(response.header().id(), 7);
+                    assert_eq!(response.answers()[0].name().to_string(), name);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // The second query couldn't be sent with the id the first was waiting with.
+        let ids = server.join().unwrap();
+        assert_ne!(ids[0], ids[1]);
+        assert!(ids.contains(&7));
+    }
+
+    #[test]
+    fn zone_transfers_end_at_the_second_soa() {
+        let (listener, address) = listener();
+        let (finished, wait) = mpsc::channel::<()>();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let query = receive(&mut stream);
+            let soa = "example. 60 SOA ns.example. hostmaster.example. 1 7200 3600 1209600 300";
+            let rest = || {
+                Message::builder()
+                    .with_id(query.header().id())
+                    .with_response(true)
+            };
+            send(
+                &mut stream,
+                answer(&query, soa).add_answer("example. 60 NS ns.example.".parse().unwrap()),
+            );
+            send(
+                &mut stream,
+                rest().add_answer("ns.example. 60 A 192.0.2.1".parse().unwrap()),
+            );
+            send(&mut stream, rest().add_answer(soa.parse().unwrap()));
+            // The connection stays open, so reading any further would wait for the timeout.
+            wait.recv().unwrap();
+        });
+
+        let mut connection = TcpConnection::connect(address, TIMEOUT).unwrap();
+        let messages: Vec<Message> = connection
+            .query_stream(&query(1, "example. AXFR"))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2].answers().len(), 1);
+        finished.send(()).unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn streams_end_on_an_error_or_when_the_server_closes() {
+        let (listener, address) = listener();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let query = receive(&mut stream);
+            send(
+                &mut stream,
+                Message::response_to(&query).with_response_code(crate::header::ReturnCode::Refused),
+            );
+            let query = receive(&mut stream);
+            for _ in 0..2 {
+                send(&mut stream, answer(&query, "example. 60 TXT \"part\""));
+            }
+        });
+
+        let mut connection = TcpConnection::connect(address, TIMEOUT).unwrap();
+        let refused = connection.query_stream(&query(1, "example. AXFR")).unwrap();
+        assert_eq!(refused.count(), 1);
+        let messages = connection.query_stream(&query(2, "example. TXT")).unwrap();
+        assert_eq!(messages.map(Result::unwrap).count(), 2);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,469 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::WebPkiSupportedAlgorithms;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+
+use crate::error::ClientError;
+
+/// How to authenticate servers for DNS over TLS (RFC 7858) and DNS over HTTPS (RFC 8484).
+///
+/// Servers are checked against the trust roots for the authentication name, or if no name is set,
+/// their IP address for DNS over TLS and the host in the URI for DNS over HTTPS. If SPKI pins are
+/// added, the server's key has to match one of them as well. With pins and no trust roots the
+/// pins are all that's checked, which is the out-of-band key-pinned profile of RFC 7858 §4.2.
+#[derive(Clone, Debug, Default)]
+pub struct TlsConfig {
+    roots: Vec<CertificateDer<'static>>,
+    server_name: Option<String>,
+    spki_pins: Vec<[u8; 32]>,
+}
+
+impl TlsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts servers whose certificates chain up to `certificate`, given in DER.
+    pub fn with_root_certificate(mut self, certificate: impl Into<Vec<u8>>) -> Self {
+        self.roots.push(CertificateDer::from(certificate.into()));
+        self
+    }
+
+    /// Sets the name the server's certificate has to be for, which is also sent as the SNI.
+    pub fn with_server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// Adds the SHA-256 hash of a SubjectPublicKeyInfo the server's key is allowed to have
+    /// (RFC 7858 §4.2). [`spki_pin`](Self::spki_pin) works it out from a certificate.
+    pub fn with_spki_pin(mut self, sha256: [u8; 32]) -> Self {
+        self.spki_pins.push(sha256);
+        self
+    }
+
+    /// The SPKI pin for a DER certificate, or `None` if it can't be parsed.
+    pub fn spki_pin(certificate: &[u8]) -> Option<[u8; 32]> {
+        let certificate = CertificateDer::from(certificate);
+        let certificate = webpki::EndEntityCert::try_from(&certificate).ok()?;
+        let digest = ring::digest::digest(
+            &ring::digest::SHA256,
+            certificate.subject_public_key_info().as_ref(),
+        );
+        digest.as_ref().try_into().ok()
+    }
+
+    /// The rustls configuration for connecting to a server that speaks the `alpn` protocol.
+    pub(crate) fn client_config(&self, alpn: &[u8]) -> Result<Arc<ClientConfig>, rustls::Error> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+        let mut roots = RootCertStore::empty();
+        for root in self.roots.iter() {
+            roots.add(root.clone())?;
+        }
+        let webpki = match roots.is_empty() {
+            true => None,
+            false => Some(
+                WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                    .build()
+                    .map_err(|e| rustls::Error::General(e.to_string()))?,
+            ),
+        };
+        if webpki.is_none() && self.spki_pins.is_empty() {
+            return Err(rustls::Error::General(
+                "no trust roots or SPKI pins to authenticate the server with".to_string(),
+            ));
+        }
+
+        let verifier = Verifier {
+            webpki,
+            spki_pins: self.spki_pins.clone(),
+            algorithms: provider.signature_verification_algorithms,
+        };
+        let mut config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_no_client_auth();
+        config.alpn_protocols = vec![alpn.to_vec()];
+        Ok(Arc::new(config))
+    }
+
+    pub(crate) fn server_name(
+        &self,
+        server: SocketAddr,
+    ) -> Result<ServerName<'static>, rustls::Error> {
+        match &self.server_name {
+            Some(name) => ServerName::try_from(name.clone())
+                .map_err(|_| rustls::Error::General(format!("{name} isn't a valid server name"))),
+            None => Ok(ServerName::IpAddress(server.ip().into())),
+        }
+    }
+
+    /// The name to authenticate a server as, which is `host` unless a name has been set.
+    #[cfg(feature = "https")]
+    pub(crate) fn server_name_or(&self, host: &str) -> Result<ServerName<'static>, rustls::Error> {
+        let name = self.server_name.as_deref().unwrap_or(host);
+        ServerName::try_from(name.to_string())
+            .map_err(|_| rustls::Error::General(format!("{name} isn't a valid server name")))
+    }
+}
+
+/// Checks the certificate chain if there are trust roots, then the key if there are pins.
+#[derive(Debug)]
+struct Verifier {
+    webpki: Option<Arc<WebPkiServerVerifier>>,
+    spki_pins: Vec<[u8; 32]>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(webpki) = &self.webpki {
+            webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            )?;
+        }
+        if !self.spki_pins.is_empty() {
+            let pin = TlsConfig::spki_pin(end_entity).ok_or(rustls::Error::InvalidCertificate(
+                CertificateError::BadEncoding,
+            ))?;
+            if !self.spki_pins.contains(&pin) {
+                return Err(rustls::Error::InvalidCertificate(
+                    CertificateError::ApplicationVerificationFailure,
+                ));
+            }
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+/// Gets the TLS error back out of the I/O error rustls wraps it in, so that a certificate that
+/// doesn't check out is reported as such.
+pub(crate) fn tls_error(error: std::io::Error) -> ClientError {
+    match error
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+    {
+        Some(tls) => ClientError::Tls(tls.clone()),
+        None => super::io_error(error),
+    }
+}
+
+/// A TLS connection that can be read on one thread while it's written on others, which
+/// [`rustls::StreamOwned`] can't do as it needs the whole connection for either. Waiting for data
+/// from the socket happens outside the lock on the TLS state, so it doesn't hold up writes.
+#[derive(Clone, Debug)]
+pub(crate) struct SharedTls {
+    connection: Arc<Mutex<rustls::ClientConnection>>,
+    socket: Arc<TcpStream>,
+}
+
+impl SharedTls {
+    /// Takes over a connection that has finished its handshake.
+    pub(crate) fn new(connection: rustls::ClientConnection, socket: TcpStream) -> Self {
+        Self {
+            connection: Arc::new(Mutex::new(connection)),
+            socket: Arc::new(socket),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, rustls::ClientConnection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sends whatever TLS records the connection has ready, such as alerts or key updates.
+    fn write_records(&self, connection: &mut rustls::ClientConnection) -> std::io::Result<()> {
+        while connection.wants_write() {
+            connection.write_tls(&mut &*self.socket)?;
+        }
+        Ok(())
+    }
+}
+
+impl Read for SharedTls {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut records = [0; 4096];
+        loop {
+            match self.lock().reader().read(buf) {
+                Err(error) if error.kind() == ErrorKind::WouldBlock => {}
+                result => return result,
+            }
+
+            let length = (&*self.socket).read(&mut records)?;
+            let mut connection = self.lock();
+            let mut records = &records[..length];
+            // Reading nothing tells rustls the socket has closed, so that the plaintext reader
+            // can say whether it was closed cleanly.
+            loop {
+                connection.read_tls(&mut records)?;
+                connection
+                    .process_new_packets()
+                    .map_err(|error| std::io::Error::new(ErrorKind::InvalidData, error))?;
+                if records.is_empty() {
+                    break;
+                }
+            }
+            self.write_records(&mut connection)?;
+        }
+    }
+}
+
+impl Write for SharedTls {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut connection = self.lock();
+        let written = connection.writer().write(buf)?;
+        self.write_records(&mut connection)?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut connection = self.lock();
+        connection.writer().flush()?;
+        self.write_records(&mut connection)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::thread;
+    use std::time::Duration;
+
+    use rustls::pki_types::PrivateKeyDer;
+    use rustls::ServerConfig;
+
+    use super::*;
+    use crate::client::tcp::{read_message, write_message};
+    use crate::client::TcpConnection;
+    use crate::message::Message;
+
+    /// A self-signed certificate for `dns.example` and 127.0.0.1, with its private key. The
+    /// common name tells certificates apart, so one can't pass for another's issuer.
+    pub(crate) fn certificate(
+        common_name: &str,
+    ) -> (CertificateDer<'static>, PrivateKeyDer<'static>) {
+        let names = vec!["dns.example".to_string(), "127.0.0.1".to_string()];
+        let mut params = rcgen::CertificateParams::new(names).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, common_name);
+        let key = rcgen::KeyPair::generate().unwrap();
+        let certificate = params.self_signed(&key).unwrap();
+        let key = PrivateKeyDer::try_from(key.serialize_der()).unwrap();
+        (certificate.der().clone(), key)
+    }
+
+    /// A server configuration for TLS 1.3 only, which is all DNS over QUIC allows.
+    pub(crate) fn server_config(
+        certificate: &CertificateDer<'static>,
+        key: &PrivateKeyDer<'static>,
+        alpn: &[u8],
+    ) -> ServerConfig {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = ServerConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certificate.clone()], key.clone_key())
+            .unwrap();
+        config.alpn_protocols = vec![alpn.to_vec()];
+        config
+    }
+
+    /// Starts a DNS over TLS server that answers every query with an empty response.
+    fn serve(certificate: &CertificateDer<'static>, key: &PrivateKeyDer<'static>) -> SocketAddr {
+        let config = Arc::new(server_config(certificate, key, b"dot"));
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let connection = rustls::ServerConnection::new(config.clone()).unwrap();
+                let mut stream = rustls::StreamOwned::new(connection, stream.unwrap());
+                while let Ok(Some(bytes)) = read_message(&mut stream) {
+                    let query = Message::try_from(&bytes[..]).unwrap();
+                    let response = Message::response_to(&query).into_bytes().unwrap();
+                    if write_message(&mut stream, &response).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        address
+    }
+
+    fn query(server: SocketAddr, tls: &TlsConfig) -> Result<Message, ClientError> {
+        let mut connection = TcpConnection::connect_tls(server, Duration::from_secs(5), tls)?;
+        let query = Message::builder()
+            .add_question("example. A".parse().unwrap())
+            .build();
+        connection.query(&query)
+    }
+
+    fn certificate_error(result: Result<Message, ClientError>) -> CertificateError {
+        match result {
+            Err(ClientError::Tls(rustls::Error::InvalidCertificate(error))) => error,
+            other => panic!("expected a certificate error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn trusts_certificates_from_the_roots() {
+        let (certificate, key) = certificate("server");
+        let server = serve(&certificate, &key);
+        let tls = TlsConfig::new().with_root_certificate(certificate.to_vec());
+
+        // Without a name, the certificate has to be for the server's address.
+        query(server, &tls).unwrap();
+        query(server, &tls.clone().with_server_name("dns.example")).unwrap();
+    }
+
+    #[test]
+    fn rejects_certificates_for_other_names() {
+        let (certificate, key) = certificate("server");
+        let server = serve(&certificate, &key);
+        let tls = TlsConfig::new()
+            .with_root_certificate(certificate.to_vec())
+            .with_server_name("other.example");
+
+        assert!(matches!(
+            certificate_error(query(server, &tls)),
+            CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_certificates_from_other_roots() {
+        let (other, _) = certificate("other");
+        let (certificate, key) = certificate("server");
+        let server = serve(&certificate, &key);
+        let tls = TlsConfig::new().with_root_certificate(other.to_vec());
+
+        assert!(matches!(
+            certificate_error(query(server, &tls)),
+            CertificateError::UnknownIssuer
+        ));
+    }
+
+    #[test]
+    fn pins_alone_authenticate_the_server() {
+        let (certificate, key) = certificate("server");
+        let server = serve(&certificate, &key);
+        let pin = TlsConfig::spki_pin(&certificate).unwrap();
+
+        // With no roots, the name isn't checked, only the key.
+        let tls = TlsConfig::new()
+            .with_spki_pin([0; 32])
+            .with_spki_pin(pin)
+            .with_server_name("other.example");
+        query(server, &tls).unwrap();
+    }
+
+    #[test]
+    fn rejects_keys_that_match_no_pin() {
+        let (other, _) = certificate("other");
+        let (certificate, key) = certificate("server");
+        let server = serve(&certificate, &key);
+        let wrong_pin = TlsConfig::spki_pin(&other).unwrap();
+
+        let tls = TlsConfig::new().with_spki_pin(wrong_pin);
+        assert!(matches!(
+            certificate_error(query(server, &tls)),
+            CertificateError::ApplicationVerificationFailure
+        ));
+
+        // A certificate the roots trust still has to match a pin as well.
+        let tls = tls.with_root_certificate(certificate.to_vec());
+        assert!(matches!(
+            certificate_error(query(server, &tls)),
+            CertificateError::ApplicationVerificationFailure
+        ));
+        let pin = TlsConfig::spki_pin(&certificate).unwrap();
+        query(server, &tls.with_spki_pin(pin)).unwrap();
+    }
+
+    #[test]
+    fn clients_share_a_connection_between_threads() {
+        let (certificate, key) = certificate("server");
+        let server = serve(&certificate, &key);
+        let tls = TlsConfig::new().with_root_certificate(certificate.to_vec());
+        let client = crate::client::Client::new(server)
+            .with_timeout(Duration::from_secs(5))
+            .with_tls(tls);
+
+        let threads: Vec<_> = (0..4)
+            .map(|id| {
+                let client = client.clone();
+                thread::spawn(move || {
+                    for _ in 0..5 {
+                        let query = Message::builder()
+                            .with_id(id)
+                            .add_question("example. A".parse().unwrap())
+                            .build();
+                        assert_eq!(client.query(&query).unwrap().header().id(), id);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn needs_roots_or_pins() {
+        let result = TlsConfig::new().client_config(b"dot");
+        assert!(matches!(result, Err(rustls::Error::General(_))));
+    }
+
+    #[test]
+    fn unwraps_tls_errors() {
+        let error = std::io::Error::new(ErrorKind::InvalidData, rustls::Error::DecryptError);
+        assert!(matches!(
+            tls_error(error),
+            ClientError::Tls(rustls::Error::DecryptError)
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::error::ClientError;
+use crate::message::Message;
+
+/// The largest UDP payload there can be, so that no response is cut short however big a buffer
+/// size the query advertised.
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+/// Sends `bytes`, the encoded `query`, to `server` from a fresh socket and waits for the response.
+///
+/// Datagrams from other addresses, and ones that aren't a response to `query`, are ignored so a
+/// spoofed packet can't cut the wait short (RFC 5452 §4). If something came from the server that
+/// couldn't be parsed and nothing better turned up, that's reported instead of a timeout.
+pub(super) fn exchange(
+    server: SocketAddr,
+    bytes: &[u8],
+    query: &Message,
+    timeout: Duration,
+) -> Result<Message, ClientError> {
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.send_to(bytes, server)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+    let mut bad_response = None;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(bad_response.unwrap_or(ClientError::Timeout));
+        }
+        socket.set_read_timeout(Some(remaining))?;
+
+        let (length, source) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Err(bad_response.unwrap_or(ClientError::Timeout));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if source != server {
+            tracing::debug!(%source, "ignoring datagram from the wrong address");
+            continue;
+        }
+
+        match Message::try_from(&buffer[..length]) {
+            Ok(response) if super::is_response_to(&response, query) => return Ok(response),
+            Ok(response) => {
+                tracing::debug!(id = response.header().id(), "ignoring unrelated response")
+            }
+            Err(error) => bad_response = Some(error.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::message::MessageBuilder;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn query(name: &str) -> Message {
+        Message::builder()
+            .with_id(0x1234)
+            .add_question(format!("{name} A").parse().unwrap())
+            .build()
+    }
+
+    /// Binds a socket on the loopback address to play the server.
+    fn responder() -> (UdpSocket, SocketAddr) {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = socket.local_addr().unwrap();
+        (socket, address)
+    }
+
+    fn receive(socket: &UdpSocket) -> (Message, SocketAddr) {
+        let mut buffer = [0; 512];
+        let (length, source) = socket.recv_from(&mut buffer).unwrap();
+        (Message::try_from(&buffer[..length]).unwrap(), source)
+    }
+
+    /// A response to `query` with a single A record, so it can be told apart from others.
+    fn answer(query: &Message, address: &str) -> MessageBuilder {
+        let name = query.questions()[0].name();
+        Message::response_to(query).add_answer(format!("{name} 60 A {address}").parse().unwrap())
+    }
+
+    fn address_in(response: &Message) -> String {
+        response.answers()[0].rdata().to_string()
+    }
+
+    #[test]
+    fn ignores_responses_to_other_queries() {
+        let (server, address) = responder();
+        let other_query = query("example.net.");
+        let query = query("example.com.");
+        let responder = thread::spawn(move || {
+            let (query, client) = receive(&server);
+            let wrong_id = answer(&query, "192.0.2.1").with_id(query.header().id() ^ 1);
+            let wrong_question = answer(&other_query, "192.0.2.1");
+            let not_a_response = answer(&query, "192.0.2.2").with_response(false);
+            for message in [wrong_id, wrong_question, not_a_response] {
+                server
+                    .send_to(&message.into_bytes().unwrap(), client)
+                    .unwrap();
+            }
+            let right = answer(&query, "192.0.2.3").into_bytes().unwrap();
+            server.send_to(&right, client).unwrap();
+        });
+
+        let response = exchange(address, &Vec::try_from(&query).unwrap(), &query, TIMEOUT).unwrap();
+        assert_eq!(address_in(&response), "192.0.2.3");
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn ignores_datagrams_from_other_addresses() {
+        let (server, address) = responder();
+        let (spoofer, _) = responder();
+        let query = query("example.com.");
+        let responder = thread::spawn(move || {
+            let (query, client) = receive(&server);
+            let spoofed = answer(&query, "192.0.2.66").into_bytes().unwrap();
+            spoofer.send_to(&spoofed, client).unwrap();
+            // Give the spoofed response time to arrive first.
+            thread::sleep(Duration::from_millis(50));
+            server
+                .send_to(&answer(&query, "192.0.2.1").into_bytes().unwrap(), client)
+                .unwrap();
+        });
+
+        let response = exchange(address, &Vec::try_from(&query).unwrap(), &query, TIMEOUT).unwrap();
+        assert_eq!(address_in(&response), "192.0.2.1");
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn times_out_without_a_response() {
+        let (_server, address) = responder();
+        let query = query("example.com.");
+        let started = Instant::now();
+        let result = exchange(
+            address,
+            &Vec::try_from(&query).unwrap(),
+            &query,
+            Duration::from_millis(100),
+        );
+        assert!(matches!(result, Err(ClientError::Timeout)));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn reports_unparsable_responses_instead_of_timing_out() {
+        let (server, address) = responder();
+        let query = query("example.com.");
+        let responder = thread::spawn(move || {
+            let mut buffer = [0; 512];
+            let (_, client) = server.recv_from(&mut buffer).unwrap();
+            server.send_to(b"\x12\x34\x80", client).unwrap();
+        });
+
+        let result = exchange(
+            address,
+            &Vec::try_from(&query).unwrap(),
+            &query,
+            Duration::from_millis(200),
+        );
+        assert!(matches!(result, Err(ClientError::BadResponse(_))));
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn retries_after_timeouts() {
+        let (server, address) = responder();
+        let responder = thread::spawn(move || {
+            // Only the third copy of the query gets answered.
+            for _ in 0..2 {
+                receive(&server);
+            }
+            let (query, client) = receive(&server);
+            server
+                .send_to(&answer(&query, "192.0.2.1").into_bytes().unwrap(), client)
+                .unwrap();
+        });
+
+        let client = Client::new(address)
+            .with_timeout(Duration::from_millis(100))
+            .with_attempts(3);
+        let response = client.query(&query("example.com.")).unwrap();
+        assert_eq!(address_in(&response), "192.0.2.1");
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        let (server, address) = responder();
+        let client = Client::new(address)
+            .with_timeout(Duration::from_millis(50))
+            .with_attempts(2);
+        let result = client.query(&query("example.com."));
+        assert!(matches!(result, Err(ClientError::Timeout)));
+
+        server
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let mut buffer = [0; 512];
+        let received = std::iter::from_fn(|| server.recv_from(&mut buffer).ok()).count();
+        assert_eq!(received, 2);
+    }
+
+    #[test]
+    fn moves_on_to_the_next_server() {
+        let (_silent, silent_address) = responder();
+        let (server, address) = responder();
+        let responder = thread::spawn(move || {
+            let (query, client) = receive(&server);
+            server
+                .send_to(&answer(&query, "192.0.2.1").into_bytes().unwrap(), client)
+                .unwrap();
+        });
+
+        let client = Client::new(silent_address)
+            .with_server(address)
+            .with_timeout(Duration::from_millis(100))
+            .with_attempts(1);
+        let response = client.query(&query("example.com.")).unwrap();
+        assert_eq!(address_in(&response), "192.0.2.1");
+        responder.join().unwrap();
+    }
+}
@@ -1,50 +1,82 @@
+use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
-use nom::IResult;
+use crate::error::{offset_in, ParseError, ParseErrorKind, ParseResult, TextError};
+use crate::presentation::unescape;
+
+/// The longest a name can be on the wire, counting length octets and the root (RFC 1035 §2.3.4).
+const MAX_LENGTH: usize = 255;
+const MAX_LABEL_LENGTH: usize = 63;
 
 #[derive(Clone, Debug)]
 pub struct DomainName {
-    labels: Vec<String>,
+    labels: Vec<Vec<u8>>,
 }
 
 impl DomainName {
-    pub fn new(labels: Vec<String>) -> Self {
-        Self { labels }
+    /// Creates a name from its labels, most specific first. Labels are raw octets, so anything
+    /// that can be turned into bytes will do, such as `String`s or `&str`s. Fails if a label is
+    /// empty or longer than 63 octets, or the name is longer than 255 octets on the wire.
+    pub fn new<L: Into<Vec<u8>>>(labels: impl IntoIterator<Item = L>) -> Result<Self, TextError> {
+        let name = Self {
+            labels: labels.into_iter().map(Into::into).collect(),
+        };
+        check_lengths(&name.labels).map_err(|error| error(name.to_string()))?;
+        Ok(name)
+    }
+
+    pub fn root() -> Self {
+        Self { labels: Vec::new() }
     }
 
-    pub fn labels(&self) -> &[String] {
+    pub fn labels(&self) -> &[Vec<u8>] {
         &self.labels
     }
 
+    /// Reads a possibly compressed name. Pointers are only followed if they point before
+    /// everything read so far, so each hop moves towards the start of the message and a chain of
+    /// them can't loop.
     #[tracing::instrument]
-    pub(crate) fn parse<'m>(
-        full_message: &'m [u8],
-    ) -> impl Fn(&'m [u8]) -> IResult<&'m [u8], Self, nom::error::Error<&[u8]>> {
-        move |i: &'m [u8]| {
-            let (i, (labels, terminator)) = nom::multi::many_till(
-                Element::parse_label,
-                nom::branch::alt((Element::parse_root, Element::parse_pointer)),
-            )(i)?;
-
-            match terminator {
-                Element::Root => Ok((
-                    i,
-                    DomainName::new(labels.iter().map(|e| e.to_string()).collect()),
-                )),
-                // This shouldn't ever happen: Domains should either terminate with a Root (zero length)
-                // byte, or with a pointer.
-                Element::Label(_) => Err(nom::Err::Failure(nom::error::Error::new(
-                    i,
-                    nom::error::ErrorKind::Verify,
-                ))),
-                Element::Pointer(p) => {
-                    let (_, domain_name) = Self::parse(full_message)(&full_message[p..])?;
-
-                    let mut labels: Vec<String> = labels.iter().map(|e| e.to_string()).collect();
-                    labels.append(&mut domain_name.labels().to_vec());
-
-                    Ok((i, DomainName::new(labels)))
+    pub(crate) fn parse<'m>(full_message: &'m [u8]) -> impl Fn(&'m [u8]) -> ParseResult<'m, Self> {
+        move |input: &'m [u8]| {
+            let mut labels: Vec<Vec<u8>> = Vec::new();
+            let mut length = 1;
+            let mut i = input;
+            let mut limit = offset_in(full_message, input);
+            // Where the caller carries on from: just after the first pointer, if there is one.
+            let mut end = None;
+
+            loop {
+                let (remaining, element) = Element::parse(i)?;
+                match element {
+                    Element::Label(label) => {
+                        length += label.len() + 1;
+                        if length > MAX_LENGTH {
+                            return Err(ParseError::failure(input, ParseErrorKind::NameTooLong));
+                        }
+                        labels.push(label);
+                    }
+                    Element::Root => {
+                        return Ok((end.unwrap_or(remaining), DomainName { labels }));
+                    }
+                    Element::Pointer(p) => {
+                        if p >= full_message.len() {
+                            return Err(ParseError::failure(
+                                i,
+                                ParseErrorKind::PointerOutOfRange(p),
+                            ));
+                        }
+                        if p >= limit {
+                            return Err(ParseError::failure(i, ParseErrorKind::PointerLoop));
+                        }
+                        end.get_or_insert(remaining);
+                        limit = p;
+                        i = &full_message[p..];
+                        continue;
+                    }
                 }
+                i = remaining;
             }
         }
     }
@@ -52,70 +84,381 @@ impl DomainName {
 
 impl Display for DomainName {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for l in self.labels.iter() {
-            write!(f, "{l}.").unwrap()
+        if self.labels.is_empty() {
+            return write!(f, ".");
+        }
+        for label in self.labels.iter() {
+            write_label(f, label)?;
+            write!(f, ".")?;
+        }
+        Ok(())
+    }
+}
+
+/// Displays a single label.
+struct Label<'l>(&'l [u8]);
+
+impl Display for Label<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_label(f, self.0)
+    }
+}
+
+/// Writes a label with dots, backslashes and characters that are special in zone files escaped,
+/// and anything that isn't printable ASCII as `\DDD` (RFC 1035 §5.1).
+fn write_label(f: &mut Formatter<'_>, label: &[u8]) -> std::fmt::Result {
+    for byte in label {
+        match byte {
+            b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                write!(f, "\\{}", *byte as char)?
+            }
+            0x21..=0x7E => write!(f, "{}", *byte as char)?,
+            _ => write!(f, "\\{byte:03}")?,
+        }
+    }
+    Ok(())
+}
+
+impl DomainName {
+    /// Reads a name in presentation format. Names ending in a dot are absolute, and other names
+    /// have `origin` appended to them. `@` on its own stands for the origin.
+    pub fn from_str_relative(s: &str, origin: &DomainName) -> Result<Self, TextError> {
+        if s == "@" {
+            return Ok(origin.clone());
+        }
+        if s == "." {
+            return Ok(Self::root());
+        }
+
+        let mut labels = Vec::new();
+        let mut label = Vec::new();
+        let mut absolute = false;
+        let mut bytes = s.as_bytes().iter();
+        while let Some(byte) = bytes.next() {
+            absolute = false;
+            match byte {
+                b'.' if label.is_empty() => return Err(TextError::EmptyLabel(s.to_string())),
+                b'.' => {
+                    labels.push(std::mem::take(&mut label));
+                    absolute = true;
+                }
+                b'\\' => {
+                    label.push(unescape(&mut bytes).ok_or(TextError::BadEscape(s.to_string()))?)
+                }
+                _ => label.push(*byte),
+            }
+        }
+        if !label.is_empty() {
+            labels.push(label);
+        } else if labels.is_empty() {
+            return Err(TextError::EmptyLabel(s.to_string()));
         }
-        write!(f, "")
+        if !absolute {
+            labels.extend(origin.labels.iter().cloned());
+        }
+
+        check_lengths(&labels).map_err(|error| error(s.to_string()))?;
+        Ok(Self { labels })
+    }
+}
+
+/// Checks labels against the limits of RFC 1035 §2.3.4, returning the error to report with the
+/// name's text if they're broken.
+fn check_lengths(labels: &[Vec<u8>]) -> Result<(), fn(String) -> TextError> {
+    if labels.iter().any(|l| l.is_empty()) {
+        return Err(TextError::EmptyLabel);
+    }
+    if labels.iter().any(|l| l.len() > MAX_LABEL_LENGTH) {
+        return Err(TextError::LabelTooLong);
+    }
+    if labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1 > MAX_LENGTH {
+        return Err(TextError::NameTooLong);
+    }
+    Ok(())
+}
+
+impl DomainName {
+    /// Compares names in the canonical order of RFC 4034 §6.1: label by label starting from the
+    /// root, ignoring ASCII case.
+    pub fn cmp_canonical(&self, other: &DomainName) -> Ordering {
+        let lowercase = |label: &Vec<u8>| label.to_ascii_lowercase();
+        self.labels
+            .iter()
+            .rev()
+            .map(lowercase)
+            .cmp(other.labels.iter().rev().map(lowercase))
+    }
+
+    /// Writes the name in presentation format relative to `origin` if it's inside it, as `@` if
+    /// it's the origin itself, and in full otherwise. The inverse of
+    /// [`from_str_relative`](Self::from_str_relative).
+    pub fn to_string_relative(&self, origin: &DomainName) -> String {
+        let inside = self.labels.len() >= origin.labels.len()
+            && self.labels[self.labels.len() - origin.labels.len()..]
+                .iter()
+                .zip(origin.labels.iter())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b));
+        if !inside || origin.labels.is_empty() {
+            return self.to_string();
+        }
+
+        let prefix = &self.labels[..self.labels.len() - origin.labels.len()];
+        if prefix.is_empty() {
+            return "@".to_string();
+        }
+        itertools::join(prefix.iter().map(|l| Label(l).to_string()), ".")
+    }
+}
+
+/// Names are equal if their labels are, ignoring ASCII case (RFC 4343 §3).
+impl PartialEq for DomainName {
+    fn eq(&self, other: &Self) -> bool {
+        self.labels.len() == other.labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(other.labels.iter())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl Eq for DomainName {}
+
+/// Reads a name in presentation format, treating relative names as if they were absolute.
+impl FromStr for DomainName {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str_relative(s, &Self::root())
     }
 }
 
 impl From<DomainName> for Vec<u8> {
     fn from(value: DomainName) -> Self {
-        let mut bytes: Vec<u8> = Vec::with_capacity(256);
+        Vec::from(&value)
+    }
+}
 
-        for name in value.labels {
-            bytes.push(name.len() as u8);
-            bytes.extend_from_slice(name.as_bytes());
-        }
-        bytes.push(0);
-        bytes
+impl From<&DomainName> for Vec<u8> {
+    fn from(value: &DomainName) -> Self {
+        let mut encoder = crate::encoder::Encoder::new();
+        encoder.put_name(value, false);
+        encoder.into_bytes()
     }
 }
 
 #[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 enum Element {
-    Label(String),
+    Label(Vec<u8>),
     Root,
     Pointer(usize),
 }
 
 impl Element {
-    fn parse_root(i: &[u8]) -> IResult<&[u8], Self> {
-        nom::combinator::value(Self::Root, nom::bytes::complete::tag([0x00; 1]))(i)
-    }
-
-    fn parse_label(i: &[u8]) -> IResult<&[u8], Self> {
-        let (i, (_, length)) = nom::bits::<_, (u8, usize), nom::error::Error<(&[u8], usize)>, _, _>(
-            nom::sequence::pair(
-                nom::bits::complete::tag(0x00, 2_usize),
-                nom::bits::complete::take(6_usize),
-            ),
-        )(i)?;
-        let (i, label) =
-            nom::combinator::map_res(nom::bytes::complete::take(length), |b: &[u8]| {
-                String::from_utf8(b.to_vec())
-            })(i)?;
-
-        Ok((i, Self::Label(label)))
-    }
-
-    fn parse_pointer(i: &[u8]) -> IResult<&[u8], Self> {
-        let (i, (_, address)) = nom::bits::<_, (_, usize), nom::error::Error<(&[u8], usize)>, _, _>(
-            nom::sequence::pair(
-                nom::bits::complete::tag(0x3, 2_usize),
-                nom::bits::complete::take(14_usize),
-            ),
-        )(i)?;
-        Ok((i, Self::Pointer(address)))
+    /// Reads a label, the root label, or a pointer, which are told apart by the top two bits of
+    /// the first byte. The other two combinations are reserved (RFC 1035 §4.1.4).
+    fn parse(i: &[u8]) -> ParseResult<'_, Self> {
+        let (remaining, length) = nom::number::complete::be_u8(i)?;
+        match length >> 6 {
+            0b00 if length == 0 => Ok((remaining, Self::Root)),
+            0b00 => {
+                let (remaining, bytes) = nom::bytes::complete::take(length)(remaining)?;
+                Ok((remaining, Self::Label(bytes.to_vec())))
+            }
+            0b11 => {
+                let (remaining, low) = nom::number::complete::be_u8(remaining)?;
+                let address = (((length & 0x3F) as usize) << 8) | low as usize;
+                Ok((remaining, Self::Pointer(address)))
+            }
+            _ => Err(ParseError::failure(
+                i,
+                ParseErrorKind::BadLabelLength(length),
+            )),
+        }
     }
 }
 
 impl Display for Element {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Element::Label(l) => write!(f, "{l}"),
+            Element::Label(l) => write_label(f, l),
             Element::Root => write!(f, ""),
             Element::Pointer(p) => write!(f, "<ptr: {p:04X}>"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Error, Location, Section};
+
+    /// Parses the name at `start` in `message`, expecting it to fail.
+    fn parse_error(message: &[u8], start: usize) -> Error {
+        match DomainName::parse(message)(&message[start..]) {
+            Err(nom::Err::Failure(error)) => {
+                error.into_error(message, Location::new(Section::Question, 0))
+            }
+            result => panic!("expected an error, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn follows_pointers_to_earlier_names() {
+        let message = b"\x01b\x00\x01a\xC0\x00\x00";
+        let (remaining, name) = DomainName::parse(message)(&message[3..]).unwrap();
+        assert_eq!(name.to_string(), "a.b.");
+        assert_eq!(remaining, b"\x00");
+    }
+
+    #[test]
+    fn rejects_pointer_loops() {
+        // "a" points to "b", which points back to "a".
+        let message = b"\x01b\xC0\x03\x01a\xC0\x00";
+        assert!(matches!(
+            parse_error(message, 4),
+            Error::PointerLoop { offset: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_pointers_to_itself() {
+        let message = b"\x01a\xC0\x00";
+        assert!(matches!(
+            parse_error(message, 0),
+            Error::PointerLoop { offset: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_forward_pointers() {
+        let message = b"\x00\xC0\x04\x00\x01a\x00";
+        assert!(matches!(
+            parse_error(message, 1),
+            Error::PointerLoop { offset: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_pointers_outside_the_message() {
+        let message = b"\x01a\xC0\x10";
+        assert!(matches!(
+            parse_error(message, 0),
+            Error::PointerOutOfRange {
+                offset: 2,
+                target: 16,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_names_longer_than_255_octets() {
+        let label = [b"\x3F".as_slice(), &[b'a'; 63]].concat();
+        let mut message = vec![0; 12];
+        message.extend(label.repeat(3));
+        message.extend([b"\x3D".as_slice(), &[b'a'; 61], b"\x00"].concat());
+        let (_, name) = DomainName::parse(&message)(&message[12..]).unwrap();
+        assert_eq!(name.labels().len(), 4);
+
+        message.truncate(12);
+        message.extend(label.repeat(4));
+        message.push(0);
+        assert!(matches!(
+            parse_error(&message, 12),
+            Error::NameTooLong { offset: 12, .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_names_made_too_long_by_pointers() {
+        let label = [b"\x3F".as_slice(), &[b'a'; 63]].concat();
+        let mut message = label.repeat(3);
+        message.push(0);
+        let start = message.len();
+        message.extend(&label);
+        message.extend(b"\xC0\x00");
+        assert!(matches!(
+            parse_error(&message, start),
+            Error::NameTooLong { offset, .. } if offset == start
+        ));
+    }
+
+    #[test]
+    fn rejects_extended_label_type() {
+        let message = b"\x01a\x41b\x00";
+        assert!(matches!(
+            parse_error(message, 0),
+            Error::BadLabelLength {
+                offset: 2,
+                length: 0x41,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_reserved_label_type() {
+        let message = b"\x01a\x81b\x00";
+        assert!(matches!(
+            parse_error(message, 0),
+            Error::BadLabelLength {
+                offset: 2,
+                length: 0x81,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn escapes_are_read_and_written() {
+        let name: DomainName = "a\\.b.\\065\\255\\\\x.example.".parse().unwrap();
+        assert_eq!(
+            name.labels(),
+            [b"a.b".to_vec(), b"A\xff\\x".to_vec(), b"example".to_vec()]
+        );
+        assert_eq!(name.to_string(), "a\\.b.A\\255\\\\x.example.");
+
+        let name = DomainName::new([&b"a.b"[..], b"\x00 \"", b"example"]).unwrap();
+        assert_eq!(name.to_string(), "a\\.b.\\000\\032\\\".example.");
+        assert_eq!(name.to_string().parse::<DomainName>().unwrap(), name);
+    }
+
+    #[test]
+    fn rejects_bad_escapes() {
+        for text in [
+            "\\256.example.",
+            "a\\25.example.",
+            "a\\2x5.example.",
+            "example\\",
+        ] {
+            assert_eq!(
+                text.parse::<DomainName>(),
+                Err(TextError::BadEscape(text.to_string())),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn new_enforces_the_length_limits() {
+        let label = |length| "x".repeat(length);
+        assert!(DomainName::new([label(63)]).is_ok());
+        assert_eq!(
+            DomainName::new([label(64)]),
+            Err(TextError::LabelTooLong(format!("{}.", label(64))))
+        );
+        assert!(matches!(
+            DomainName::new(["a", "", "example"]),
+            Err(TextError::EmptyLabel(_))
+        ));
+
+        // 3 * (63 + 1) + (61 + 1) + 1 = 255 octets on the wire.
+        let longest = DomainName::new([label(63), label(63), label(63), label(61)]).unwrap();
+        assert_eq!(Vec::from(&longest).len(), 255);
+        assert!(matches!(
+            DomainName::new([label(63), label(63), label(63), label(62)]),
+            Err(TextError::NameTooLong(_))
+        ));
+    }
+}
@@ -0,0 +1,526 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::domain_name::DomainName;
+use crate::error::ParseResult;
+use crate::presentation::hex;
+use crate::resource_record::{RecordData, ResourceRecord, OPT};
+
+/// The DO bit of the OPT record's flags, which asks for DNSSEC records (RFC 3225).
+const DNSSEC_OK: u16 = 0x8000;
+
+/// The EDNS(0) parameters of a message (RFC 6891), carried on the wire by an OPT pseudo-record in
+/// the additional section.
+#[derive(Clone, Debug)]
+pub struct Edns {
+    udp_payload_size: u16,
+    extended_rcode: u8,
+    version: u8,
+    dnssec_ok: bool,
+    z: u16,
+    options: Vec<EdnsOption>,
+}
+
+impl Edns {
+    pub fn new(udp_payload_size: u16) -> Self {
+        Self {
+            udp_payload_size,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            z: 0,
+            options: Vec::new(),
+        }
+    }
+
+    pub fn with_extended_rcode(mut self, extended_rcode: u8) -> Self {
+        self.extended_rcode = extended_rcode;
+        self
+    }
+
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn with_dnssec_ok(mut self, dnssec_ok: bool) -> Self {
+        self.dnssec_ok = dnssec_ok;
+        self
+    }
+
+    /// Sets the flag bits other than DO, which are reserved and should be zero (RFC 6891 §6.1.4).
+    /// The DO bit in `z` is ignored.
+    pub fn with_z(mut self, z: u16) -> Self {
+        self.z = z & !DNSSEC_OK;
+        self
+    }
+
+    pub fn with_option(mut self, option: EdnsOption) -> Self {
+        self.options.push(option);
+        self
+    }
+
+    pub fn udp_payload_size(&self) -> u16 {
+        self.udp_payload_size
+    }
+
+    /// The upper eight bits of the message's 12-bit RCODE.
+    pub fn extended_rcode(&self) -> u8 {
+        self.extended_rcode
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn dnssec_ok(&self) -> bool {
+        self.dnssec_ok
+    }
+
+    /// The reserved flag bits, with the DO bit cleared.
+    pub fn z(&self) -> u16 {
+        self.z
+    }
+
+    pub fn options(&self) -> &[EdnsOption] {
+        &self.options
+    }
+
+    /// Interprets an OPT pseudo-record, returning `None` if the record is of any other type.
+    pub fn from_record(record: &ResourceRecord) -> Option<Self> {
+        let RecordData::OPT(opt) = record.rdata() else {
+            return None;
+        };
+        let flags = record.ttl() as u32;
+
+        Some(Self {
+            udp_payload_size: record.class().into(),
+            extended_rcode: (flags >> 24) as u8,
+            version: (flags >> 16) as u8,
+            dnssec_ok: flags as u16 & DNSSEC_OK != 0,
+            z: flags as u16 & !DNSSEC_OK,
+            options: opt.options().to_vec(),
+        })
+    }
+}
+
+impl From<&Edns> for ResourceRecord {
+    fn from(value: &Edns) -> Self {
+        let mut flags =
+            ((value.extended_rcode as u32) << 24) | ((value.version as u32) << 16) | value.z as u32;
+        if value.dnssec_ok {
+            flags |= DNSSEC_OK as u32;
+        }
+
+        ResourceRecord::new(
+            DomainName::root(),
+            crate::Type::OPT,
+            value.udp_payload_size.into(),
+            flags as i32,
+            RecordData::OPT(OPT::new(value.options.clone())),
+        )
+    }
+}
+
+impl Display for Edns {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EDNS: version: {}, flags:", self.version)?;
+        if self.dnssec_ok {
+            write!(f, " do")?;
+        }
+        if self.z != 0 {
+            write!(f, "; MBZ: {:#06x}", self.z)?;
+        }
+        write!(f, "; udp: {}", self.udp_payload_size)?;
+        for option in self.options.iter() {
+            write!(f, "; {option}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single option from the OPT RDATA. Options this library doesn't know how to interpret, and
+/// known ones whose data is malformed, are kept as their raw code and data.
+#[derive(Clone, Debug, derive_more::Display)]
+pub enum EdnsOption {
+    #[display(fmt = "NSID: {}", r#"hex(_0)"#)]
+    NameServerIdentifier(Vec<u8>),
+    #[display(fmt = "CLIENT-SUBNET: {}", _0)]
+    ClientSubnet(ClientSubnet),
+    #[display(fmt = "COOKIE: {}", _0)]
+    Cookie(Cookie),
+    #[display(
+        fmt = "KEEPALIVE: {}",
+        r#"_0.map(|t| t.to_string()).unwrap_or_default()"#
+    )]
+    TcpKeepalive(Option<u16>),
+    #[display(fmt = "PADDING: {}", "_0.len()")]
+    Padding(Vec<u8>),
+    #[display(fmt = "EDE: {}", _0)]
+    ExtendedError(ExtendedError),
+    #[display(fmt = "OPT={}: {}", _0, r#"hex(_1)"#)]
+    Unknown(u16, Vec<u8>),
+}
+
+impl EdnsOption {
+    pub fn code(&self) -> u16 {
+        match self {
+            EdnsOption::NameServerIdentifier(_) => 3,
+            EdnsOption::ClientSubnet(_) => 8,
+            EdnsOption::Cookie(_) => 10,
+            EdnsOption::TcpKeepalive(_) => 11,
+            EdnsOption::Padding(_) => 12,
+            EdnsOption::ExtendedError(_) => 15,
+            EdnsOption::Unknown(code, _) => *code,
+        }
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u16(self.code());
+        let start = encoder.start_length();
+
+        match self {
+            EdnsOption::NameServerIdentifier(data)
+            | EdnsOption::Padding(data)
+            | EdnsOption::Unknown(_, data) => encoder.put_slice(data),
+            EdnsOption::ClientSubnet(subnet) => {
+                let (family, address) = match subnet.address {
+                    IpAddr::V4(address) => (1, address.octets().to_vec()),
+                    IpAddr::V6(address) => (2, address.octets().to_vec()),
+                };
+                encoder.put_u16(family);
+                encoder.put_u8(subnet.source_prefix_length);
+                encoder.put_u8(subnet.scope_prefix_length);
+                let length = (subnet.source_prefix_length as usize).div_ceil(8);
+                encoder.put_slice(&address[..length.min(address.len())]);
+            }
+            EdnsOption::Cookie(cookie) => {
+                encoder.put_slice(&cookie.client);
+                encoder.put_slice(&cookie.server);
+            }
+            EdnsOption::TcpKeepalive(timeout) => {
+                if let Some(timeout) = timeout {
+                    encoder.put_u16(*timeout);
+                }
+            }
+            EdnsOption::ExtendedError(error) => {
+                encoder.put_u16(error.info_code);
+                encoder.put_slice(&error.extra_text);
+            }
+        }
+
+        encoder.end_length("EDNS option length", start);
+    }
+}
+
+/// The EDNS Client Subnet option (RFC 7871).
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(fmt = "{}/{}/{}", address, source_prefix_length, scope_prefix_length)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    source_prefix_length: u8,
+    scope_prefix_length: u8,
+}
+
+impl ClientSubnet {
+    pub fn new(address: IpAddr, source_prefix_length: u8, scope_prefix_length: u8) -> Self {
+        Self {
+            address,
+            source_prefix_length,
+            scope_prefix_length,
+        }
+    }
+
+    pub fn address(&self) -> &IpAddr {
+        &self.address
+    }
+
+    pub fn source_prefix_length(&self) -> u8 {
+        self.source_prefix_length
+    }
+
+    pub fn scope_prefix_length(&self) -> u8 {
+        self.scope_prefix_length
+    }
+}
+
+/// A DNS Cookie (RFC 7873). The server cookie is empty when a client hasn't learnt one yet.
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(fmt = "{}{}", r#"hex(client)"#, r#"hex(server)"#)]
+pub struct Cookie {
+    client: [u8; 8],
+    server: Vec<u8>,
+}
+
+impl Cookie {
+    pub fn new(client: [u8; 8], server: Vec<u8>) -> Self {
+        Self { client, server }
+    }
+
+    pub fn client(&self) -> &[u8; 8] {
+        &self.client
+    }
+
+    pub fn server(&self) -> &[u8] {
+        &self.server
+    }
+}
+
+/// Extended DNS Error (RFC 8914). The text should be UTF-8 but is kept as the bytes that were
+/// sent, so that it goes back out unchanged.
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(fmt = "{} ({})", info_code, r#"String::from_utf8_lossy(extra_text)"#)]
+pub struct ExtendedError {
+    info_code: u16,
+    extra_text: Vec<u8>,
+}
+
+impl ExtendedError {
+    pub fn new(info_code: u16, extra_text: impl Into<Vec<u8>>) -> Self {
+        Self {
+            info_code,
+            extra_text: extra_text.into(),
+        }
+    }
+
+    pub fn info_code(&self) -> u16 {
+        self.info_code
+    }
+
+    pub fn extra_text(&self) -> &[u8] {
+        &self.extra_text
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) fn parse_option(i: &[u8]) -> ParseResult<'_, EdnsOption> {
+    let (remaining, code) = nom::number::complete::be_u16(i)?;
+    let (remaining, data) = nom::error::context(
+        "EDNS option",
+        nom::multi::length_data(nom::number::complete::be_u16),
+    )(remaining)?;
+
+    // One malformed option shouldn't make the rest of the message unreadable.
+    let option = parse_option_data(code, data).unwrap_or_else(|| {
+        tracing::debug!(code, "keeping malformed EDNS option as unknown");
+        EdnsOption::Unknown(code, data.to_vec())
+    });
+    Ok((remaining, option))
+}
+
+/// Interprets the data of an option, or returns `None` if it isn't well formed and so couldn't be
+/// written back out as it was.
+fn parse_option_data(code: u16, data: &[u8]) -> Option<EdnsOption> {
+    let option = match code {
+        3 => EdnsOption::NameServerIdentifier(data.to_vec()),
+        8 => {
+            let family = u16::from_be_bytes(data.get(..2)?.try_into().ok()?);
+            let (source_prefix_length, scope_prefix_length) = (*data.get(2)?, *data.get(3)?);
+            let address = &data[4..];
+            // The address has exactly as many octets as the source prefix needs (RFC 7871 §6).
+            if address.len() != (source_prefix_length as usize).div_ceil(8) {
+                return None;
+            }
+            let address = match family {
+                1 if source_prefix_length <= 32 => {
+                    let mut octets = [0_u8; 4];
+                    octets[..address.len()].copy_from_slice(address);
+                    IpAddr::V4(Ipv4Addr::from(octets))
+                }
+                2 if source_prefix_length <= 128 => {
+                    let mut octets = [0_u8; 16];
+                    octets[..address.len()].copy_from_slice(address);
+                    IpAddr::V6(Ipv6Addr::from(octets))
+                }
+                _ => return None,
+            };
+            EdnsOption::ClientSubnet(ClientSubnet::new(
+                address,
+                source_prefix_length,
+                scope_prefix_length,
+            ))
+        }
+        10 => {
+            if data.len() != 8 && !(16..=40).contains(&data.len()) {
+                return None;
+            }
+            let mut client = [0_u8; 8];
+            client.copy_from_slice(&data[..8]);
+            EdnsOption::Cookie(Cookie::new(client, data[8..].to_vec()))
+        }
+        11 => match data.len() {
+            0 => EdnsOption::TcpKeepalive(None),
+            2 => EdnsOption::TcpKeepalive(Some(u16::from_be_bytes([data[0], data[1]]))),
+            _ => return None,
+        },
+        12 => EdnsOption::Padding(data.to_vec()),
+        15 => {
+            let info_code = u16::from_be_bytes(data.get(..2)?.try_into().ok()?);
+            EdnsOption::ExtendedError(ExtendedError::new(info_code, &data[2..]))
+        }
+        code => EdnsOption::Unknown(code, data.to_vec()),
+    };
+    Some(option)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_flags_are_kept() {
+        let record = ResourceRecord::new(
+            DomainName::root(),
+            crate::Type::OPT,
+            1232.into(),
+            0x0100_c001,
+            RecordData::OPT(OPT::new(Vec::new())),
+        );
+        let edns = Edns::from_record(&record).unwrap();
+        assert_eq!(edns.version(), 0);
+        assert_eq!(edns.extended_rcode(), 1);
+        assert!(edns.dnssec_ok());
+        assert_eq!(edns.z(), 0x4001);
+        assert_eq!(
+            Vec::try_from(&ResourceRecord::from(&edns)).unwrap(),
+            Vec::try_from(&record).unwrap()
+        );
+    }
+
+    fn encode(option: &EdnsOption) -> Vec<u8> {
+        let mut encoder = crate::encoder::Encoder::new();
+        option.encode(&mut encoder);
+        encoder.finish().unwrap()
+    }
+
+    /// Parses an option's wire form and checks that it's written back out the same.
+    fn parse(bytes: &[u8]) -> EdnsOption {
+        let (remaining, option) = parse_option(bytes).unwrap();
+        assert!(remaining.is_empty());
+        assert_eq!(encode(&option), bytes);
+        option
+    }
+
+    #[test]
+    fn client_subnets_round_trip_with_only_the_prefix_octets() {
+        let subnet = ClientSubnet::new("192.0.2.123".parse().unwrap(), 24, 0);
+        let bytes = encode(&EdnsOption::ClientSubnet(subnet));
+        assert_eq!(bytes, [0, 8, 0, 7, 0, 1, 24, 0, 192, 0, 2]);
+        let EdnsOption::ClientSubnet(subnet) = parse(&bytes) else {
+            panic!("not a client subnet");
+        };
+        assert_eq!(subnet.to_string(), "192.0.2.0/24/0");
+
+        let subnet = ClientSubnet::new("2001:db8:1:2::1".parse().unwrap(), 56, 48);
+        let bytes = encode(&EdnsOption::ClientSubnet(subnet));
+        assert_eq!(bytes[4..8], [0, 2, 56, 48]);
+        assert_eq!(bytes[8..], [0x20, 0x01, 0x0d, 0xb8, 0, 1, 0]);
+        assert_eq!(
+            parse(&bytes).to_string(),
+            "CLIENT-SUBNET: 2001:db8:1::/56/48"
+        );
+
+        let bytes = encode(&EdnsOption::ClientSubnet(ClientSubnet::new(
+            "192.0.2.1".parse().unwrap(),
+            0,
+            0,
+        )));
+        assert_eq!(bytes, [0, 8, 0, 4, 0, 1, 0, 0]);
+        assert_eq!(parse(&bytes).to_string(), "CLIENT-SUBNET: 0.0.0.0/0/0");
+    }
+
+    #[test]
+    fn cookies_round_trip() {
+        let client = [1, 2, 3, 4, 5, 6, 7, 8];
+        let bytes = encode(&EdnsOption::Cookie(Cookie::new(client, Vec::new())));
+        assert_eq!(bytes, [&[0, 10, 0, 8][..], &client].concat());
+        let EdnsOption::Cookie(cookie) = parse(&bytes) else {
+            panic!("not a cookie");
+        };
+        assert_eq!(cookie.client(), &client);
+        assert!(cookie.server().is_empty());
+
+        let bytes = encode(&EdnsOption::Cookie(Cookie::new(client, vec![0xAA; 16])));
+        let EdnsOption::Cookie(cookie) = parse(&bytes) else {
+            panic!("not a cookie");
+        };
+        assert_eq!(cookie.server(), [0xAA; 16]);
+    }
+
+    #[test]
+    fn keepalives_round_trip() {
+        let bytes = encode(&EdnsOption::TcpKeepalive(None));
+        assert_eq!(bytes, [0, 11, 0, 0]);
+        assert!(matches!(parse(&bytes), EdnsOption::TcpKeepalive(None)));
+
+        let bytes = encode(&EdnsOption::TcpKeepalive(Some(1200)));
+        assert_eq!(bytes, [0, 11, 0, 2, 0x04, 0xB0]);
+        assert!(matches!(
+            parse(&bytes),
+            EdnsOption::TcpKeepalive(Some(1200))
+        ));
+    }
+
+    #[test]
+    fn extended_errors_keep_their_text_as_sent() {
+        let bytes = [0, 15, 0, 8, 0, 18, b'n', b'o', b'p', b'e', 0xFF, b'!'];
+        let EdnsOption::ExtendedError(error) = parse(&bytes) else {
+            panic!("not an extended error");
+        };
+        assert_eq!(error.info_code(), 18);
+        assert_eq!(error.extra_text(), b"nope\xFF!");
+        assert_eq!(error.to_string(), "18 (nope\u{FFFD}!)");
+
+        let bytes = [0, 15, 0, 2, 0, 0];
+        let EdnsOption::ExtendedError(error) = parse(&bytes) else {
+            panic!("not an extended error");
+        };
+        assert!(error.extra_text().is_empty());
+    }
+
+    #[test]
+    fn malformed_options_are_kept_as_unknown() {
+        let malformed: &[&[u8]] = &[
+            // A cookie of 12 octets, between client-only and the shortest with a server cookie.
+            &[0, 10, 0, 12, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+            // Client subnets of family 3, with more octets than the prefix needs, with a prefix
+            // longer than the address, and cut short.
+            &[0, 8, 0, 5, 0, 3, 8, 0, 10],
+            &[0, 8, 0, 8, 0, 1, 8, 0, 10, 0, 0, 0],
+            &[0, 8, 0, 9, 0, 1, 33, 0, 10, 0, 0, 0, 0],
+            &[0, 8, 0, 3, 0, 1, 0],
+            // A keepalive of one octet.
+            &[0, 11, 0, 1, 5],
+            // An extended error without a whole info code.
+            &[0, 15, 0, 1, 0],
+        ];
+        for bytes in malformed {
+            match parse(bytes) {
+                EdnsOption::Unknown(code, data) => {
+                    assert_eq!(code.to_be_bytes(), bytes[..2]);
+                    assert_eq!(data, bytes[4..]);
+                }
+                other => panic!("{bytes:?} was read as {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn a_malformed_option_leaves_the_message_readable() {
+        let message = crate::message::Message::builder()
+            .add_answer("example. 60 A 192.0.2.1".parse().unwrap())
+            .with_edns(
+                Edns::new(1232)
+                    .with_option(EdnsOption::Unknown(11, vec![5]))
+                    .with_option(EdnsOption::TcpKeepalive(Some(100))),
+            )
+            .build();
+        let bytes = Vec::try_from(&message).unwrap();
+        let parsed = crate::message::Message::try_from(&bytes[..]).unwrap();
+        assert_eq!(parsed.answers().len(), 1);
+        let options = parsed.edns().unwrap().options();
+        assert!(matches!(&options[0], EdnsOption::Unknown(11, data) if data == &[5]));
+        assert!(matches!(options[1], EdnsOption::TcpKeepalive(Some(100))));
+        assert_eq!(Vec::try_from(&parsed).unwrap(), bytes);
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use crate::domain_name::DomainName;
+use crate::error::EncodeError;
+
+/// Pointers only have 14 bits to address the message with, so names written past this offset can't
+/// be referred back to.
+const MAX_POINTER_OFFSET: usize = 0x3FFF;
+
+/// Builds up the wire format of a message.
+///
+/// When compression is enabled, every name (and each of its suffixes) written through
+/// [`Encoder::put_name`] is remembered along with its offset, so that later occurrences of the same
+/// suffix can be replaced with a pointer as described in RFC 1035 §4.1.4.
+#[derive(Debug)]
+pub(crate) struct Encoder {
+    bytes: Vec<u8>,
+    compress: bool,
+    names: HashMap<Vec<Vec<u8>>, u16>,
+    lowercase: bool,
+    /// The first length or count that was too big for its field.
+    error: Option<EncodeError>,
+}
+
+impl Encoder {
+    pub(crate) fn new() -> Self {
+        Self {
+            bytes: Vec::with_capacity(512),
+            compress: false,
+            names: HashMap::new(),
+            lowercase: false,
+            error: None,
+        }
+    }
+
+    pub(crate) fn with_compression() -> Self {
+        Self {
+            compress: true,
+            ..Self::new()
+        }
+    }
+
+    /// Writes names in lowercase, as in the canonical form of RFC 4034 §6.2.
+    pub(crate) fn with_lowercase_names() -> Self {
+        Self {
+            lowercase: true,
+            ..Self::new()
+        }
+    }
+
+    pub(crate) fn put_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn put_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn put_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn put_i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn put_slice(&mut self, value: &[u8]) {
+        self.bytes.extend_from_slice(value);
+    }
+
+    /// Writes a 16-bit count or length, remembering that encoding failed if `value` doesn't fit.
+    pub(crate) fn put_length(&mut self, field: &'static str, value: usize) {
+        let value = self.check_length(field, value);
+        self.put_u16(value);
+    }
+
+    /// Writes a placeholder for a 16-bit length, returning the offset to give to [`end_length`]
+    /// once the data it describes has been written.
+    ///
+    /// [`end_length`]: Encoder::end_length
+    pub(crate) fn start_length(&mut self) -> usize {
+        self.put_u16(0);
+        self.bytes.len()
+    }
+
+    /// Fills in a length started with [`start_length`] with the number of bytes written since.
+    ///
+    /// [`start_length`]: Encoder::start_length
+    pub(crate) fn end_length(&mut self, field: &'static str, start: usize) {
+        let length = self.check_length(field, self.bytes.len() - start);
+        self.bytes[start - 2..start].copy_from_slice(&length.to_be_bytes());
+    }
+
+    fn check_length(&mut self, field: &'static str, value: usize) -> u16 {
+        u16::try_from(value).unwrap_or_else(|_| {
+            self.error
+                .get_or_insert(EncodeError::TooLong { field, value });
+            u16::MAX
+        })
+    }
+
+    /// Writes a domain name, using a pointer to an earlier copy of its longest known suffix if
+    /// `compressible` is set and the encoder is compressing.
+    ///
+    /// Even when a name may not itself be compressed, its suffixes are still recorded so that
+    /// names written later can point into it.
+    pub(crate) fn put_name(&mut self, name: &DomainName, compressible: bool) {
+        let labels = name.labels();
+        let keys: Vec<Vec<u8>> = labels.iter().map(|l| l.to_ascii_lowercase()).collect();
+
+        for (index, label) in labels.iter().enumerate() {
+            if self.compress {
+                let suffix = &keys[index..];
+                if compressible {
+                    if let Some(pointer) = self.names.get(suffix) {
+                        self.put_u16(0xC000 | pointer);
+                        return;
+                    }
+                }
+                if self.bytes.len() <= MAX_POINTER_OFFSET && !self.names.contains_key(suffix) {
+                    self.names.insert(suffix.to_vec(), self.bytes.len() as u16);
+                }
+            }
+            self.put_u8(label.len() as u8);
+            match self.lowercase {
+                true => self.put_slice(&label.to_ascii_lowercase()),
+                false => self.put_slice(label),
+            }
+        }
+        self.put_u8(0);
+    }
+
+    /// The bytes written so far, even if a length didn't fit. Only for when they're to be shown
+    /// or compared rather than sent.
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// The bytes written, or the first length or count that didn't fit in its field.
+    pub(crate) fn finish(self) -> Result<Vec<u8>, EncodeError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.bytes),
+        }
+    }
+}
@@ -1,5 +1,354 @@
+use std::fmt::{Display, Formatter};
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("Format of packet is not what was expected")]
-    FormatError,
+    #[error("Message ended unexpectedly at offset {offset} in {location}")]
+    Truncated { offset: usize, location: Location },
+    #[error("Label length {length} at offset {offset} in {location} is not valid")]
+    BadLabelLength {
+        offset: usize,
+        length: u8,
+        location: Location,
+    },
+    #[error(
+        "Compression pointer at offset {offset} in {location} doesn't point to an earlier name"
+    )]
+    PointerLoop { offset: usize, location: Location },
+    #[error(
+        "Compression pointer at offset {offset} in {location} points outside the message to {target}"
+    )]
+    PointerOutOfRange {
+        offset: usize,
+        target: usize,
+        location: Location,
+    },
+    #[error("Name at offset {offset} in {location} is longer than 255 octets")]
+    NameTooLong { offset: usize, location: Location },
+    #[error("{length} bytes of unexpected data after the last record at offset {offset}")]
+    TrailingData { offset: usize, length: usize },
+    #[error("RDATA in {location} doesn't fit its RDLENGTH of {rdlength}, at offset {offset}")]
+    RdataLengthMismatch {
+        offset: usize,
+        rdlength: usize,
+        location: Location,
+    },
+    #[error("Invalid {field} at offset {offset} in {location}")]
+    BadRecordField {
+        offset: usize,
+        field: &'static str,
+        location: Location,
+    },
+}
+
+impl Error {
+    /// The offset from the start of the message of the byte that couldn't be parsed.
+    pub fn offset(&self) -> usize {
+        match self {
+            Error::Truncated { offset, .. }
+            | Error::BadLabelLength { offset, .. }
+            | Error::PointerLoop { offset, .. }
+            | Error::PointerOutOfRange { offset, .. }
+            | Error::NameTooLong { offset, .. }
+            | Error::TrailingData { offset, .. }
+            | Error::RdataLengthMismatch { offset, .. }
+            | Error::BadRecordField { offset, .. } => *offset,
+        }
+    }
+
+    /// The part of the message being parsed when the error happened, if it was inside one.
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Error::Truncated { location, .. }
+            | Error::BadLabelLength { location, .. }
+            | Error::PointerLoop { location, .. }
+            | Error::PointerOutOfRange { location, .. }
+            | Error::NameTooLong { location, .. }
+            | Error::RdataLengthMismatch { location, .. }
+            | Error::BadRecordField { location, .. } => Some(*location),
+            Error::TrailingData { .. } => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Section {
+    Header,
+    Question,
+    Answer,
+    Authority,
+    Additional,
+}
+
+/// Identifies an entry in a message: the header, or a question or record by its index within its
+/// section.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    section: Section,
+    index: usize,
+}
+
+impl Location {
+    pub(crate) fn new(section: Section, index: usize) -> Self {
+        Self { section, index }
+    }
+
+    pub fn section(&self) -> Section {
+        self.section
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.section {
+            Section::Header => write!(f, "header"),
+            Section::Question => write!(f, "question {}", self.index),
+            Section::Answer => write!(f, "answer record {}", self.index),
+            Section::Authority => write!(f, "authority record {}", self.index),
+            Section::Additional => write!(f, "additional record {}", self.index),
+        }
+    }
+}
+
+/// An error from reading something written in presentation format, as used in zone files.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TextError {
+    #[error("Empty label in {0:?}")]
+    EmptyLabel(String),
+    #[error("Label longer than 63 octets in {0:?}")]
+    LabelTooLong(String),
+    #[error("Name {0:?} is longer than 255 octets")]
+    NameTooLong(String),
+    #[error("{0} is longer than the 255 octets a character-string can hold")]
+    StringTooLong(&'static str),
+    #[error("Invalid escape sequence in {0:?}")]
+    BadEscape(String),
+    #[error("Quoted string isn't closed before the end of the line")]
+    UnterminatedQuote,
+    #[error("Parentheses aren't balanced")]
+    UnbalancedParentheses,
+    #[error("Unknown type {0:?}")]
+    UnknownType(String),
+    #[error("Unknown class {0:?}")]
+    UnknownClass(String),
+    #[error("Missing {0}")]
+    MissingField(&'static str),
+    #[error("Invalid {field} {value:?}")]
+    BadField { field: &'static str, value: String },
+    #[error("Unexpected {0:?} after the end of the record")]
+    TrailingData(String),
+    #[error("{0} records can only be written in the generic \\# format")]
+    UnsupportedType(String),
+}
+
+/// An error from reading a zone file, along with where it happened.
+#[derive(Debug, thiserror::Error)]
+pub struct ZoneError {
+    pub(crate) file: Option<std::path::PathBuf>,
+    pub(crate) line: usize,
+    pub(crate) kind: ZoneErrorKind,
+}
+
+impl ZoneError {
+    /// The file the error is in, or `None` if it's in text that was read from a string.
+    pub fn file(&self) -> Option<&std::path::Path> {
+        self.file.as_deref()
+    }
+
+    /// The line the entry with the error starts on, counting from 1, or 0 if the error isn't in
+    /// the text of the file.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn kind(&self) -> &ZoneErrorKind {
+        &self.kind
+    }
+}
+
+impl Display for ZoneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.file, self.line) {
+            (_, 0) => write!(f, "{}", self.kind),
+            (Some(file), line) => write!(f, "{}:{line}: {}", file.display(), self.kind),
+            (None, line) => write!(f, "line {line}: {}", self.kind),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ZoneErrorKind {
+    #[error(transparent)]
+    Text(#[from] TextError),
+    #[error("Unknown directive {0}")]
+    UnknownDirective(String),
+    #[error("$INCLUDEs are nested too deeply")]
+    IncludeTooDeep,
+    #[error("Couldn't read {}: {source}", path.display())]
+    Io {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Why a message or record couldn't be written in wire format.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum EncodeError {
+    #[error("{field} of {value} doesn't fit in 16 bits")]
+    TooLong { field: &'static str, value: usize },
+}
+
+/// Why a query didn't get a response.
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("No response before the timeout")]
+    Timeout,
+    #[error("Couldn't parse the response: {0}")]
+    BadResponse(#[from] Error),
+    #[error("Couldn't encode the query: {0}")]
+    BadQuery(#[from] EncodeError),
+    #[error("A query with id {0} is already waiting for a response on this connection")]
+    IdInUse(u16),
+    #[error("The response doesn't answer the query")]
+    WrongResponse,
+    #[error("Invalid name to look up: {0}")]
+    BadName(#[from] TextError),
+    #[cfg(feature = "tls")]
+    #[error("TLS error: {0}")]
+    Tls(#[from] rustls::Error),
+    #[cfg(feature = "https")]
+    #[error("Invalid DNS over HTTPS URI {0}")]
+    BadUri(String),
+    #[cfg(feature = "https")]
+    #[error("HTTP error: {0}")]
+    Http(#[from] hyper::Error),
+    #[cfg(feature = "https")]
+    #[error("The server responded with HTTP status {0}")]
+    HttpStatus(u16),
+    #[cfg(feature = "quic")]
+    #[error("Couldn't connect over QUIC: {0}")]
+    QuicConnect(#[from] quinn::ConnectError),
+    #[cfg(feature = "quic")]
+    #[error("QUIC connection failed: {0}")]
+    QuicConnection(#[from] quinn::ConnectionError),
+}
+
+/// The error type used by this crate's nom parsers. It remembers the input it failed at so that
+/// the position can be worked out once it's known which message the input belongs to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError<'a> {
+    input: &'a [u8],
+    kind: ParseErrorKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum ParseErrorKind {
+    Truncated,
+    BadLabelLength(u8),
+    PointerLoop,
+    PointerOutOfRange(usize),
+    NameTooLong,
+    RdataLengthMismatch(usize),
+    BadField(&'static str),
+    Invalid,
+}
+
+pub(crate) type ParseResult<'a, T> = nom::IResult<&'a [u8], T, ParseError<'a>>;
+
+impl<'a> ParseError<'a> {
+    pub(crate) fn new(input: &'a [u8], kind: ParseErrorKind) -> Self {
+        Self { input, kind }
+    }
+
+    pub(crate) fn failure(input: &'a [u8], kind: ParseErrorKind) -> nom::Err<Self> {
+        nom::Err::Failure(Self::new(input, kind))
+    }
+
+    pub(crate) fn input(&self) -> &'a [u8] {
+        self.input
+    }
+
+    pub(crate) fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// Resolves the error against the message the input was taken from.
+    pub(crate) fn into_error(self, message: &[u8], location: Location) -> Error {
+        let offset = offset_in(message, self.input);
+        match self.kind {
+            ParseErrorKind::Truncated => Error::Truncated { offset, location },
+            ParseErrorKind::BadLabelLength(length) => Error::BadLabelLength {
+                offset,
+                length,
+                location,
+            },
+            ParseErrorKind::PointerLoop => Error::PointerLoop { offset, location },
+            ParseErrorKind::PointerOutOfRange(target) => Error::PointerOutOfRange {
+                offset,
+                target,
+                location,
+            },
+            ParseErrorKind::NameTooLong => Error::NameTooLong { offset, location },
+            ParseErrorKind::RdataLengthMismatch(rdlength) => Error::RdataLengthMismatch {
+                offset,
+                rdlength,
+                location,
+            },
+            ParseErrorKind::BadField(field) => Error::BadRecordField {
+                offset,
+                field,
+                location,
+            },
+            ParseErrorKind::Invalid => Error::BadRecordField {
+                offset,
+                field: "record data",
+                location,
+            },
+        }
+    }
+}
+
+impl<'a> nom::error::ParseError<&'a [u8]> for ParseError<'a> {
+    fn from_error_kind(input: &'a [u8], kind: nom::error::ErrorKind) -> Self {
+        match kind {
+            nom::error::ErrorKind::Eof => Self::new(input, ParseErrorKind::Truncated),
+            _ => Self::new(input, ParseErrorKind::Invalid),
+        }
+    }
+
+    fn append(_input: &'a [u8], _kind: nom::error::ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<'a> nom::error::ContextError<&'a [u8]> for ParseError<'a> {
+    /// Names the field being parsed, unless the error already says more than that.
+    fn add_context(_input: &'a [u8], context: &'static str, other: Self) -> Self {
+        match other.kind {
+            ParseErrorKind::Invalid => Self::new(other.input, ParseErrorKind::BadField(context)),
+            _ => other,
+        }
+    }
+}
+
+impl<'a, E> nom::error::FromExternalError<&'a [u8], E> for ParseError<'a> {
+    fn from_external_error(input: &'a [u8], _kind: nom::error::ErrorKind, _e: E) -> Self {
+        Self::new(input, ParseErrorKind::Invalid)
+    }
+}
+
+/// Works out where `input` starts within `message`, given that it's a subslice of it.
+pub(crate) fn offset_in(message: &[u8], input: &[u8]) -> usize {
+    let start = message.as_ptr() as usize;
+    let position = input.as_ptr() as usize;
+    if position >= start && position <= start + message.len() {
+        position - start
+    } else {
+        message.len()
+    }
 }
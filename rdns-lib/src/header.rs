@@ -7,12 +7,16 @@ mod return_code;
 #[derive(Copy, Clone, Debug)]
 pub struct Header {
     id: u16,
-    is_answer: bool,
+    is_response: bool,
     opcode: Opcode,
     authoritive_answer: bool,
     truncation: bool,
     recursion_desired: bool,
     recursion_available: bool,
+    /// The reserved bit, which should be zero but is kept so messages survive a round trip.
+    z: bool,
+    authentic_data: bool,
+    checking_disabled: bool,
     response_code: ReturnCode,
 }
 
@@ -20,7 +24,7 @@ impl Header {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: Option<u16>,
-        is_answer: bool,
+        is_response: bool,
         opcode: Opcode,
         authoritive_answer: bool,
         truncation: bool,
@@ -30,16 +34,43 @@ impl Header {
     ) -> Self {
         Self {
             id: id.unwrap_or_else(rand::random),
-            is_answer,
+            is_response,
             opcode,
             authoritive_answer,
             truncation,
             recursion_desired,
             recursion_available,
+            z: false,
+            authentic_data: false,
+            checking_disabled: false,
             response_code,
         }
     }
 
+    pub fn with_id(mut self, id: u16) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Sets the AD bit, which says that the answer and authority sections have been validated
+    /// (RFC 4035 §3.2.3), or in a query that the client understands it (RFC 6840 §5.7).
+    pub fn with_authentic_data(mut self, authentic_data: bool) -> Self {
+        self.authentic_data = authentic_data;
+        self
+    }
+
+    /// Sets the CD bit, which asks a validating resolver not to check signatures (RFC 4035
+    /// §3.2.2).
+    pub fn with_checking_disabled(mut self, checking_disabled: bool) -> Self {
+        self.checking_disabled = checking_disabled;
+        self
+    }
+
+    pub fn with_z(mut self, z: bool) -> Self {
+        self.z = z;
+        self
+    }
+
     pub(crate) fn new_question(opcode: Opcode, recursion_desired: bool) -> Self {
         Self::new(
             None,
@@ -57,8 +88,12 @@ impl Header {
         self.id
     }
 
-    pub(crate) fn is_query(&self) -> bool {
-        self.is_answer
+    pub fn is_query(&self) -> bool {
+        !self.is_response
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.is_response
     }
 
     pub fn opcode(&self) -> Opcode {
@@ -81,42 +116,118 @@ impl Header {
         self.recursion_available
     }
 
+    pub fn z(&self) -> bool {
+        self.z
+    }
+
+    pub fn authentic_data(&self) -> bool {
+        self.authentic_data
+    }
+
+    pub fn checking_disabled(&self) -> bool {
+        self.checking_disabled
+    }
+
+    /// The lower four bits of the response code. Use
+    /// [`Message::response_code`](crate::message::Message::response_code) to include the extended
+    /// bits from EDNS.
     pub fn response_code(&self) -> ReturnCode {
         self.response_code
     }
 }
 
-type HeaderFlags<'p> = nom::IResult<&'p [u8], (bool, u8, bool, bool, bool, bool, u8, u8)>;
-
-fn parse_header_flags(input: &[u8]) -> HeaderFlags<'_> {
-    use nom::bits::{
-        bits,
-        streaming::{bool, take},
-    };
-    use nom::sequence::tuple;
-
-    bits::<_, _, nom::error::Error<(&[u8], usize)>, _, _>(tuple((
-        bool,
-        take(4_usize),
-        bool,
-        bool,
-        bool,
-        bool,
-        take(3_usize),
-        take(4_usize),
-    )))(input)
-}
-
 #[tracing::instrument(skip_all)]
-pub fn header_parser(i: &[u8]) -> nom::IResult<&[u8], Header> {
-    let (i, id) = nom::number::streaming::be_u16(i)?;
-
-    let (i, (qr, opcode, aa, tc, rd, ra, _zeros, return_code)) = parse_header_flags(i)?;
-    let opcode = Opcode::from(opcode);
-    let return_code = ReturnCode::from(return_code);
+pub fn header_parser(i: &[u8]) -> nom::IResult<&[u8], Header, crate::error::ParseError<'_>> {
+    let (i, id) = nom::number::complete::be_u16(i)?;
+    let (i, flags) = nom::number::complete::be_u16(i)?;
+
+    let qr = flags & 0x8000 != 0;
+    let opcode = Opcode::from(((flags >> 11) & 0x0F) as u8);
+    let aa = flags & 0x0400 != 0;
+    let tc = flags & 0x0200 != 0;
+    let rd = flags & 0x0100 != 0;
+    let ra = flags & 0x0080 != 0;
+    let z = flags & 0x0040 != 0;
+    let ad = flags & 0x0020 != 0;
+    let cd = flags & 0x0010 != 0;
+    let return_code = ReturnCode::from(flags & 0x000F);
 
     Ok((
         i,
-        Header::new(Some(id), qr, opcode, aa, tc, rd, ra, return_code),
+        Header::new(Some(id), qr, opcode, aa, tc, rd, ra, return_code)
+            .with_z(z)
+            .with_authentic_data(ad)
+            .with_checking_disabled(cd),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Message;
+
+    /// Parses a header with the given flags and no records, and writes it back out.
+    fn round_trip(flags: u16) -> Header {
+        let bytes = [&[0xAB, 0xCD][..], &flags.to_be_bytes(), &[0; 8]].concat();
+        let (rem, header) = header_parser(&bytes).unwrap();
+        assert_eq!(rem.len(), 8);
+        let message = Message::try_from(&bytes[..]).unwrap();
+        let encoded = Vec::try_from(&message).unwrap();
+        assert_eq!(encoded, bytes);
+        header
+    }
+
+    #[test]
+    fn ad_cd_and_z_are_read_and_written_on_their_own() {
+        let header = round_trip(0x0020);
+        assert!(header.authentic_data());
+        assert!(!header.checking_disabled());
+        assert!(!header.z());
+
+        let header = round_trip(0x0010);
+        assert!(!header.authentic_data());
+        assert!(header.checking_disabled());
+        assert!(!header.z());
+
+        let header = round_trip(0x0040);
+        assert!(!header.authentic_data());
+        assert!(!header.checking_disabled());
+        assert!(header.z());
+    }
+
+    #[test]
+    fn built_flags_are_written_to_their_bits() {
+        let header = |message: Message| Vec::try_from(&message).unwrap()[2..4].to_vec();
+        let ad = Message::builder().with_authentic_data(true).build();
+        assert_eq!(header(ad), [0x00, 0x20]);
+        let cd = Message::builder().with_checking_disabled(true).build();
+        assert_eq!(header(cd), [0x00, 0x10]);
+    }
+
+    #[test]
+    fn is_response_follows_qr() {
+        let header = round_trip(0x8000);
+        assert!(header.is_response());
+        assert!(!header.is_query());
+        assert_eq!(header.id(), 0xABCD);
+
+        let header = round_trip(0x0000);
+        assert!(!header.is_response());
+        assert!(header.is_query());
+
+        let response = Header::new(
+            Some(1),
+            true,
+            Opcode::Query,
+            false,
+            false,
+            false,
+            false,
+            ReturnCode::NoError,
+        );
+        assert!(response.is_response());
+        let query = Header::new_question(Opcode::Query, true);
+        assert!(!query.is_response());
+        assert!(query.recursion_desired());
+    }
+}
@@ -1,3 +1,4 @@
+/// The kind of message, from the IANA DNS OpCodes registry.
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, derive_more::Display)]
 pub enum Opcode {
@@ -7,16 +8,43 @@ pub enum Opcode {
     InverseQuery = 1,
     #[display(fmt = "ServerStatusReport")]
     ServerStatusReport = 2,
-    #[display(fmt = "Unknown Opcode ({}/{:01x}", _0, _0)]
+    /// RFC 1996
+    #[display(fmt = "Notify")]
+    Notify = 4,
+    /// RFC 2136
+    #[display(fmt = "Update")]
+    Update = 5,
+    /// DNS Stateful Operations (RFC 8490)
+    #[display(fmt = "StatefulOperations")]
+    StatefulOperations = 6,
+    #[display(fmt = "Unknown Opcode ({}/{:01x})", _0, _0)]
     Unknown(u8),
 }
 
+impl Opcode {
+    /// The short upper case name `dig` uses, such as `QUERY` or `NOTIFY`.
+    pub fn mnemonic(&self) -> String {
+        match self {
+            Self::Query => "QUERY".to_string(),
+            Self::InverseQuery => "IQUERY".to_string(),
+            Self::ServerStatusReport => "STATUS".to_string(),
+            Self::Notify => "NOTIFY".to_string(),
+            Self::Update => "UPDATE".to_string(),
+            Self::StatefulOperations => "DSO".to_string(),
+            Self::Unknown(n) => format!("RESERVED{n}"),
+        }
+    }
+}
+
 impl From<u8> for Opcode {
     fn from(value: u8) -> Self {
         match value {
             0 => Self::Query,
             1 => Self::InverseQuery,
             2 => Self::ServerStatusReport,
+            4 => Self::Notify,
+            5 => Self::Update,
+            6 => Self::StatefulOperations,
             n => Self::Unknown(n),
         }
     }
@@ -28,7 +56,31 @@ impl From<Opcode> for u8 {
             Opcode::Query => 0,
             Opcode::InverseQuery => 1,
             Opcode::ServerStatusReport => 2,
+            Opcode::Notify => 4,
+            Opcode::Update => 5,
+            Opcode::StatefulOperations => 6,
             Opcode::Unknown(n) => n,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_opcode_round_trips() {
+        for n in 0..=0x0F {
+            assert_eq!(u8::from(Opcode::from(n)), n);
+        }
+    }
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(Opcode::from(0).mnemonic(), "QUERY");
+        assert_eq!(Opcode::from(4).mnemonic(), "NOTIFY");
+        assert_eq!(Opcode::from(5).mnemonic(), "UPDATE");
+        assert_eq!(Opcode::from(6).mnemonic(), "DSO");
+        assert_eq!(Opcode::from(3).mnemonic(), "RESERVED3");
+    }
+}
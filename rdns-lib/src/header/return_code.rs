@@ -1,4 +1,9 @@
-#[repr(u8)]
+/// The result of a query, from the IANA DNS RCODEs registry.
+///
+/// Codes are 12 bits: the header only has room for the lower four, and the upper eight are carried
+/// in the OPT record (RFC 6891 §6.1.3). [`Message::response_code`](crate::message::Message::response_code)
+/// puts the two together.
+#[repr(u16)]
 #[derive(Copy, Clone, Debug, derive_more::Display)]
 pub enum ReturnCode {
     #[display(fmt = "No Error")]
@@ -13,12 +18,80 @@ pub enum ReturnCode {
     NotImplemented = 4,
     #[display(fmt = "Refused")]
     Refused = 5,
+    /// A name exists when it should not (RFC 2136).
+    #[display(fmt = "Name Exists")]
+    YXDomain = 6,
+    /// An RRset exists when it should not (RFC 2136).
+    #[display(fmt = "RRset Exists")]
+    YXRRSet = 7,
+    /// An RRset that should exist does not (RFC 2136).
+    #[display(fmt = "RRset Does Not Exist")]
+    NXRRSet = 8,
+    /// The server isn't authoritative for the zone (RFC 2136), or the request isn't authorized
+    /// (RFC 8945).
+    #[display(fmt = "Not Authorized")]
+    NotAuth = 9,
+    /// A name isn't in the zone (RFC 2136).
+    #[display(fmt = "Name Not In Zone")]
+    NotZone = 10,
+    /// The DSO-TYPE isn't implemented (RFC 8490).
+    #[display(fmt = "DSO-TYPE Not Implemented")]
+    DSOTypeNI = 11,
+    /// The EDNS version isn't supported (RFC 6891). TSIG uses the same code for BADSIG, a
+    /// signature that failed to verify (RFC 8945).
+    #[display(fmt = "Bad OPT Version")]
+    BadVers = 16,
+    #[display(fmt = "Key Not Recognized")]
+    BadKey = 17,
+    #[display(fmt = "Signature Out Of Time Window")]
+    BadTime = 18,
+    #[display(fmt = "Bad TKEY Mode")]
+    BadMode = 19,
+    #[display(fmt = "Duplicate Key Name")]
+    BadName = 20,
+    #[display(fmt = "Algorithm Not Supported")]
+    BadAlg = 21,
+    #[display(fmt = "Bad Truncation")]
+    BadTrunc = 22,
+    /// A missing or wrong server cookie (RFC 7873).
+    #[display(fmt = "Bad Server Cookie")]
+    BadCookie = 23,
     #[display(fmt = "Unknown Return Code ({}/{:01x})", _0, _0)]
-    Unknown(u8),
+    Unknown(u16),
 }
 
-impl From<u8> for ReturnCode {
-    fn from(value: u8) -> Self {
+impl ReturnCode {
+    /// The short upper case name `dig` uses, such as `NXDOMAIN` or `SERVFAIL`. Codes without a
+    /// name are written as `RCODE` and the number.
+    pub fn mnemonic(&self) -> String {
+        match self {
+            Self::NoError => "NOERROR".to_string(),
+            Self::FormatError => "FORMERR".to_string(),
+            Self::ServerFailure => "SERVFAIL".to_string(),
+            Self::NameError => "NXDOMAIN".to_string(),
+            Self::NotImplemented => "NOTIMP".to_string(),
+            Self::Refused => "REFUSED".to_string(),
+            Self::YXDomain => "YXDOMAIN".to_string(),
+            Self::YXRRSet => "YXRRSET".to_string(),
+            Self::NXRRSet => "NXRRSET".to_string(),
+            Self::NotAuth => "NOTAUTH".to_string(),
+            Self::NotZone => "NOTZONE".to_string(),
+            Self::DSOTypeNI => "DSOTYPENI".to_string(),
+            Self::BadVers => "BADVERS".to_string(),
+            Self::BadKey => "BADKEY".to_string(),
+            Self::BadTime => "BADTIME".to_string(),
+            Self::BadMode => "BADMODE".to_string(),
+            Self::BadName => "BADNAME".to_string(),
+            Self::BadAlg => "BADALG".to_string(),
+            Self::BadTrunc => "BADTRUNC".to_string(),
+            Self::BadCookie => "BADCOOKIE".to_string(),
+            Self::Unknown(n) => format!("RCODE{n}"),
+        }
+    }
+}
+
+impl From<u16> for ReturnCode {
+    fn from(value: u16) -> Self {
         match value {
             0 => Self::NoError,
             1 => Self::FormatError,
@@ -26,12 +99,26 @@ impl From<u8> for ReturnCode {
             3 => Self::NameError,
             4 => Self::NotImplemented,
             5 => Self::Refused,
+            6 => Self::YXDomain,
+            7 => Self::YXRRSet,
+            8 => Self::NXRRSet,
+            9 => Self::NotAuth,
+            10 => Self::NotZone,
+            11 => Self::DSOTypeNI,
+            16 => Self::BadVers,
+            17 => Self::BadKey,
+            18 => Self::BadTime,
+            19 => Self::BadMode,
+            20 => Self::BadName,
+            21 => Self::BadAlg,
+            22 => Self::BadTrunc,
+            23 => Self::BadCookie,
             n => Self::Unknown(n),
         }
     }
 }
 
-impl From<ReturnCode> for u8 {
+impl From<ReturnCode> for u16 {
     fn from(value: ReturnCode) -> Self {
         match value {
             ReturnCode::NoError => 0,
@@ -40,7 +127,48 @@ impl From<ReturnCode> for u8 {
             ReturnCode::NameError => 3,
             ReturnCode::NotImplemented => 4,
             ReturnCode::Refused => 5,
+            ReturnCode::YXDomain => 6,
+            ReturnCode::YXRRSet => 7,
+            ReturnCode::NXRRSet => 8,
+            ReturnCode::NotAuth => 9,
+            ReturnCode::NotZone => 10,
+            ReturnCode::DSOTypeNI => 11,
+            ReturnCode::BadVers => 16,
+            ReturnCode::BadKey => 17,
+            ReturnCode::BadTime => 18,
+            ReturnCode::BadMode => 19,
+            ReturnCode::BadName => 20,
+            ReturnCode::BadAlg => 21,
+            ReturnCode::BadTrunc => 22,
+            ReturnCode::BadCookie => 23,
             ReturnCode::Unknown(n) => n,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_code_round_trips() {
+        for n in 0..=0x0FFF {
+            assert_eq!(u16::from(ReturnCode::from(n)), n);
+        }
+        let named = (0..=0x0FFF)
+            .filter(|n| !matches!(ReturnCode::from(*n), ReturnCode::Unknown(_)))
+            .count();
+        assert_eq!(named, 20);
+    }
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(ReturnCode::NoError.mnemonic(), "NOERROR");
+        assert_eq!(ReturnCode::from(2).mnemonic(), "SERVFAIL");
+        assert_eq!(ReturnCode::from(3).mnemonic(), "NXDOMAIN");
+        assert_eq!(ReturnCode::from(16).mnemonic(), "BADVERS");
+        assert_eq!(ReturnCode::from(23).mnemonic(), "BADCOOKIE");
+        assert_eq!(ReturnCode::from(12).mnemonic(), "RCODE12");
+        assert_eq!(ReturnCode::from(4000).mnemonic(), "RCODE4000");
+    }
+}
@@ -1,13 +1,22 @@
 use std::fmt::Formatter;
 
-pub use error::Error;
+pub use error::{
+    ClientError, EncodeError, Error, Location, ParseError, Section, TextError, ZoneError,
+    ZoneErrorKind,
+};
 
+pub mod client;
 pub mod domain_name;
+pub mod edns;
+mod encoder;
 mod error;
 pub mod header;
 pub mod message;
+mod presentation;
 pub mod question;
+pub mod resolver;
 pub mod resource_record;
+pub mod zone;
 
 #[repr(u16)]
 #[derive(Debug, Copy, Clone)]
@@ -28,10 +37,27 @@ pub enum Type {
     MINFO = 14,
     MX = 15,
     TXT = 16,
+    AAAA = 28,
+    SRV = 33,
+    NAPTR = 35,
+    OPT = 41,
+    DS = 43,
+    SSHFP = 44,
+    RRSIG = 46,
+    NSEC = 47,
+    DNSKEY = 48,
+    NSEC3 = 50,
+    NSEC3PARAM = 51,
+    TLSA = 52,
+    SMIMEA = 53,
+    SVCB = 64,
+    HTTPS = 65,
     AXFR = 252,
     MAILB = 253,
     MAILA = 254,
     ALL = 255,
+    URI = 256,
+    CAA = 257,
     Unknown(u16),
 }
 
@@ -54,10 +80,27 @@ impl From<u16> for Type {
             14 => Self::MINFO,
             15 => Self::MX,
             16 => Self::TXT,
+            28 => Self::AAAA,
+            33 => Self::SRV,
+            35 => Self::NAPTR,
+            41 => Self::OPT,
+            43 => Self::DS,
+            44 => Self::SSHFP,
+            46 => Self::RRSIG,
+            47 => Self::NSEC,
+            48 => Self::DNSKEY,
+            50 => Self::NSEC3,
+            51 => Self::NSEC3PARAM,
+            52 => Self::TLSA,
+            53 => Self::SMIMEA,
+            64 => Self::SVCB,
+            65 => Self::HTTPS,
             252 => Self::AXFR,
             253 => Self::MAILB,
             254 => Self::MAILA,
             255 => Self::ALL,
+            256 => Self::URI,
+            257 => Self::CAA,
             x => Self::Unknown(x),
         }
     }
@@ -82,10 +125,27 @@ impl From<Type> for u16 {
             Type::MINFO => 14,
             Type::MX => 15,
             Type::TXT => 16,
+            Type::AAAA => 28,
+            Type::SRV => 33,
+            Type::NAPTR => 35,
+            Type::OPT => 41,
+            Type::DS => 43,
+            Type::SSHFP => 44,
+            Type::RRSIG => 46,
+            Type::NSEC => 47,
+            Type::DNSKEY => 48,
+            Type::NSEC3 => 50,
+            Type::NSEC3PARAM => 51,
+            Type::TLSA => 52,
+            Type::SMIMEA => 53,
+            Type::SVCB => 64,
+            Type::HTTPS => 65,
             Type::AXFR => 252,
             Type::MAILB => 253,
             Type::MAILA => 254,
             Type::ALL => 255,
+            Type::URI => 256,
+            Type::CAA => 257,
             Type::Unknown(x) => x,
         }
     }
@@ -110,11 +170,80 @@ impl std::fmt::Display for Type {
             Self::MINFO => write!(f, "MINFO"),
             Self::MX => write!(f, "MX"),
             Self::TXT => write!(f, "TXT"),
+            Self::AAAA => write!(f, "AAAA"),
+            Self::SRV => write!(f, "SRV"),
+            Self::NAPTR => write!(f, "NAPTR"),
+            Self::OPT => write!(f, "OPT"),
+            Self::DS => write!(f, "DS"),
+            Self::SSHFP => write!(f, "SSHFP"),
+            Self::RRSIG => write!(f, "RRSIG"),
+            Self::NSEC => write!(f, "NSEC"),
+            Self::DNSKEY => write!(f, "DNSKEY"),
+            Self::NSEC3 => write!(f, "NSEC3"),
+            Self::NSEC3PARAM => write!(f, "NSEC3PARAM"),
+            Self::TLSA => write!(f, "TLSA"),
+            Self::SMIMEA => write!(f, "SMIMEA"),
+            Self::SVCB => write!(f, "SVCB"),
+            Self::HTTPS => write!(f, "HTTPS"),
             Self::AXFR => write!(f, "AXFR"),
             Self::MAILB => write!(f, "MAILB"),
             Self::MAILA => write!(f, "MAILA"),
             Self::ALL => write!(f, "ALL"),
-            Self::Unknown(ty) => write!(f, "Unknown Type ({ty}/0x{ty:04x})"),
+            Self::URI => write!(f, "URI"),
+            Self::CAA => write!(f, "CAA"),
+            Self::Unknown(ty) => write!(f, "TYPE{ty}"),
+        }
+    }
+}
+
+/// Reads a type from its mnemonic, or from the generic `TYPEnnn` form of RFC 3597 §5.
+impl std::str::FromStr for Type {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "A" => Ok(Self::A),
+            "NS" => Ok(Self::NS),
+            "MD" => Ok(Self::MD),
+            "MF" => Ok(Self::MF),
+            "CNAME" => Ok(Self::CNAME),
+            "SOA" => Ok(Self::SOA),
+            "MB" => Ok(Self::MB),
+            "MG" => Ok(Self::MG),
+            "MR" => Ok(Self::MR),
+            "NULL" => Ok(Self::NULL),
+            "WKS" => Ok(Self::WKS),
+            "PTR" => Ok(Self::PTR),
+            "HINFO" => Ok(Self::HINFO),
+            "MINFO" => Ok(Self::MINFO),
+            "MX" => Ok(Self::MX),
+            "TXT" => Ok(Self::TXT),
+            "AAAA" => Ok(Self::AAAA),
+            "SRV" => Ok(Self::SRV),
+            "NAPTR" => Ok(Self::NAPTR),
+            "OPT" => Ok(Self::OPT),
+            "DS" => Ok(Self::DS),
+            "SSHFP" => Ok(Self::SSHFP),
+            "RRSIG" => Ok(Self::RRSIG),
+            "NSEC" => Ok(Self::NSEC),
+            "DNSKEY" => Ok(Self::DNSKEY),
+            "NSEC3" => Ok(Self::NSEC3),
+            "NSEC3PARAM" => Ok(Self::NSEC3PARAM),
+            "TLSA" => Ok(Self::TLSA),
+            "SMIMEA" => Ok(Self::SMIMEA),
+            "SVCB" => Ok(Self::SVCB),
+            "HTTPS" => Ok(Self::HTTPS),
+            "AXFR" => Ok(Self::AXFR),
+            "MAILB" => Ok(Self::MAILB),
+            "MAILA" => Ok(Self::MAILA),
+            "ALL" | "ANY" | "*" => Ok(Self::ALL),
+            "URI" => Ok(Self::URI),
+            "CAA" => Ok(Self::CAA),
+            other => other
+                .strip_prefix("TYPE")
+                .and_then(|n| n.parse::<u16>().ok())
+                .map(Self::from)
+                .ok_or_else(|| TextError::UnknownType(s.to_string())),
         }
     }
 }
@@ -126,7 +255,7 @@ pub enum Class {
     CSNET = 2,
     Chaos = 3,
     Hesiod = 4,
-    All = 5,
+    All = 255,
     Unknown(u16),
 }
 
@@ -137,7 +266,7 @@ impl From<u16> for Class {
             2 => Self::CSNET,
             3 => Self::Chaos,
             4 => Self::Hesiod,
-            5 => Self::All,
+            255 => Self::All,
             x => Self::Unknown(x),
         }
     }
@@ -150,7 +279,7 @@ impl From<Class> for u16 {
             Class::CSNET => 2,
             Class::Chaos => 3,
             Class::Hesiod => 4,
-            Class::All => 5,
+            Class::All => 255,
             Class::Unknown(x) => x,
         }
     }
@@ -163,8 +292,28 @@ impl std::fmt::Display for Class {
             Self::CSNET => write!(f, "CS"),
             Self::Chaos => write!(f, "CH"),
             Self::Hesiod => write!(f, "HS"),
-            Self::All => write!(f, "* "),
-            Self::Unknown(x) => write!(f, "Unknown Class ({x}/0x{x:04x}"),
+            Self::All => write!(f, "ANY"),
+            Self::Unknown(x) => write!(f, "CLASS{x}"),
+        }
+    }
+}
+
+/// Reads a class from its mnemonic, or from the generic `CLASSnnn` form of RFC 3597 §5.
+impl std::str::FromStr for Class {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "IN" => Ok(Self::Internet),
+            "CS" => Ok(Self::CSNET),
+            "CH" => Ok(Self::Chaos),
+            "HS" => Ok(Self::Hesiod),
+            "ANY" | "*" => Ok(Self::All),
+            other => other
+                .strip_prefix("CLASS")
+                .and_then(|n| n.parse::<u16>().ok())
+                .map(Self::from)
+                .ok_or_else(|| TextError::UnknownClass(s.to_string())),
         }
     }
 }
@@ -1,4 +1,5 @@
-use crate::{header, question, resource_record};
+use crate::error::{EncodeError, Error, Location, ParseError, Section};
+use crate::{edns, header, question, resource_record};
 
 #[derive(Debug, Clone)]
 pub struct Message {
@@ -7,6 +8,7 @@ pub struct Message {
     answers: Vec<resource_record::ResourceRecord>,
     authorities: Vec<resource_record::ResourceRecord>,
     additional_records: Vec<resource_record::ResourceRecord>,
+    edns: Option<edns::Edns>,
 }
 
 impl Message {
@@ -17,9 +19,28 @@ impl Message {
             answers: Vec::new(),
             authorities: Vec::new(),
             additional_records: Vec::new(),
+            edns: None,
         }
     }
 
+    /// Starts building a query with a random id, for the standard opcode.
+    pub fn builder() -> MessageBuilder {
+        MessageBuilder::new()
+    }
+
+    /// Starts building the response to `query`, which shares its id, opcode, questions, and
+    /// recursion desired and checking disabled flags.
+    pub fn response_to(query: &Message) -> MessageBuilder {
+        let mut builder = MessageBuilder::new()
+            .with_id(query.header.id())
+            .with_response(true)
+            .with_opcode(query.header.opcode())
+            .with_recursion_desired(query.header.recursion_desired())
+            .with_checking_disabled(query.header.checking_disabled());
+        builder.questions = query.questions.clone();
+        builder
+    }
+
     pub fn new_inverse_query(
         recursion_desired: bool,
         questions: Vec<resource_record::ResourceRecord>,
@@ -30,15 +51,24 @@ impl Message {
             answers: questions,
             authorities: Vec::new(),
             additional_records: Vec::new(),
+            edns: None,
         }
     }
 
     pub fn is_question(&self) -> bool {
-        !self.header.is_query()
+        self.header.is_query()
     }
 
     pub fn is_answer(&self) -> bool {
-        self.header.is_query()
+        self.header.is_response()
+    }
+
+    /// The full 12-bit response code, made up of the four bits in the header and, if there's an
+    /// OPT record, the eight extended bits from it.
+    pub fn response_code(&self) -> header::ReturnCode {
+        let low = u16::from(self.header.response_code()) & 0x000F;
+        let high = self.edns.as_ref().map_or(0, |e| e.extended_rcode() as u16);
+        header::ReturnCode::from((high << 4) | low)
     }
 
     pub fn header(&self) -> &header::Header {
@@ -57,20 +87,204 @@ impl Message {
         &self.authorities[..]
     }
 
+    /// The additional records, not including the OPT pseudo-record, which is available through
+    /// [`Message::edns`] instead.
     pub fn additional_records(&self) -> &[resource_record::ResourceRecord] {
         &self.additional_records[..]
     }
+
+    pub fn edns(&self) -> Option<&edns::Edns> {
+        self.edns.as_ref()
+    }
+
+    pub fn set_edns(&mut self, edns: Option<edns::Edns>) {
+        self.edns = edns;
+    }
+
+    pub fn set_id(&mut self, id: u16) {
+        self.header = self.header.with_id(id);
+    }
+}
+
+/// Puts together a [`Message`] a field and a record at a time.
+#[derive(Clone, Debug)]
+pub struct MessageBuilder {
+    id: Option<u16>,
+    is_response: bool,
+    opcode: header::Opcode,
+    authoritative_answer: bool,
+    truncation: bool,
+    recursion_desired: bool,
+    recursion_available: bool,
+    authentic_data: bool,
+    checking_disabled: bool,
+    response_code: header::ReturnCode,
+    questions: Vec<question::Question>,
+    answers: Vec<resource_record::ResourceRecord>,
+    authorities: Vec<resource_record::ResourceRecord>,
+    additional_records: Vec<resource_record::ResourceRecord>,
+    edns: Option<edns::Edns>,
+}
+
+impl MessageBuilder {
+    fn new() -> Self {
+        Self {
+            id: None,
+            is_response: false,
+            opcode: header::Opcode::Query,
+            authoritative_answer: false,
+            truncation: false,
+            recursion_desired: false,
+            recursion_available: false,
+            authentic_data: false,
+            checking_disabled: false,
+            response_code: header::ReturnCode::NoError,
+            questions: Vec::new(),
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additional_records: Vec::new(),
+            edns: None,
+        }
+    }
+
+    pub fn with_id(mut self, id: u16) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn with_response(mut self, is_response: bool) -> Self {
+        self.is_response = is_response;
+        self
+    }
+
+    pub fn with_opcode(mut self, opcode: header::Opcode) -> Self {
+        self.opcode = opcode;
+        self
+    }
+
+    pub fn with_authoritative_answer(mut self, authoritative_answer: bool) -> Self {
+        self.authoritative_answer = authoritative_answer;
+        self
+    }
+
+    pub fn with_truncation(mut self, truncation: bool) -> Self {
+        self.truncation = truncation;
+        self
+    }
+
+    pub fn with_recursion_desired(mut self, recursion_desired: bool) -> Self {
+        self.recursion_desired = recursion_desired;
+        self
+    }
+
+    pub fn with_recursion_available(mut self, recursion_available: bool) -> Self {
+        self.recursion_available = recursion_available;
+        self
+    }
+
+    pub fn with_authentic_data(mut self, authentic_data: bool) -> Self {
+        self.authentic_data = authentic_data;
+        self
+    }
+
+    pub fn with_checking_disabled(mut self, checking_disabled: bool) -> Self {
+        self.checking_disabled = checking_disabled;
+        self
+    }
+
+    /// Sets the response code. Codes above 15 don't fit in the header, so the upper bits go in
+    /// the OPT record, and one is added if the message doesn't have one yet.
+    pub fn with_response_code(mut self, response_code: header::ReturnCode) -> Self {
+        self.response_code = response_code;
+        self
+    }
+
+    /// Adds EDNS parameters. Their extended RCODE is replaced with the upper bits of the response
+    /// code when the message is built.
+    pub fn with_edns(mut self, edns: edns::Edns) -> Self {
+        self.edns = Some(edns);
+        self
+    }
+
+    pub fn add_question(mut self, question: question::Question) -> Self {
+        self.questions.push(question);
+        self
+    }
+
+    pub fn add_answer(mut self, record: resource_record::ResourceRecord) -> Self {
+        self.answers.push(record);
+        self
+    }
+
+    pub fn add_authority(mut self, record: resource_record::ResourceRecord) -> Self {
+        self.authorities.push(record);
+        self
+    }
+
+    pub fn add_additional_record(mut self, record: resource_record::ResourceRecord) -> Self {
+        self.additional_records.push(record);
+        self
+    }
+
+    pub fn build(self) -> Message {
+        let response_code = u16::from(self.response_code);
+        let extended_rcode = (response_code >> 4) as u8;
+        let edns = match self.edns {
+            Some(edns) => Some(edns.with_extended_rcode(extended_rcode)),
+            None if extended_rcode != 0 => {
+                Some(edns::Edns::new(512).with_extended_rcode(extended_rcode))
+            }
+            None => None,
+        };
+
+        Message {
+            header: header::Header::new(
+                self.id,
+                self.is_response,
+                self.opcode,
+                self.authoritative_answer,
+                self.truncation,
+                self.recursion_desired,
+                self.recursion_available,
+                header::ReturnCode::from(response_code & 0x000F),
+            )
+            .with_authentic_data(self.authentic_data)
+            .with_checking_disabled(self.checking_disabled),
+            questions: self.questions,
+            answers: self.answers,
+            authorities: self.authorities,
+            additional_records: self.additional_records,
+            edns,
+        }
+    }
+
+    /// Builds the message and encodes it, ready to be sent.
+    pub fn into_bytes(self) -> Result<Vec<u8>, EncodeError> {
+        Vec::try_from(&self.build())
+    }
 }
 
-impl From<Message> for Vec<u8> {
-    fn from(value: Message) -> Self {
-        let mut bytes: Vec<u8> = Vec::with_capacity(512);
+impl TryFrom<Message> for Vec<u8> {
+    type Error = EncodeError;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        Vec::try_from(&value)
+    }
+}
+
+/// Fails if a section has more entries than the header can count, or a record is longer than its
+/// RDLENGTH can say.
+impl TryFrom<&Message> for Vec<u8> {
+    type Error = EncodeError;
+
+    fn try_from(value: &Message) -> Result<Self, Self::Error> {
+        let mut encoder = crate::encoder::Encoder::with_compression();
 
         // Construct Header
-        bytes.extend_from_slice(&value.header.id().to_be_bytes());
+        encoder.put_u16(value.header.id());
         let mut packed: u16 = 0;
 
-        if value.header.is_query() {
+        if value.header.is_response() {
             packed += 1 << 15;
         }
         packed += ((u8::from(value.header.opcode()) as u16) & 0x00_0F) << 11;
@@ -86,25 +300,50 @@ impl From<Message> for Vec<u8> {
         if value.header.recursion_available() {
             packed += 1 << 7;
         }
-        packed += (u8::from(value.header.response_code()) as u16) & 0x00_0F;
+        if value.header.z() {
+            packed += 1 << 6;
+        }
+        if value.header.authentic_data() {
+            packed += 1 << 5;
+        }
+        if value.header.checking_disabled() {
+            packed += 1 << 4;
+        }
+        packed += u16::from(value.header.response_code()) & 0x00_0F;
 
-        bytes.extend_from_slice(&packed.to_be_bytes());
-        bytes.extend_from_slice(&(value.questions.len() as u16).to_be_bytes());
-        bytes.extend_from_slice(&(value.answers.len() as u16).to_be_bytes());
-        bytes.extend_from_slice(&(value.authorities.len() as u16).to_be_bytes());
-        bytes.extend_from_slice(&(value.additional_records.len() as u16).to_be_bytes());
+        encoder.put_u16(packed);
+        encoder.put_length("QDCOUNT", value.questions.len());
+        encoder.put_length("ANCOUNT", value.answers.len());
+        encoder.put_length("NSCOUNT", value.authorities.len());
+        encoder.put_length(
+            "ARCOUNT",
+            value.additional_records.len() + value.edns.iter().len(),
+        );
 
         // Construct questions
-        for question in value.questions {
-            bytes.extend_from_slice(Vec::from(question).as_slice());
+        for question in value.questions.iter() {
+            question.encode(&mut encoder);
         }
 
-        bytes
+        // Construct resource records
+        for record in value
+            .answers
+            .iter()
+            .chain(value.authorities.iter())
+            .chain(value.additional_records.iter())
+        {
+            record.encode(&mut encoder);
+        }
+        if let Some(edns) = &value.edns {
+            resource_record::ResourceRecord::from(edns).encode(&mut encoder);
+        }
+
+        encoder.finish()
     }
 }
 
 impl TryFrom<&[u8]> for Message {
-    type Error = crate::error::Error;
+    type Error = Error;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         parse(value)
@@ -112,9 +351,10 @@ impl TryFrom<&[u8]> for Message {
 }
 
 #[tracing::instrument(skip_all)]
-pub(crate) fn parse(message: &[u8]) -> Result<Message, crate::error::Error> {
+pub(crate) fn parse(message: &[u8]) -> Result<Message, Error> {
+    let header_location = Location::new(Section::Header, 0);
     let (
-        remaining,
+        mut remaining,
         (header, question_count, answer_count, nameserver_count, additional_record_count),
     ) = nom::sequence::tuple((
         header::header_parser,
@@ -123,25 +363,667 @@ pub(crate) fn parse(message: &[u8]) -> Result<Message, crate::error::Error> {
         nom::number::complete::be_u16,
         nom::number::complete::be_u16,
     ))(message)
-    .map_err(|_| crate::error::Error::FormatError)?;
-
-    let (_, (questions, answers, authorities, additional_records)) =
-        nom::combinator::all_consuming(nom::sequence::tuple((
-            nom::multi::count(question::parse(message), question_count as usize),
-            nom::multi::count(resource_record::parse(message), answer_count as usize),
-            nom::multi::count(resource_record::parse(message), nameserver_count as usize),
-            nom::multi::count(
-                resource_record::parse(message),
-                additional_record_count as usize,
-            ),
-        )))(remaining)
-        .map_err(|_| crate::error::Error::FormatError)?;
+    .map_err(|e| into_error(e, message, header_location))?;
+
+    let questions = parse_section(
+        message,
+        &mut remaining,
+        Section::Question,
+        question_count,
+        question::parse(message),
+    )?;
+    let answers = parse_section(
+        message,
+        &mut remaining,
+        Section::Answer,
+        answer_count,
+        resource_record::parse(message),
+    )?;
+    let authorities = parse_section(
+        message,
+        &mut remaining,
+        Section::Authority,
+        nameserver_count,
+        resource_record::parse(message),
+    )?;
+    let additional_records = parse_section(
+        message,
+        &mut remaining,
+        Section::Additional,
+        additional_record_count,
+        resource_record::parse(message),
+    )?;
+
+    if !remaining.is_empty() {
+        return Err(Error::TrailingData {
+            offset: message.len() - remaining.len(),
+            length: remaining.len(),
+        });
+    }
+
+    // RFC 6891 §6.1.1: at most one OPT record, and it must be owned by the root.
+    let mut edns = None;
+    let mut additional = Vec::with_capacity(additional_records.len());
+    for (index, (offset, record)) in additional_records.into_iter().enumerate() {
+        if !matches!(record.record_type(), crate::Type::OPT) {
+            additional.push(record);
+            continue;
+        }
+        if edns.is_some() || !record.name().labels().is_empty() {
+            return Err(Error::BadRecordField {
+                offset,
+                field: "OPT record",
+                location: Location::new(Section::Additional, index),
+            });
+        }
+        edns = edns::Edns::from_record(&record);
+    }
 
     Ok(Message {
         header,
-        questions,
-        answers,
-        authorities,
-        additional_records,
+        questions: questions.into_iter().map(|(_, q)| q).collect(),
+        answers: answers.into_iter().map(|(_, r)| r).collect(),
+        authorities: authorities.into_iter().map(|(_, r)| r).collect(),
+        additional_records: additional,
+        edns,
     })
 }
+
+/// Parses `count` entries of a section, pairing each with the offset it started at.
+fn parse_section<'m, T>(
+    message: &'m [u8],
+    input: &mut &'m [u8],
+    section: Section,
+    count: u16,
+    parser: impl Fn(&'m [u8]) -> nom::IResult<&'m [u8], T, ParseError<'m>>,
+) -> Result<Vec<(usize, T)>, Error> {
+    // The count comes from the header, so it's only trusted as far as there's room for that many
+    // entries. A question, the smallest kind, takes at least five bytes.
+    let mut entries = Vec::with_capacity((count as usize).min(input.len() / 5));
+    for index in 0..count as usize {
+        let offset = message.len() - input.len();
+        let (remaining, entry) =
+            parser(input).map_err(|e| into_error(e, message, Location::new(section, index)))?;
+        entries.push((offset, entry));
+        *input = remaining;
+    }
+    Ok(entries)
+}
+
+fn into_error(error: nom::Err<ParseError<'_>>, message: &[u8], location: Location) -> Error {
+    match error {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e.into_error(message, location),
+        nom::Err::Incomplete(_) => Error::Truncated {
+            offset: message.len(),
+            location,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64ct::{Base64, Encoding};
+
+    use super::*;
+    use crate::edns::{Edns, EdnsOption};
+    use crate::resource_record::{RecordData, ResourceRecord};
+
+    /// A response for the MX records of proton.me, as captured from a resolver.
+    const PROTON_MX: &str = "MRiBoAABAAIAAAAABnByb3RvbgJtZQAADwABwAwADwABAAACigAWAAoEbWFpbApwcm90b25tYWlsAmNoAMAMAA8AAQAAAooADAAUB21haWxzZWPALg==";
+
+    /// One record of every kind of RDATA, in presentation format.
+    const RECORDS: &[&str] = &[
+        "example. 60 A 192.0.2.1",
+        "example. 60 NS ns.example.",
+        "example. 60 MD mail.example.",
+        "example. 60 MF mail.example.",
+        "www.example. 60 CNAME example.",
+        "example. 60 SOA ns.example. hostmaster.example. 2024010101 7200 3600 1209600 300",
+        "example. 60 MB mail.example.",
+        "example. 60 MG mail.example.",
+        "example. 60 MR mail.example.",
+        "example. 60 NULL \\# 3 010203",
+        "example. 60 WKS 192.0.2.1 6 25 80",
+        "1.2.0.192.in-addr.arpa. 60 PTR example.",
+        "example. 60 HINFO \"x86_64\" \"Linux\"",
+        "example. 60 MINFO admin.example. errors.example.",
+        "example. 60 MX 10 mail.example.",
+        "example. 60 TXT \"v=spf1 -all\" \"\\000binary\\255\"",
+        "example. 60 AAAA 2001:db8::1",
+        "_sip._tcp.example. 60 SRV 10 60 5060 sip.example.",
+        "example. 60 NAPTR 100 10 \"u\" \"E2U+sip\" \"!^.*$!sip:info@example!\" .",
+        "example. 60 URI 10 1 \"https://example/\"",
+        "example. 60 DS 12345 8 2 49FD46E6C4B45C55D4AC69CBD3CD34AC1AFE51DE",
+        "example. 60 RRSIG A 8 1 300 20250101000000 20240101000000 12345 example. dGhpcyBpcyBub3QgYSBzaWduYXR1cmU=",
+        "example. 60 NSEC next.example. A NS SOA RRSIG NSEC TYPE1234",
+        "example. 60 DNSKEY 257 3 8 AwEAAagAIKlVZrpC6Ia7gEzahOR+9W29euxhJhVVLOyQbSEW0O8gcCjF",
+        "example. 60 NSEC3 1 1 10 AABBCCDD 2T7B4G4VSA5SMI47K61MV5BV1A22BOJR A RRSIG",
+        "example. 0 NSEC3PARAM 1 0 10 -",
+        "example. 60 SSHFP 4 2 123456789ABCDEF67890123456789ABCDEF67890123456789ABCDEF123456789",
+        "_443._tcp.example. 60 TLSA 3 1 1 0C72AC70B745AC19998811B131D662C9AC69DBDBE7CB23E5B514B56664C5D3D6",
+        "example. 60 SMIMEA 3 1 0 30820122",
+        "example. 60 SVCB 1 svc.example. mandatory=alpn alpn=h2,h3 port=8443 ipv4hint=192.0.2.1 ech=AAAA ipv6hint=2001:db8::1 key65000=\"x\"",
+        "example. 60 HTTPS 0 alias.example.",
+        "example. 60 CAA 128 issue \"ca.example\"",
+        "example. 60 TYPE65280 \\# 2 abcd",
+    ];
+
+    fn records() -> Vec<ResourceRecord> {
+        let edns = Edns::new(1232)
+            .with_option(EdnsOption::NameServerIdentifier(b"ns1".to_vec()))
+            .with_option(EdnsOption::Padding(vec![0; 4]));
+        RECORDS
+            .iter()
+            .map(|text| text.parse().unwrap_or_else(|e| panic!("{text}: {e}")))
+            .chain([ResourceRecord::from(&edns)])
+            .collect()
+    }
+
+    #[test]
+    fn captured_response_round_trips() {
+        let bytes = Base64::decode_vec(PROTON_MX).unwrap();
+        let message = Message::try_from(&bytes[..]).unwrap();
+
+        assert!(message.is_answer());
+        assert_eq!(message.questions().len(), 1);
+        assert_eq!(message.answers().len(), 2);
+        assert_eq!(
+            message.answers()[0].to_string(),
+            "proton.me. 650 IN MX 10 mail.protonmail.ch."
+        );
+        assert_eq!(Vec::try_from(&message).unwrap(), bytes);
+    }
+
+    #[test]
+    fn every_record_type_round_trips_with_its_rdlength() {
+        for record in records() {
+            let bytes = Vec::try_from(&record).unwrap();
+            let rdata = Vec::try_from(record.rdata()).unwrap();
+            assert!(bytes.ends_with(&rdata), "{record}");
+            let rdlength = &bytes[bytes.len() - rdata.len() - 2..][..2];
+            assert_eq!(
+                u16::from_be_bytes([rdlength[0], rdlength[1]]) as usize,
+                rdata.len(),
+                "{record}"
+            );
+
+            let (rest, parsed) = crate::resource_record::parse(&bytes)(&bytes).unwrap();
+            assert!(rest.is_empty(), "{record}");
+            assert_eq!(
+                std::mem::discriminant(parsed.rdata()),
+                std::mem::discriminant(record.rdata()),
+                "{record}"
+            );
+            assert_eq!(parsed.to_string(), record.to_string());
+            assert_eq!(Vec::try_from(&parsed).unwrap(), bytes, "{record}");
+        }
+    }
+
+    #[test]
+    fn compressed_message_round_trips() {
+        let mut builder = Message::response_to(
+            &Message::builder()
+                .with_id(0x1234)
+                .add_question("example. A".parse().unwrap())
+                .build(),
+        )
+        .with_authentic_data(true)
+        .with_edns(Edns::new(4096).with_dnssec_ok(true));
+        for record in records()
+            .into_iter()
+            .filter(|r| !matches!(r.rdata(), RecordData::OPT(_)))
+        {
+            builder = builder.add_answer(record);
+        }
+        let message = builder.build();
+        let bytes = Vec::try_from(&message).unwrap();
+        let uncompressed: usize = message
+            .answers()
+            .iter()
+            .chain([&ResourceRecord::from(message.edns().unwrap())])
+            .map(|r| Vec::try_from(r).unwrap().len())
+            .sum();
+        assert!(bytes.len() < 12 + 13 + uncompressed);
+
+        let message = Message::try_from(&bytes[..]).unwrap();
+        assert_eq!(message.answers().len(), RECORDS.len());
+        assert!(message.edns().unwrap().dnssec_ok());
+        assert_eq!(Vec::try_from(&message).unwrap(), bytes);
+    }
+
+    #[test]
+    fn lengths_that_do_not_fit_are_errors() {
+        use crate::resource_record::{Null, SvcParam, SVCB};
+        use crate::{Class, EncodeError, Type};
+
+        let too_long = |field| {
+            Err::<Vec<u8>, _>(EncodeError::TooLong {
+                field,
+                value: 70_000,
+            })
+        };
+        let record = |ty, rdata| {
+            ResourceRecord::new(
+                crate::domain_name::DomainName::root(),
+                ty,
+                Class::Internet,
+                60,
+                rdata,
+            )
+        };
+
+        let null = record(Type::NULL, RecordData::Null(Null::new(vec![0; 70_000])));
+        assert_eq!(Vec::try_from(&null), too_long("RDLENGTH"));
+        let message = Message::builder().add_answer(null).build();
+        assert_eq!(Vec::try_from(&message), too_long("RDLENGTH"));
+
+        let svcb = SVCB::new(
+            1,
+            crate::domain_name::DomainName::root(),
+            vec![SvcParam::Ech(vec![0; 70_000])],
+        );
+        let svcb = record(Type::SVCB, RecordData::SVCB(svcb.unwrap()));
+        assert_eq!(Vec::try_from(&svcb), too_long("SvcParam length"));
+
+        let padding = Edns::new(1232).with_option(EdnsOption::Padding(vec![0; 70_000]));
+        let message = Message::builder().with_edns(padding).into_bytes();
+        assert_eq!(message, too_long("EDNS option length"));
+
+        let question: crate::question::Question = "example. A".parse().unwrap();
+        let mut builder = Message::builder();
+        for _ in 0..70_000 {
+            builder = builder.add_question(question.clone());
+        }
+        assert_eq!(builder.into_bytes(), too_long("QDCOUNT"));
+    }
+
+    /// A response with two A records for example., whose RDATA starts at offsets 37 and 53.
+    fn two_answers() -> Vec<u8> {
+        let query = Message::builder()
+            .add_question("example. A".parse().unwrap())
+            .build();
+        let bytes = Message::response_to(&query)
+            .add_answer("example. 60 A 192.0.2.1".parse().unwrap())
+            .add_answer("example. 60 A 192.0.2.2".parse().unwrap())
+            .into_bytes()
+            .unwrap();
+        assert_eq!(bytes[37..41], [192, 0, 2, 1]);
+        assert_eq!(bytes[53..], [192, 0, 2, 2]);
+        bytes
+    }
+
+    /// Checks where an error says it happened.
+    fn assert_at(error: &Error, offset: usize, section: Section, index: usize) {
+        assert_eq!(error.offset(), offset, "{error:?}");
+        assert_eq!(
+            error.location(),
+            Some(Location::new(section, index)),
+            "{error:?}"
+        );
+    }
+
+    #[test]
+    fn truncated_messages_say_where_they_end() {
+        let bytes = two_answers();
+        for (length, offset, section, index) in [
+            (5, 4, Section::Header, 0),
+            (30, 29, Section::Answer, 0),
+            (45, 45, Section::Answer, 1),
+            (55, 53, Section::Answer, 1),
+        ] {
+            let error = Message::try_from(&bytes[..length]).unwrap_err();
+            assert!(matches!(error, Error::Truncated { .. }), "{error:?}");
+            assert_at(&error, offset, section, index);
+        }
+    }
+
+    #[test]
+    fn data_after_the_last_record_is_an_error() {
+        let mut bytes = two_answers();
+        bytes.extend_from_slice(&[0; 3]);
+        let error = Message::try_from(&bytes[..]).unwrap_err();
+        assert!(
+            matches!(error, Error::TrailingData { length: 3, .. }),
+            "{error:?}"
+        );
+        assert_eq!(error.offset(), 57);
+        assert_eq!(error.location(), None);
+    }
+
+    #[test]
+    fn rdata_must_fill_its_rdlength_exactly() {
+        let mut bytes = two_answers();
+        bytes[51..53].copy_from_slice(&5_u16.to_be_bytes());
+        bytes.push(0);
+        let error = Message::try_from(&bytes[..]).unwrap_err();
+        assert!(
+            matches!(error, Error::RdataLengthMismatch { rdlength: 5, .. }),
+            "{error:?}"
+        );
+        assert_at(&error, 57, Section::Answer, 1);
+
+        let mut bytes = two_answers();
+        bytes[51..53].copy_from_slice(&3_u16.to_be_bytes());
+        bytes.pop();
+        let error = Message::try_from(&bytes[..]).unwrap_err();
+        assert!(
+            matches!(error, Error::RdataLengthMismatch { rdlength: 3, .. }),
+            "{error:?}"
+        );
+        assert_at(&error, 53, Section::Answer, 1);
+    }
+
+    #[test]
+    fn invalid_fields_are_named() {
+        let mut bytes = Message::builder()
+            .add_question("example. CAA".parse().unwrap())
+            .add_answer("example. 60 CAA 0 issue \"ca.example\"".parse().unwrap())
+            .into_bytes()
+            .unwrap();
+        // The tag length, which mustn't be zero.
+        assert_eq!(bytes[38], 5);
+        bytes[38] = 0;
+        let error = Message::try_from(&bytes[..]).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                Error::BadRecordField {
+                    field: "CAA tag",
+                    ..
+                }
+            ),
+            "{error:?}"
+        );
+        assert_at(&error, 38, Section::Answer, 0);
+    }
+
+    #[test]
+    fn only_one_opt_record_owned_by_the_root_is_allowed() {
+        let query = || Message::builder().add_question("example. A".parse().unwrap());
+        let edns = Edns::new(1232);
+
+        let bytes = query()
+            .with_edns(edns.clone())
+            .add_additional_record(ResourceRecord::from(&edns))
+            .into_bytes()
+            .unwrap();
+        let error = Message::try_from(&bytes[..]).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                Error::BadRecordField {
+                    field: "OPT record",
+                    ..
+                }
+            ),
+            "{error:?}"
+        );
+        assert_at(&error, 36, Section::Additional, 1);
+
+        let opt = ResourceRecord::from(&edns);
+        let misplaced = ResourceRecord::new(
+            "example.".parse().unwrap(),
+            opt.record_type(),
+            opt.class(),
+            opt.ttl(),
+            opt.rdata().clone(),
+        );
+        let bytes = query()
+            .add_additional_record(misplaced)
+            .into_bytes()
+            .unwrap();
+        let error = Message::try_from(&bytes[..]).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                Error::BadRecordField {
+                    field: "OPT record",
+                    ..
+                }
+            ),
+            "{error:?}"
+        );
+        assert_at(&error, 25, Section::Additional, 0);
+    }
+
+    #[test]
+    fn huge_counts_in_a_short_message_are_not_trusted() {
+        let mut bytes = two_answers();
+        bytes[4..12].copy_from_slice(&[0xFF; 8]);
+        let error = Message::try_from(&bytes[..]).unwrap_err();
+        assert!(matches!(error, Error::Truncated { .. }), "{error:?}");
+    }
+
+    /// Splits the answer section of a message with one question into each record's owner and
+    /// RDATA, as they are on the wire.
+    fn answers_on_the_wire(bytes: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let skip_name = |mut i: usize| loop {
+            match bytes[i] {
+                0 => return i + 1,
+                length if length & 0xC0 == 0xC0 => return i + 2,
+                length => i += 1 + length as usize,
+            }
+        };
+        let mut i = skip_name(12) + 4;
+        let count = u16::from_be_bytes([bytes[6], bytes[7]]);
+        (0..count)
+            .map(|_| {
+                let owner_end = skip_name(i);
+                let rdata = owner_end + 10;
+                let length = u16::from_be_bytes([bytes[rdata - 2], bytes[rdata - 1]]) as usize;
+                let record = (&bytes[i..owner_end], &bytes[rdata..rdata + length]);
+                i = rdata + length;
+                record
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_are_only_compressed_where_allowed() {
+        let records = [
+            "example. 60 NS ns.example.",
+            "example. 60 MX 10 ns.example.",
+            "example. 60 SOA ns.example. hostmaster.example. 1 7200 3600 1209600 300",
+            "example. 60 SRV 10 60 53 ns.example.",
+            "example. 60 NAPTR 100 10 \"u\" \"E2U+sip\" \"\" ns.example.",
+            "example. 60 RRSIG A 8 1 300 20250101000000 20240101000000 12345 example. AAAA",
+            "example. 60 NSEC ns.example. A",
+            "example. 60 SVCB 1 ns.example.",
+        ];
+        let mut builder = Message::builder().add_question("example. A".parse().unwrap());
+        for record in records {
+            builder = builder.add_answer(record.parse().unwrap());
+        }
+        let message = builder.build();
+        let bytes = Vec::try_from(&message).unwrap();
+
+        let answers = answers_on_the_wire(&bytes);
+        assert_eq!(answers.len(), records.len());
+        // Every owner is a pointer to the question's name at offset 12.
+        for (owner, _) in answers.iter() {
+            assert_eq!(owner, &[0xC0, 0x0C]);
+        }
+
+        // NS writes "ns" and points at "example.". MX and SOA point at names already written.
+        let ns = answers[0].1;
+        assert_eq!(ns, [2, b'n', b's', 0xC0, 0x0C]);
+        let ns_offset = ns.as_ptr() as usize - bytes.as_ptr() as usize;
+        let pointer = [0xC0 | (ns_offset >> 8) as u8, ns_offset as u8];
+        assert_eq!(answers[1].1, [&[0, 10][..], &pointer].concat());
+        assert_eq!(answers[2].1[..2], pointer);
+        assert_eq!(answers[2].1[2..13], *b"\x0ahostmaster");
+        assert_eq!(answers[2].1[13..15], [0xC0, 0x0C]);
+
+        // RFC 3597 §4: names in types defined after RFC 1035 are written out in full.
+        for (record, (_, rdata)) in records.iter().zip(answers.iter()).skip(3) {
+            let full: &[u8] = match record.contains("RRSIG") {
+                true => b"\x07example\x00",
+                false => b"\x02ns\x07example\x00",
+            };
+            assert!(
+                rdata.windows(full.len()).any(|w| w == full),
+                "{record}: {rdata:?}"
+            );
+        }
+
+        let uncompressed: usize = message
+            .answers()
+            .iter()
+            .map(|r| Vec::try_from(r).unwrap().len())
+            .sum();
+        assert!(bytes.len() < 12 + 13 + uncompressed);
+    }
+
+    fn round_trip(message: &Message) -> Message {
+        Message::try_from(&Vec::try_from(message).unwrap()[..]).unwrap()
+    }
+
+    #[test]
+    fn responses_copy_the_query() {
+        let query = Message::builder()
+            .with_id(0x1234)
+            .with_opcode(header::Opcode::Notify)
+            .with_recursion_desired(true)
+            .with_checking_disabled(true)
+            .with_authentic_data(true)
+            .add_question("example. A".parse().unwrap())
+            .add_question("example. AAAA".parse().unwrap())
+            .build();
+
+        let response = round_trip(&Message::response_to(&query).build());
+        let header = response.header();
+        assert_eq!(header.id(), 0x1234);
+        assert!(header.is_response());
+        assert_eq!(u8::from(header.opcode()), 4);
+        assert!(header.recursion_desired());
+        assert!(header.checking_disabled());
+        // AD is about the data in the response, so it isn't copied from the query.
+        assert!(!header.authentic_data());
+        let questions: Vec<_> = response.questions().iter().map(|q| q.to_string()).collect();
+        let asked: Vec<_> = query.questions().iter().map(|q| q.to_string()).collect();
+        assert_eq!(questions, asked);
+
+        let query = Message::builder().with_id(1).build();
+        let header = Message::response_to(&query).build().header;
+        assert!(!header.recursion_desired());
+        assert!(!header.checking_disabled());
+    }
+
+    #[test]
+    fn each_flag_is_set_on_its_own() {
+        type Setter = fn(MessageBuilder) -> MessageBuilder;
+        type Getter = fn(&header::Header) -> bool;
+        let flags: &[(Setter, Getter, u16)] = &[
+            (|b| b.with_response(true), |h| h.is_response(), 0x8000),
+            (
+                |b| b.with_authoritative_answer(true),
+                |h| h.authoritive_answer(),
+                0x0400,
+            ),
+            (|b| b.with_truncation(true), |h| h.truncation(), 0x0200),
+            (
+                |b| b.with_recursion_desired(true),
+                |h| h.recursion_desired(),
+                0x0100,
+            ),
+            (
+                |b| b.with_recursion_available(true),
+                |h| h.recursion_available(),
+                0x0080,
+            ),
+            (
+                |b| b.with_authentic_data(true),
+                |h| h.authentic_data(),
+                0x0020,
+            ),
+            (
+                |b| b.with_checking_disabled(true),
+                |h| h.checking_disabled(),
+                0x0010,
+            ),
+        ];
+        let getters: Vec<Getter> = flags.iter().map(|(_, get, _)| *get).collect();
+
+        for (i, (set, _, bit)) in flags.iter().enumerate() {
+            let message = set(Message::builder().with_id(7)).build();
+            let bytes = Vec::try_from(&message).unwrap();
+            assert_eq!(u16::from_be_bytes([bytes[2], bytes[3]]), *bit);
+
+            let header = *round_trip(&message).header();
+            for (j, get) in getters.iter().enumerate() {
+                assert_eq!(get(&header), i == j, "flag {j} with flag {i} set");
+            }
+        }
+    }
+
+    #[test]
+    fn opcode_and_response_code_are_written_to_the_header() {
+        let message = Message::builder()
+            .with_opcode(header::Opcode::Update)
+            .with_response_code(header::ReturnCode::Refused)
+            .build();
+        let bytes = Vec::try_from(&message).unwrap();
+        assert_eq!(u16::from_be_bytes([bytes[2], bytes[3]]), (5 << 11) | 5);
+        assert!(message.edns().is_none());
+    }
+
+    #[test]
+    fn extended_response_codes_add_an_opt_record() {
+        let message = Message::builder()
+            .with_response_code(header::ReturnCode::BadCookie)
+            .build();
+        assert_eq!(u16::from(message.header().response_code()), 23 & 0xF);
+        let edns = message.edns().unwrap();
+        assert_eq!(edns.udp_payload_size(), 512);
+        assert_eq!(edns.extended_rcode(), 1);
+
+        let message = round_trip(&message);
+        assert_eq!(message.response_code().mnemonic(), "BADCOOKIE");
+        assert_eq!(message.edns().unwrap().udp_payload_size(), 512);
+    }
+
+    #[test]
+    fn the_response_code_replaces_the_callers_extended_rcode() {
+        let message = Message::builder()
+            .with_edns(Edns::new(1232).with_extended_rcode(5))
+            .with_response_code(header::ReturnCode::BadVers)
+            .build();
+        let edns = message.edns().unwrap();
+        assert_eq!(edns.udp_payload_size(), 1232);
+        assert_eq!(edns.extended_rcode(), 1);
+        assert_eq!(u16::from(message.header().response_code()), 0);
+
+        let message = Message::builder()
+            .with_edns(Edns::new(1232).with_extended_rcode(5))
+            .with_response_code(header::ReturnCode::NameError)
+            .build();
+        assert_eq!(message.edns().unwrap().extended_rcode(), 0);
+        assert_eq!(message.response_code().mnemonic(), "NXDOMAIN");
+    }
+
+    #[test]
+    fn response_codes_combine_the_header_and_opt_bits() {
+        let with_opt = |rcode: header::ReturnCode, extended_rcode: u8| {
+            let mut message = Message::builder().with_response_code(rcode).build();
+            message.set_edns(Some(Edns::new(1232).with_extended_rcode(extended_rcode)));
+            round_trip(&message).response_code()
+        };
+        let badvers = with_opt(header::ReturnCode::NoError, 1);
+        assert_eq!(u16::from(badvers), 16);
+        assert_eq!(badvers.mnemonic(), "BADVERS");
+        assert_eq!(u16::from(with_opt(header::ReturnCode::NameError, 1)), 19);
+        assert_eq!(u16::from(with_opt(header::ReturnCode::Refused, 0xFA)), 4005);
+        assert_eq!(
+            with_opt(header::ReturnCode::NameError, 0).mnemonic(),
+            "NXDOMAIN"
+        );
+
+        let without_opt = Message::builder()
+            .with_response_code(header::ReturnCode::ServerFailure)
+            .build();
+        assert_eq!(
+            round_trip(&without_opt).response_code().mnemonic(),
+            "SERVFAIL"
+        );
+    }
+}
@@ -0,0 +1,690 @@
+//! Helpers for reading and writing values in their zone file presentation formats.
+
+use std::str::FromStr;
+
+use base64ct::{Base64, Encoding};
+
+use crate::domain_name::DomainName;
+use crate::error::TextError;
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+/// Writes a character-string in double quotes, escaping quotes and backslashes, and any bytes that
+/// aren't printable ASCII as `\DDD` (RFC 1035 §5.1).
+pub(crate) fn quoted(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len() + 2);
+    output.push('"');
+    for byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                output.push('\\');
+                output.push(*byte as char);
+            }
+            0x20..=0x7E => output.push(*byte as char),
+            _ => output.push_str(&format!("\\{byte:03}")),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// Reads the rest of an escape sequence once its backslash has been consumed: either `\DDD` with a
+/// decimal octet value, or `\X` standing for the character X itself (RFC 1035 §5.1).
+pub(crate) fn unescape(bytes: &mut std::slice::Iter<u8>) -> Option<u8> {
+    let first = *bytes.next()?;
+    if !first.is_ascii_digit() {
+        return Some(first);
+    }
+    let mut value = (first - b'0') as u16;
+    for _ in 0..2 {
+        let digit = *bytes.next()?;
+        if !digit.is_ascii_digit() {
+            return None;
+        }
+        value = value * 10 + (digit - b'0') as u16;
+    }
+    u8::try_from(value).ok()
+}
+
+/// Writes RDATA in the generic `\# <length> <hex>` format of RFC 3597 §5.
+pub(crate) fn generic(data: &[u8]) -> String {
+    match data.is_empty() {
+        true => "\\# 0".to_string(),
+        false => format!("\\# {} {}", data.len(), hex(data)),
+    }
+}
+
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    Base64::encode_string(bytes)
+}
+
+/// Base 32 with the extended hex alphabet and no padding, as used for NSEC3 hashes (RFC 5155 §3.3).
+pub(crate) fn base32hex(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+    let mut output = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let mut buffer: u16 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(ALPHABET[((buffer >> bits) & 0x1F) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        output.push(ALPHABET[((buffer << (5 - bits)) & 0x1F) as usize] as char);
+    }
+    output
+}
+
+/// Formats a number of seconds since the epoch as `YYYYMMDDHHmmSS` in UTC (RFC 4034 §3.2).
+pub(crate) fn timestamp(seconds: u32) -> String {
+    let days = (seconds / 86_400) as i64;
+    let remainder = seconds % 86_400;
+
+    // Civil-from-days, from Howard Hinnant's date algorithms.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{year:04}{month:02}{day:02}{:02}{:02}{:02}",
+        remainder / 3_600,
+        remainder % 3_600 / 60,
+        remainder % 60
+    )
+}
+
+/// Reads a timestamp written as `YYYYMMDDHHmmSS`, or as a plain number of seconds since the epoch
+/// (RFC 4034 §3.2).
+pub(crate) fn from_timestamp(text: &str) -> Option<u32> {
+    if text.len() != 14 || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return text.parse().ok();
+    }
+    let field = |range: std::ops::Range<usize>| text[range].parse::<i64>().ok();
+    let (year, month, day) = (field(0..4)?, field(4..6)?, field(6..8)?);
+    let (hour, minute, second) = (field(8..10)?, field(10..12)?, field(12..14)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    // Days-from-civil, the inverse of the algorithm in `timestamp`.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    u32::try_from(days * 86_400 + hour * 3_600 + minute * 60 + second).ok()
+}
+
+/// Reads pairs of hex digits, in either case. Nothing else is allowed, not even a sign.
+pub(crate) fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    let digit = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| Some((digit(pair[0])? << 4) | digit(pair[1])?))
+        .collect()
+}
+
+pub(crate) fn from_base64(text: &str) -> Option<Vec<u8>> {
+    Base64::decode_vec(text).ok()
+}
+
+pub(crate) fn from_base32hex(text: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(text.len() * 5 / 8);
+    let mut buffer: u16 = 0;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'0'..=b'9' => c - b'0',
+            c @ b'A'..=b'V' => c - b'A' + 10,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value as u16;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    Some(output)
+}
+
+/// Reads a TTL or other time interval, either as a number of seconds or with BIND-style units such
+/// as `1h30m`.
+pub(crate) fn from_duration(text: &str) -> Option<u32> {
+    if let Ok(seconds) = text.parse() {
+        return Some(seconds);
+    }
+
+    let mut total: u32 = 0;
+    let mut number: Option<u32> = None;
+    for c in text.bytes() {
+        match c.to_ascii_lowercase() {
+            c @ b'0'..=b'9' => {
+                number = Some(
+                    number
+                        .unwrap_or(0)
+                        .checked_mul(10)?
+                        .checked_add((c - b'0') as u32)?,
+                )
+            }
+            unit => {
+                let scale = match unit {
+                    b's' => 1,
+                    b'm' => 60,
+                    b'h' => 3_600,
+                    b'd' => 86_400,
+                    b'w' => 604_800,
+                    _ => return None,
+                };
+                total = total.checked_add(number.take()?.checked_mul(scale)?)?;
+            }
+        }
+    }
+    match number {
+        Some(_) => None,
+        None => Some(total),
+    }
+}
+
+/// Turns a character-string's escapes back into the bytes they stand for.
+pub(crate) fn unescape_string(text: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(text.len());
+    let mut bytes = text.as_bytes().iter();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\\' => output.push(unescape(&mut bytes)?),
+            _ => output.push(*byte),
+        }
+    }
+    Some(output)
+}
+
+/// A single field of presentation text. Escapes are left in place, since what they mean depends
+/// on the field: an escaped dot in a name doesn't separate labels, for instance.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Token<'a> {
+    text: &'a str,
+    quoted: bool,
+}
+
+impl<'a> Token<'a> {
+    /// The text of the token, without the surrounding quotes if it had them.
+    pub(crate) fn text(&self) -> &'a str {
+        self.text
+    }
+
+    pub(crate) fn is_quoted(&self) -> bool {
+        self.quoted
+    }
+}
+
+/// One entry of presentation text: a record or a directive, which may have been spread over several
+/// lines with parentheses.
+#[derive(Clone, Debug)]
+pub(crate) struct Entry<'a> {
+    /// Whether the entry starts with whitespace, meaning it has the same owner as the one before.
+    pub(crate) blank_owner: bool,
+    pub(crate) tokens: Vec<Token<'a>>,
+}
+
+/// Splits text into entries and their tokens following the rules of RFC 1035 §5.1: fields are
+/// separated by whitespace, `;` starts a comment, quotes group a field with spaces in it, and
+/// parentheses let an entry continue over several lines.
+pub(crate) struct Tokenizer<'a> {
+    text: &'a str,
+    position: usize,
+    line: usize,
+    entry_line: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            position: 0,
+            line: 1,
+            entry_line: 1,
+        }
+    }
+
+    /// The line that the entry returned last, or the one that failed to be read, started on.
+    pub(crate) fn entry_line(&self) -> usize {
+        self.entry_line
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.position).copied()
+    }
+
+    /// Moves past an escape sequence, taking care not to stop in the middle of a character.
+    fn skip_escape(&mut self) {
+        self.position += 1;
+        if self.peek() == Some(b'\n') {
+            self.line += 1;
+        }
+        self.position += self.text[self.position..]
+            .chars()
+            .next()
+            .map_or(0, char::len_utf8);
+    }
+
+    /// Moves past a quoted string, starting on its opening quote, and returns its contents.
+    fn quoted(&mut self) -> Result<&'a str, TextError> {
+        self.position += 1;
+        let start = self.position;
+        loop {
+            match self.peek() {
+                Some(b'"') => break,
+                Some(b'\\') => self.skip_escape(),
+                Some(b'\n') | None => return Err(TextError::UnterminatedQuote),
+                Some(_) => self.position += 1,
+            }
+        }
+        let contents = &self.text[start..self.position];
+        self.position += 1;
+        Ok(contents)
+    }
+
+    fn entry(&mut self) -> Result<Entry<'a>, TextError> {
+        self.entry_line = self.line;
+        let mut entry = Entry {
+            blank_owner: matches!(self.peek(), Some(b' ' | b'\t')),
+            tokens: Vec::new(),
+        };
+        let mut depth = 0;
+
+        while let Some(byte) = self.peek() {
+            match byte {
+                b'\n' => {
+                    self.position += 1;
+                    self.line += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                b' ' | b'\t' | b'\r' => self.position += 1,
+                b';' => {
+                    while !matches!(self.peek(), Some(b'\n') | None) {
+                        self.position += 1;
+                    }
+                }
+                b'(' => {
+                    depth += 1;
+                    self.position += 1;
+                }
+                b')' if depth == 0 => return Err(TextError::UnbalancedParentheses),
+                b')' => {
+                    depth -= 1;
+                    self.position += 1;
+                }
+                b'"' => {
+                    let text = self.quoted()?;
+                    entry.tokens.push(Token { text, quoted: true });
+                }
+                _ => {
+                    let start = self.position;
+                    while let Some(byte) = self.peek() {
+                        match byte {
+                            b' ' | b'\t' | b'\r' | b'\n' | b';' | b'(' | b')' => break,
+                            b'\\' => self.skip_escape(),
+                            // A quoted part of a field, as in `alpn="h2,h3"`.
+                            b'"' => {
+                                self.quoted()?;
+                            }
+                            _ => self.position += 1,
+                        }
+                    }
+                    entry.tokens.push(Token {
+                        text: &self.text[start..self.position],
+                        quoted: false,
+                    });
+                }
+            }
+        }
+
+        if depth > 0 {
+            return Err(TextError::UnbalancedParentheses);
+        }
+        Ok(entry)
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Entry<'a>, TextError>;
+
+    /// Returns the next entry, skipping blank lines and lines that only hold comments.
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.position < self.text.len() {
+            match self.entry() {
+                Ok(entry) if entry.tokens.is_empty() => continue,
+                Ok(entry) => return Some(Ok(entry)),
+                Err(e) => {
+                    // Carry on from the next line if asked for more.
+                    while !matches!(self.peek(), Some(b'\n') | None) {
+                        self.position += 1;
+                    }
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Reads the fields of an entry one at a time, turning them into the types they hold.
+pub(crate) struct Fields<'t, 'a> {
+    tokens: std::slice::Iter<'t, Token<'a>>,
+    origin: &'t DomainName,
+}
+
+impl<'t, 'a> Fields<'t, 'a> {
+    /// Relative names among the fields are taken to be relative to `origin`.
+    pub(crate) fn new(tokens: &'t [Token<'a>], origin: &'t DomainName) -> Self {
+        Self {
+            tokens: tokens.iter(),
+            origin,
+        }
+    }
+
+    pub(crate) fn peek(&self) -> Option<&'t Token<'a>> {
+        self.tokens.clone().next()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.tokens.len() == 0
+    }
+
+    pub(crate) fn next(&mut self, field: &'static str) -> Result<&'t Token<'a>, TextError> {
+        self.tokens.next().ok_or(TextError::MissingField(field))
+    }
+
+    /// Reads the next field with `parse`, which returns `None` if the field isn't valid.
+    pub(crate) fn parse<T>(
+        &mut self,
+        field: &'static str,
+        parse: impl FnOnce(&str) -> Option<T>,
+    ) -> Result<T, TextError> {
+        let text = self.next(field)?.text();
+        parse(text).ok_or_else(|| bad_field(field, text))
+    }
+
+    pub(crate) fn number<T: FromStr>(&mut self, field: &'static str) -> Result<T, TextError> {
+        self.parse(field, |text| text.parse().ok())
+    }
+
+    pub(crate) fn duration(&mut self, field: &'static str) -> Result<u32, TextError> {
+        self.parse(field, from_duration)
+    }
+
+    pub(crate) fn name(&mut self, field: &'static str) -> Result<DomainName, TextError> {
+        let text = self.next(field)?.text();
+        DomainName::from_str_relative(text, self.origin)
+    }
+
+    pub(crate) fn character_string(&mut self, field: &'static str) -> Result<Vec<u8>, TextError> {
+        let token = *self.peek().ok_or(TextError::MissingField(field))?;
+        match self.octets(field)? {
+            bytes if bytes.len() <= 255 => Ok(bytes),
+            _ => Err(bad_field(field, token.text())),
+        }
+    }
+
+    /// Reads a string of bytes written like a character-string, but with no limit on its length.
+    pub(crate) fn octets(&mut self, field: &'static str) -> Result<Vec<u8>, TextError> {
+        let token = self.next(field)?;
+        unescape_string(token.text()).ok_or_else(|| bad_field(field, token.text()))
+    }
+
+    /// Reads a character-string that has to be UTF-8 to be stored.
+    pub(crate) fn string(&mut self, field: &'static str) -> Result<String, TextError> {
+        let token = *self
+            .tokens
+            .clone()
+            .next()
+            .ok_or(TextError::MissingField(field))?;
+        String::from_utf8(self.character_string(field)?).map_err(|_| bad_field(field, token.text()))
+    }
+
+    /// Joins up all the remaining fields, for base64 and hex values that are allowed to contain
+    /// spaces.
+    pub(crate) fn rest(&mut self, field: &'static str) -> Result<String, TextError> {
+        if self.is_empty() {
+            return Err(TextError::MissingField(field));
+        }
+        Ok(self.tokens.by_ref().map(|t| t.text()).collect())
+    }
+
+    /// Reads the remaining fields with `parse`.
+    pub(crate) fn all<T>(
+        &mut self,
+        field: &'static str,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Result<Vec<T>, TextError> {
+        self.tokens
+            .by_ref()
+            .map(|t| parse(t.text()).ok_or_else(|| bad_field(field, t.text())))
+            .collect()
+    }
+
+    /// Checks that every field has been used.
+    pub(crate) fn finish(mut self) -> Result<(), TextError> {
+        match self.tokens.next() {
+            Some(token) => Err(TextError::TrailingData(token.text().to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn bad_field(field: &'static str, value: &str) -> TextError {
+    TextError::BadField {
+        field,
+        value: value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The token texts of each entry in `text`.
+    fn entries(text: &str) -> Vec<Vec<&str>> {
+        Tokenizer::new(text)
+            .map(|entry| entry.unwrap().tokens.iter().map(Token::text).collect())
+            .collect()
+    }
+
+    #[test]
+    fn tokenizer_splits_fields_and_entries() {
+        let text =
+            "a 60 A 192.0.2.1 ; a comment\n\n; only a comment\nb\tTXT \"two words\" x\"y z\"\n";
+        assert_eq!(
+            entries(text),
+            [
+                vec!["a", "60", "A", "192.0.2.1"],
+                vec!["b", "TXT", "two words", "x\"y z\""],
+            ]
+        );
+
+        let entry = Tokenizer::new("@ TXT \"quoted\" plain")
+            .next()
+            .unwrap()
+            .unwrap();
+        let quoted: Vec<bool> = entry.tokens.iter().map(Token::is_quoted).collect();
+        assert_eq!(quoted, [false, false, true, false]);
+    }
+
+    #[test]
+    fn tokenizer_keeps_escapes_in_fields() {
+        assert_eq!(
+            entries(r#"a\ b\;c "q\"uote" \"d"#),
+            [vec![r"a\ b\;c", r#"q\"uote"#, r#"\"d"#]]
+        );
+    }
+
+    #[test]
+    fn parentheses_continue_an_entry_over_lines() {
+        let text = "a SOA ns hostmaster (\n  1 ; serial\n  2 3 4 5 )\nb A 192.0.2.1\n";
+        let mut tokenizer = Tokenizer::new(text);
+        let soa = tokenizer.next().unwrap().unwrap();
+        assert_eq!(soa.tokens.len(), 9);
+        assert_eq!(tokenizer.entry_line(), 1);
+        tokenizer.next().unwrap().unwrap();
+        assert_eq!(tokenizer.entry_line(), 4);
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn blank_owners_are_noticed() {
+        let blank: Vec<bool> = Tokenizer::new("a A 192.0.2.1\n  A 192.0.2.2\n\tA 192.0.2.3\n")
+            .map(|entry| entry.unwrap().blank_owner)
+            .collect();
+        assert_eq!(blank, [false, true, true]);
+    }
+
+    #[test]
+    fn tokenizer_errors_and_carries_on() {
+        let mut tokenizer = Tokenizer::new("a )\nb ( c\n");
+        assert!(matches!(
+            tokenizer.next(),
+            Some(Err(TextError::UnbalancedParentheses))
+        ));
+        assert!(matches!(
+            tokenizer.next(),
+            Some(Err(TextError::UnbalancedParentheses))
+        ));
+        assert!(tokenizer.next().is_none());
+
+        let mut tokenizer = Tokenizer::new("a TXT \"open\nb A 192.0.2.1\n");
+        assert!(matches!(
+            tokenizer.next(),
+            Some(Err(TextError::UnterminatedQuote))
+        ));
+        let entry = tokenizer.next().unwrap().unwrap();
+        assert_eq!(entry.tokens[0].text(), "b");
+        assert_eq!(tokenizer.entry_line(), 2);
+    }
+
+    #[test]
+    fn escapes_are_read() {
+        assert_eq!(
+            unescape_string("\\065\\.\\\\x\\\""),
+            Some(b"A.\\x\"".to_vec())
+        );
+        assert_eq!(unescape_string("\\000\\255"), Some(vec![0, 255]));
+        assert_eq!(unescape(&mut b"0659".iter()), Some(b'A'));
+    }
+
+    #[test]
+    fn malformed_escapes_are_rejected() {
+        // Too few digits, a value that doesn't fit in an octet, and nothing after the backslash.
+        assert_eq!(unescape_string("\\25"), None);
+        assert_eq!(unescape_string("\\2a5"), None);
+        assert_eq!(unescape_string("\\256"), None);
+        assert_eq!(unescape_string("ab\\"), None);
+    }
+
+    #[test]
+    fn quoted_strings_escape_what_they_must() {
+        assert_eq!(quoted(b"a \"b\" \\c"), "\"a \\\"b\\\" \\\\c\"");
+        assert_eq!(quoted(&[0, 0x7F, 0xFF]), "\"\\000\\127\\255\"");
+        assert_eq!(
+            unescape_string(quoted(&[0, 0x7F, 0xFF]).trim_matches('"')),
+            Some(vec![0, 0x7F, 0xFF])
+        );
+    }
+
+    #[test]
+    fn hex_is_pairs_of_digits_only() {
+        assert_eq!(from_hex("00aBff"), Some(vec![0x00, 0xAB, 0xFF]));
+        assert_eq!(from_hex(""), Some(Vec::new()));
+        assert_eq!(hex(&[0x0A, 0xBC]), "0ABC");
+        for bad in ["+1", "-1", "0", "0g", " 1", "1\u{e9}"] {
+            assert_eq!(from_hex(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn generic_rdata_is_written_with_its_length() {
+        assert_eq!(generic(&[]), "\\# 0");
+        assert_eq!(generic(&[0x01, 0xAB]), "\\# 2 01AB");
+    }
+
+    #[test]
+    fn base32hex_round_trips() {
+        // The test vectors of RFC 4648 §10, without the padding.
+        let vectors = [
+            ("", ""),
+            ("f", "CO"),
+            ("fo", "CPNG"),
+            ("foo", "CPNMU"),
+            ("foob", "CPNMUOG"),
+            ("fooba", "CPNMUOJ1"),
+            ("foobar", "CPNMUOJ1E8"),
+        ];
+        for (bytes, text) in vectors {
+            assert_eq!(base32hex(bytes.as_bytes()), text);
+            assert_eq!(from_base32hex(text), Some(bytes.as_bytes().to_vec()));
+            assert_eq!(
+                from_base32hex(&text.to_lowercase()),
+                Some(bytes.as_bytes().to_vec())
+            );
+        }
+        assert_eq!(from_base32hex("CPNW"), None);
+        assert_eq!(from_base32hex("CO=="), None);
+    }
+
+    #[test]
+    fn timestamps_round_trip() {
+        let times = [
+            (0, "19700101000000"),
+            (1_709_210_096, "20240229123456"),
+            (u32::MAX, "21060207062815"),
+        ];
+        for (seconds, text) in times {
+            assert_eq!(timestamp(seconds), text);
+            assert_eq!(from_timestamp(text), Some(seconds));
+        }
+        assert_eq!(from_timestamp("1709210096"), Some(1_709_210_096));
+    }
+
+    #[test]
+    fn malformed_timestamps_are_rejected() {
+        for bad in [
+            "20241301000000",
+            "20240100000000",
+            "20240101240000",
+            "21060207062816",
+            "2024-01-01",
+        ] {
+            assert_eq!(from_timestamp(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn durations_take_units() {
+        assert_eq!(from_duration("3600"), Some(3600));
+        assert_eq!(from_duration("1h30M"), Some(5400));
+        assert_eq!(from_duration("1w1d"), Some(691_200));
+        for bad in ["1h30", "h", "1x", "-1"] {
+            assert_eq!(from_duration(bad), None, "{bad}");
+        }
+    }
+}
@@ -1,5 +1,3 @@
-use nom::IResult;
-
 use crate::domain_name::DomainName;
 
 #[derive(Clone, Debug, derive_more::Display)]
@@ -32,39 +30,132 @@ impl Question {
     }
 }
 
-impl From<Question> for Vec<u8> {
-    fn from(value: Question) -> Self {
-        let mut bytes = Vec::with_capacity(512);
+impl Question {
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_name(&self.name, true);
+        encoder.put_u16(self.ty.into());
+        encoder.put_u16(self.class.into());
+    }
+}
+
+/// Reads a question such as `example.com. IN MX`. The class can come before or after the type, and
+/// defaults to IN.
+impl std::str::FromStr for Question {
+    type Err = crate::TextError;
 
-        bytes.extend_from_slice(Vec::from(value.name).as_slice());
-        bytes.extend_from_slice(&u16::from(value.ty).to_be_bytes());
-        bytes.extend_from_slice(&u16::from(value.class).to_be_bytes());
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let entry = crate::presentation::Tokenizer::new(s)
+            .next()
+            .ok_or(crate::TextError::MissingField("name"))??;
+        let fields: Vec<&str> = entry.tokens.iter().map(|t| t.text()).collect();
+        let (name, fields) = fields
+            .split_first()
+            .ok_or(crate::TextError::MissingField("name"))?;
+        let name: DomainName = name.parse()?;
 
-        bytes
+        // ANY is both a class and a type, so the first of two fields is only the class if the
+        // second can be the type.
+        let (ty, class) = match fields[..] {
+            [] => return Err(crate::TextError::MissingField("type")),
+            [ty] => (ty, None),
+            [class, ty]
+                if class.parse::<super::Class>().is_ok() && ty.parse::<super::Type>().is_ok() =>
+            {
+                (ty, Some(class))
+            }
+            [ty, class] => (ty, Some(class)),
+            [_, _, extra, ..] => return Err(crate::TextError::TrailingData(extra.to_string())),
+        };
+
+        Ok(Question::new(
+            name,
+            ty.parse()?,
+            class.map_or(Ok(super::Class::Internet), str::parse)?,
+        ))
+    }
+}
+
+impl From<Question> for Vec<u8> {
+    fn from(value: Question) -> Self {
+        Vec::from(&value)
     }
 }
 
 impl From<&Question> for Vec<u8> {
     fn from(value: &Question) -> Self {
-        let mut bytes = Vec::with_capacity(512);
-
-        bytes.extend_from_slice(Vec::from(value.name.clone()).as_slice());
-        bytes.extend_from_slice(&u16::from(value.ty).to_be_bytes());
-        bytes.extend_from_slice(&u16::from(value.class).to_be_bytes());
-
-        bytes
+        let mut encoder = crate::encoder::Encoder::new();
+        value.encode(&mut encoder);
+        encoder.into_bytes()
     }
 }
 
 #[tracing::instrument(skip_all)]
-pub fn parse<'p>(message: &'p [u8]) -> impl Fn(&'p [u8]) -> IResult<&'p [u8], Question> {
+pub fn parse<'p>(
+    message: &'p [u8],
+) -> impl Fn(&'p [u8]) -> nom::IResult<&'p [u8], Question, crate::error::ParseError<'p>> {
     move |i: &'p [u8]| {
         let (remaining, domain_name) = DomainName::parse(message)(i)?;
-        let (remaining, question_type) = nom::number::streaming::be_u16(remaining)?;
-        let (remaining, question_class) = nom::number::streaming::be_u16(remaining)?;
+        let (remaining, question_type) = nom::number::complete::be_u16(remaining)?;
+        let (remaining, question_class) = nom::number::complete::be_u16(remaining)?;
         Ok((
             remaining,
             Question::new(domain_name, question_type.into(), question_class.into()),
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TextError;
+
+    /// The type and class of the question written as `s`.
+    fn parse(s: &str) -> String {
+        let question: Question = s.parse().unwrap_or_else(|e| panic!("{s}: {e}"));
+        assert_eq!(question.name().to_string(), "example.com.");
+        format!("{} {}", question.question_type(), question.class())
+    }
+
+    #[test]
+    fn class_defaults_to_internet() {
+        assert_eq!(parse("example.com. MX"), "MX IN");
+    }
+
+    #[test]
+    fn class_can_come_before_or_after_the_type() {
+        assert_eq!(parse("example.com. CH TXT"), "TXT CH");
+        assert_eq!(parse("example.com. TXT CH"), "TXT CH");
+    }
+
+    #[test]
+    fn any_on_its_own_is_the_type() {
+        assert_eq!(parse("example.com. ANY"), "ALL IN");
+    }
+
+    #[test]
+    fn any_is_the_type_before_or_after_a_class() {
+        assert_eq!(parse("example.com IN ANY"), "ALL IN");
+        assert_eq!(parse("example.com ANY IN"), "ALL IN");
+    }
+
+    #[test]
+    fn any_can_be_both() {
+        assert_eq!(parse("example.com ANY ANY"), "ALL ANY");
+    }
+
+    #[test]
+    fn rejects_missing_and_extra_fields() {
+        assert_eq!(
+            "example.com. IN".parse::<Question>().unwrap_err(),
+            TextError::UnknownType("IN".to_string())
+        );
+        assert_eq!(
+            "example.com.".parse::<Question>().unwrap_err(),
+            TextError::MissingField("type")
+        );
+        assert_eq!(
+            "example.com. A IN A".parse::<Question>().unwrap_err(),
+            TextError::TrailingData("A".to_string())
+        );
+    }
+}
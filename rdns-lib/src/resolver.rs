@@ -0,0 +1,538 @@
+//! Looking names up the way the C library's stub resolver does, configured by resolv.conf.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::client::Client;
+use crate::domain_name::DomainName;
+use crate::edns::Edns;
+use crate::error::{ClientError, TextError};
+use crate::header::ReturnCode;
+use crate::message::Message;
+use crate::question::Question;
+use crate::{Class, Type};
+
+/// Where the C library reads its resolver configuration from.
+pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// How many name servers are used, with any after that ignored, as in the C library.
+const MAX_NAME_SERVERS: usize = 3;
+
+/// The settings in a resolv.conf file (see resolv.conf(5)).
+///
+/// Only `nameserver`, `search`, `domain`, and the `ndots`, `timeout`, `attempts`, `rotate` and
+/// `edns0` options are understood. Anything else is ignored, as are lines that can't be read,
+/// just like the C library does.
+#[derive(Clone, Debug)]
+pub struct ResolvConf {
+    name_servers: Vec<SocketAddr>,
+    search: Vec<DomainName>,
+    ndots: usize,
+    timeout: Duration,
+    attempts: usize,
+    rotate: bool,
+    edns0: bool,
+}
+
+/// The settings used when there's no resolv.conf: the name server on the local host, no search
+/// list, and the C library's default options.
+impl Default for ResolvConf {
+    fn default() -> Self {
+        Self {
+            name_servers: Vec::new(),
+            search: Vec::new(),
+            ndots: 1,
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+            rotate: false,
+            edns0: false,
+        }
+    }
+}
+
+impl ResolvConf {
+    pub fn read(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    /// Reads the settings from the text of a resolv.conf file. Where `search` and `domain` both
+    /// appear, the last one wins.
+    pub fn parse(text: &str) -> Self {
+        let mut config = Self::default();
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("nameserver") => {
+                    // Link-local IPv6 addresses can have a `%interface` after them, which
+                    // `SocketAddr` has no way to hold.
+                    let address = words.next().and_then(|a| a.parse::<IpAddr>().ok());
+                    if let Some(address) = address {
+                        if config.name_servers.len() < MAX_NAME_SERVERS {
+                            config.name_servers.push(SocketAddr::new(address, 53));
+                        }
+                    }
+                }
+                Some("domain") => {
+                    config.search = words
+                        .next()
+                        .and_then(|d| d.parse().ok())
+                        .into_iter()
+                        .collect();
+                }
+                Some("search") => {
+                    config.search = words.filter_map(|d| d.parse().ok()).collect();
+                }
+                Some("options") => {
+                    for option in words {
+                        config.option(option);
+                    }
+                }
+                _ => {}
+            }
+        }
+        config
+    }
+
+    /// Applies an option, limiting the numbers to the same ranges as the C library.
+    fn option(&mut self, option: &str) {
+        let (name, value) = match option.split_once(':') {
+            Some((name, value)) => (name, value.parse::<u64>().ok()),
+            None => (option, None),
+        };
+        match (name, value) {
+            ("ndots", Some(ndots)) => self.ndots = ndots.min(15) as usize,
+            ("timeout", Some(seconds)) => self.timeout = Duration::from_secs(seconds.clamp(1, 30)),
+            ("attempts", Some(attempts)) => self.attempts = attempts.clamp(1, 5) as usize,
+            ("rotate", None) => self.rotate = true,
+            ("edns0", None) => self.edns0 = true,
+            _ => {}
+        }
+    }
+
+    /// The name servers to query, which is the one on the local host if none are listed.
+    pub fn name_servers(&self) -> Vec<SocketAddr> {
+        match self.name_servers.is_empty() {
+            true => vec![SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 53)],
+            false => self.name_servers.clone(),
+        }
+    }
+
+    /// The domains to append to names with fewer than [`ndots`](Self::ndots) dots.
+    pub fn search(&self) -> &[DomainName] {
+        &self.search
+    }
+
+    pub fn ndots(&self) -> usize {
+        self.ndots
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    pub fn rotate(&self) -> bool {
+        self.rotate
+    }
+
+    pub fn edns0(&self) -> bool {
+        self.edns0
+    }
+
+    /// Creates a client that queries the name servers with these settings.
+    pub fn client(&self) -> Client {
+        let mut servers = self.name_servers().into_iter();
+        let mut client = Client::new(servers.next().expect("there's always a name server"));
+        for server in servers {
+            client = client.with_server(server);
+        }
+        client
+            .with_timeout(self.timeout)
+            .with_attempts(self.attempts)
+            .with_rotate(self.rotate)
+    }
+}
+
+/// A stub resolver that looks names up like the C library does, with the search list and `ndots`
+/// rules of resolv.conf.
+#[derive(Clone, Debug)]
+pub struct Resolver {
+    config: ResolvConf,
+    client: Client,
+}
+
+impl Resolver {
+    /// Creates a resolver configured by [`RESOLV_CONF`], or with the default settings if there is
+    /// no such file.
+    pub fn new() -> std::io::Result<Self> {
+        match ResolvConf::read(RESOLV_CONF) {
+            Ok(config) => Ok(Self::from_config(config)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                Ok(Self::from_config(ResolvConf::default()))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Creates a resolver configured by the resolv.conf file at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::from_config(ResolvConf::read(path)?))
+    }
+
+    pub fn from_config(config: ResolvConf) -> Self {
+        Self {
+            client: config.client(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &ResolvConf {
+        &self.config
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The names that looking up `name` tries, in order. A name ending in a dot is only tried as
+    /// it is. Otherwise it's tried with each search domain appended, and as it is either first,
+    /// if it has at least `ndots` dots, or last.
+    pub fn search_names(&self, name: &str) -> Result<Vec<DomainName>, TextError> {
+        let as_is: DomainName = name.parse()?;
+        if is_absolute(name) {
+            return Ok(vec![as_is]);
+        }
+
+        let mut names = Vec::with_capacity(self.config.search.len() + 1);
+        let dots = as_is.labels().len().saturating_sub(1);
+        if dots >= self.config.ndots {
+            names.push(as_is.clone());
+        }
+        for domain in self.config.search.iter() {
+            // Names that would be too long with the domain appended are skipped.
+            if let Ok(expanded) = DomainName::from_str_relative(name, domain) {
+                names.push(expanded);
+            }
+        }
+        if dots < self.config.ndots {
+            names.push(as_is);
+        }
+        Ok(names)
+    }
+
+    /// Looks up the records of type `record_type` for `name`, going through the names from
+    /// [`search_names`](Self::search_names) until one of them has some.
+    ///
+    /// Names that don't exist, have no records of the type, or whose servers failed or couldn't
+    /// be reached are passed over, as the C library does. If none of the names have any records,
+    /// the first response saying a name exists is returned, or failing that the response for the
+    /// last name that got one, or if none did, the last error. Any other response code stops the
+    /// search and is returned straight away.
+    #[tracing::instrument(skip(self))]
+    pub fn lookup(&self, name: &str, record_type: Type) -> Result<Message, ClientError> {
+        let mut no_data = None;
+        let mut last = None;
+        let mut error = None;
+        for name in self.search_names(name)? {
+            let response = match self.client.query(&self.query(name.clone(), record_type)) {
+                Ok(response) => response,
+                Err(e) => {
+                    tracing::debug!(%name, error = %e, "trying the next name");
+                    error = Some(e);
+                    continue;
+                }
+            };
+            match response.response_code() {
+                ReturnCode::NoError if !response.answers().is_empty() => return Ok(response),
+                ReturnCode::NoError => {
+                    no_data.get_or_insert(response);
+                }
+                ReturnCode::NameError | ReturnCode::ServerFailure => last = Some(response),
+                _ => return Ok(response),
+            }
+        }
+        match no_data.or(last) {
+            Some(response) => Ok(response),
+            None => Err(error.expect("there's always at least one name to try")),
+        }
+    }
+
+    fn query(&self, name: DomainName, record_type: Type) -> Message {
+        let builder = Message::builder()
+            .with_recursion_desired(true)
+            .add_question(Question::new(name, record_type, Class::Internet));
+        match self.config.edns0 {
+            true => builder.with_edns(Edns::new(1232)).build(),
+            false => builder.build(),
+        }
+    }
+}
+
+/// Whether a name in presentation format ends in a dot that isn't escaped.
+fn is_absolute(name: &str) -> bool {
+    match name.strip_suffix('.') {
+        Some(rest) => rest.bytes().rev().take_while(|b| *b == b'\\').count() % 2 == 0,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+
+    fn fixture() -> ResolvConf {
+        ResolvConf::read(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/resolv.conf")).unwrap()
+    }
+
+    fn search_names(config: &str, name: &str) -> Vec<String> {
+        Resolver::from_config(ResolvConf::parse(config))
+            .search_names(name)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn reads_the_fixture() {
+        let config = fixture();
+        assert_eq!(
+            config.name_servers(),
+            [
+                "192.0.2.1:53".parse().unwrap(),
+                "[2001:db8::53]:53".parse().unwrap(),
+                "192.0.2.2:53".parse().unwrap(),
+            ]
+        );
+        let search: Vec<String> = config.search().iter().map(ToString::to_string).collect();
+        assert_eq!(search, ["corp.example.", "lab.example."]);
+        assert_eq!(config.ndots(), 2);
+        assert!(config.rotate());
+        assert!(config.edns0());
+        assert_eq!(config.client().servers().len(), 3);
+    }
+
+    #[test]
+    fn last_of_domain_and_search_wins() {
+        let config = ResolvConf::parse("search a.example b.example\ndomain c.example\n");
+        let search: Vec<String> = config.search().iter().map(ToString::to_string).collect();
+        assert_eq!(search, ["c.example."]);
+
+        let config = ResolvConf::parse("domain c.example\nsearch a.example b.example\n");
+        let search: Vec<String> = config.search().iter().map(ToString::to_string).collect();
+        assert_eq!(search, ["a.example.", "b.example."]);
+    }
+
+    #[test]
+    fn keeps_at_most_three_name_servers() {
+        let config = ResolvConf::parse(
+            "nameserver 192.0.2.1\nnameserver 192.0.2.2\nnameserver 192.0.2.3\nnameserver 192.0.2.4\n",
+        );
+        assert_eq!(config.name_servers().len(), 3);
+        assert_eq!(config.name_servers()[2], "192.0.2.3:53".parse().unwrap());
+    }
+
+    #[test]
+    fn defaults_to_the_local_name_server() {
+        let config = ResolvConf::parse("");
+        assert_eq!(config.name_servers(), ["127.0.0.1:53".parse().unwrap()]);
+        assert_eq!(config.ndots(), 1);
+        assert_eq!(config.timeout(), Duration::from_secs(5));
+        assert_eq!(config.attempts(), 2);
+        assert!(!config.rotate() && !config.edns0());
+    }
+
+    #[test]
+    fn limits_options_like_the_c_library() {
+        let config = fixture();
+        assert_eq!(config.timeout(), Duration::from_secs(30));
+        assert_eq!(config.attempts(), 1);
+
+        let config = ResolvConf::parse("options ndots:20 timeout:0 attempts:9\n");
+        assert_eq!(config.ndots(), 15);
+        assert_eq!(config.timeout(), Duration::from_secs(1));
+        assert_eq!(config.attempts(), 5);
+
+        let config = ResolvConf::parse("options ndots:x timeout attempts:-1\n");
+        assert_eq!(config.ndots(), 1);
+        assert_eq!(config.timeout(), Duration::from_secs(5));
+        assert_eq!(config.attempts(), 2);
+    }
+
+    #[test]
+    fn names_with_few_dots_are_tried_as_is_last() {
+        let config = "search corp.example lab.example\noptions ndots:2\n";
+        assert_eq!(
+            search_names(config, "www.dev"),
+            ["www.dev.corp.example.", "www.dev.lab.example.", "www.dev."]
+        );
+    }
+
+    #[test]
+    fn names_with_enough_dots_are_tried_as_is_first() {
+        let config = "search corp.example lab.example\noptions ndots:2\n";
+        assert_eq!(
+            search_names(config, "www.dev.test"),
+            [
+                "www.dev.test.",
+                "www.dev.test.corp.example.",
+                "www.dev.test.lab.example.",
+            ]
+        );
+    }
+
+    #[test]
+    fn absolute_names_are_only_tried_as_is() {
+        let config = "search corp.example\n";
+        assert_eq!(search_names(config, "www."), ["www."]);
+    }
+
+    #[test]
+    fn escaped_trailing_dots_are_part_of_the_label() {
+        let config = "search corp.example\n";
+        assert_eq!(
+            search_names(config, "www\\."),
+            ["www\\..corp.example.", "www\\.."]
+        );
+        assert_eq!(search_names(config, "www\\\\."), ["www\\\\."]);
+    }
+
+    #[test]
+    fn rejects_names_that_are_not_valid() {
+        let resolver = Resolver::from_config(ResolvConf::default());
+        assert!(resolver.search_names("www..example").is_err());
+    }
+
+    /// How the test server answers a query.
+    enum Reply {
+        Code(ReturnCode),
+        Address,
+        Garbage,
+    }
+
+    /// A resolver searching `a.example` to `d.example`, whose server on the loopback address
+    /// answers each name as `reply` says. The names are sent on the receiver as they're asked
+    /// about, if it's kept.
+    fn serve(reply: fn(&str) -> Reply) -> (Resolver, mpsc::Receiver<String>) {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = socket.local_addr().unwrap();
+        // The server stops once it's been left alone for a while.
+        socket
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let (names, asked) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 512];
+            while let Ok((length, client)) = socket.recv_from(&mut buffer) {
+                let query = Message::try_from(&buffer[..length]).unwrap();
+                let name = query.questions()[0].name().to_string();
+                let response = match reply(&name) {
+                    Reply::Code(code) => Message::response_to(&query)
+                        .with_response_code(code)
+                        .into_bytes()
+                        .unwrap(),
+                    Reply::Address => Message::response_to(&query)
+                        .add_answer(format!("{name} 60 A 192.0.2.1").parse().unwrap())
+                        .into_bytes()
+                        .unwrap(),
+                    Reply::Garbage => vec![0; 3],
+                };
+                let _ = names.send(name);
+                socket.send_to(&response, client).unwrap();
+            }
+        });
+
+        let config = ResolvConf::parse("search a.example b.example c.example d.example\n");
+        let resolver = Resolver {
+            // Garbage is only reported once the timeout is up, in case a real response follows.
+            client: Client::new(address)
+                .with_timeout(Duration::from_millis(300))
+                .with_attempts(1),
+            config,
+        };
+        (resolver, asked)
+    }
+
+    fn question(response: &Message) -> String {
+        response.questions()[0].name().to_string()
+    }
+
+    #[test]
+    fn lookups_move_on_past_missing_names_and_failed_servers() {
+        let (resolver, asked) = serve(|name| match name {
+            "www.a.example." => Reply::Code(ReturnCode::NameError),
+            "www.b.example." => Reply::Code(ReturnCode::ServerFailure),
+            _ => Reply::Address,
+        });
+        let response = resolver.lookup("www", Type::A).unwrap();
+        assert_eq!(question(&response), "www.c.example.");
+        assert_eq!(response.answers().len(), 1);
+        let asked: Vec<String> = asked.try_iter().collect();
+        assert_eq!(
+            asked,
+            ["www.a.example.", "www.b.example.", "www.c.example."]
+        );
+    }
+
+    #[test]
+    fn lookups_prefer_a_name_that_exists_without_records() {
+        let (resolver, asked) = serve(|name| match name {
+            "www.b.example." => Reply::Code(ReturnCode::NoError),
+            _ => Reply::Code(ReturnCode::NameError),
+        });
+        let response = resolver.lookup("www", Type::A).unwrap();
+        assert_eq!(question(&response), "www.b.example.");
+        assert!(response.answers().is_empty());
+        assert_eq!(asked.try_iter().count(), 5);
+
+        // With no such name, the last response is the one returned.
+        let (resolver, _) = serve(|_| Reply::Code(ReturnCode::NameError));
+        let response = resolver.lookup("www", Type::A).unwrap();
+        assert_eq!(question(&response), "www.");
+        assert_eq!(response.response_code().mnemonic(), "NXDOMAIN");
+    }
+
+    #[test]
+    fn other_response_codes_stop_the_lookup() {
+        let (resolver, asked) = serve(|name| match name {
+            "www.a.example." => Reply::Code(ReturnCode::NameError),
+            "www.b.example." => Reply::Code(ReturnCode::Refused),
+            _ => Reply::Address,
+        });
+        let response = resolver.lookup("www", Type::A).unwrap();
+        assert_eq!(question(&response), "www.b.example.");
+        assert_eq!(response.response_code().mnemonic(), "REFUSED");
+        assert_eq!(asked.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn lookups_move_on_past_transport_errors() {
+        let (resolver, _) = serve(|name| match name {
+            "www.a.example." | "www.c.example." => Reply::Garbage,
+            "www.b.example." => Reply::Code(ReturnCode::NameError),
+            _ => Reply::Address,
+        });
+        let response = resolver.lookup("www", Type::A).unwrap();
+        assert_eq!(question(&response), "www.d.example.");
+
+        // The last response wins over errors for later names, and with no responses at all
+        // the last error is returned.
+        let (resolver, _) = serve(|name| match name {
+            "www.a.example." => Reply::Code(ReturnCode::NameError),
+            _ => Reply::Garbage,
+        });
+        let response = resolver.lookup("www", Type::A).unwrap();
+        assert_eq!(question(&response), "www.a.example.");
+        let (resolver, asked) = serve(|_| Reply::Garbage);
+        assert!(resolver.lookup("www", Type::A).is_err());
+        assert_eq!(asked.try_iter().count(), 5);
+    }
+}
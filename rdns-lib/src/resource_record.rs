@@ -1,11 +1,21 @@
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
 
 use nom::IResult;
 
 use crate::domain_name::DomainName;
+use crate::error::{EncodeError, ParseError, ParseErrorKind, TextError};
+use crate::presentation::{bad_field, from_hex, generic, quoted, Fields, Tokenizer};
+
+pub use dnssec::{DNSKEY, DS, NSEC, NSEC3, NSEC3PARAM, RRSIG};
+pub use security::{CAA, SMIMEA, SSHFP, TLSA};
+pub use svcb::{SvcParam, HTTPS, SVCB};
+
+mod dnssec;
+mod security;
+mod svcb;
 
 #[derive(Clone, Debug, derive_more::Display)]
-#[display(fmt = "{} {} {} {} {}", name, ty, class, ttl, rdata)]
+#[display(fmt = "{} {} {} {} {}", name, "*ttl as u32", class, ty, rdata)]
 pub struct ResourceRecord {
     name: DomainName,
     ty: super::Type,
@@ -30,6 +40,114 @@ impl ResourceRecord {
             rdata,
         }
     }
+
+    pub fn name(&self) -> &DomainName {
+        &self.name
+    }
+
+    pub fn record_type(&self) -> super::Type {
+        self.ty
+    }
+
+    pub fn class(&self) -> super::Class {
+        self.class
+    }
+
+    /// The TTL as it is on the wire. Values of 2^31 and up come out negative, and are written in
+    /// presentation format as the unsigned number they are on the wire.
+    pub fn ttl(&self) -> i32 {
+        self.ttl
+    }
+
+    pub fn rdata(&self) -> &RecordData {
+        &self.rdata
+    }
+}
+
+impl ResourceRecord {
+    /// Reads the rest of a record in presentation format once its owner is known: the TTL and
+    /// class, in either order, then the type and RDATA. Either of the first two may be left out,
+    /// in which case the defaults are used.
+    pub(crate) fn from_fields(
+        name: DomainName,
+        fields: &mut Fields,
+        default_ttl: Option<u32>,
+        default_class: super::Class,
+    ) -> Result<Self, TextError> {
+        let mut ttl = None;
+        let mut class = None;
+        while let Some(token) = fields.peek() {
+            if ttl.is_none() && token.text().starts_with(|c: char| c.is_ascii_digit()) {
+                ttl = Some(fields.duration("TTL")?);
+            } else if class.is_none() && token.text().parse::<super::Class>().is_ok() {
+                class = Some(fields.parse("class", |t| t.parse().ok())?);
+            } else {
+                break;
+            }
+        }
+
+        let ttl = ttl.or(default_ttl).ok_or(TextError::MissingField("TTL"))?;
+        // Kept as on the wire, so that records with a TTL over 2^31 can be written and read back.
+        let ttl = ttl as i32;
+        let class = class.unwrap_or(default_class);
+        let ty: super::Type = fields.next("type")?.text().parse()?;
+        let rdata = parse_rdata_text(class, ty, fields)?;
+
+        Ok(Self::new(name, ty, class, ttl, rdata))
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_name(&self.name, true);
+        encoder.put_u16(self.ty.into());
+        encoder.put_u16(self.class.into());
+        encoder.put_i32(self.ttl);
+
+        let start = encoder.start_length();
+        self.rdata.encode(encoder);
+        encoder.end_length("RDLENGTH", start);
+    }
+}
+
+/// Reads a record written on one line, such as `example.com. 300 IN MX 10 mail.example.com.`. The
+/// class defaults to IN, but the TTL has to be given. Relative names are taken to be relative to
+/// the root.
+impl std::str::FromStr for ResourceRecord {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries = Tokenizer::new(s);
+        let entry = entries.next().ok_or(TextError::MissingField("owner"))??;
+        if let Some(next) = entries.next() {
+            let text = next?.tokens.first().map_or("", |t| t.text()).to_string();
+            return Err(TextError::TrailingData(text));
+        }
+
+        let origin = DomainName::root();
+        let mut fields = Fields::new(&entry.tokens, &origin);
+        let name = fields.name("owner")?;
+        let record = Self::from_fields(name, &mut fields, None, super::Class::Internet)?;
+        fields.finish()?;
+        Ok(record)
+    }
+}
+
+impl TryFrom<ResourceRecord> for Vec<u8> {
+    type Error = EncodeError;
+
+    fn try_from(value: ResourceRecord) -> Result<Self, Self::Error> {
+        Vec::try_from(&value)
+    }
+}
+
+/// Fails if the RDATA, or a length inside it, is longer than its 16-bit length field can say.
+impl TryFrom<&ResourceRecord> for Vec<u8> {
+    type Error = EncodeError;
+
+    fn try_from(value: &ResourceRecord) -> Result<Self, Self::Error> {
+        let mut encoder = crate::encoder::Encoder::new();
+        value.encode(&mut encoder);
+        encoder.finish()
+    }
 }
 
 #[derive(Clone, Debug, derive_more::Display)]
@@ -50,9 +168,170 @@ pub enum RecordData {
     MInfo(MInfo),
     MX(MX),
     TXT(TXT),
-    #[display(fmt = "<Unknown RR Class/Type {}/{}> {:?}", _0, _1, _2)]
+    AAAA(AAAA),
+    SRV(SRV),
+    NAPTR(NAPTR),
+    URI(URI),
+    DS(DS),
+    RRSIG(RRSIG),
+    NSEC(NSEC),
+    DNSKEY(DNSKEY),
+    NSEC3(NSEC3),
+    NSEC3PARAM(NSEC3PARAM),
+    SSHFP(SSHFP),
+    TLSA(TLSA),
+    SMIMEA(SMIMEA),
+    SVCB(SVCB),
+    HTTPS(HTTPS),
+    CAA(CAA),
+    /// OPT records don't belong in zone files, so they're shown in the generic format that
+    /// anything can be.
+    #[display(fmt = "{}", r#"generic(&RecordData::OPT(_0.clone()).to_bytes())"#)]
+    OPT(OPT),
+    #[display(fmt = "{}", r#"generic(_2)"#)]
     Unknown(super::Class, super::Type, Vec<u8>),
 }
+
+impl RecordData {
+    /// The wire format of the RDATA, for showing or comparing it, even if a length inside it
+    /// doesn't fit.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = crate::encoder::Encoder::new();
+        self.encode(&mut encoder);
+        encoder.into_bytes()
+    }
+
+    /// The RDATA in the canonical form of RFC 4034 §6.2, for sorting records. The names in the
+    /// types it lists are lowercased; NSEC isn't one of them (RFC 6840 §5.1).
+    pub(crate) fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut encoder = match self {
+            RecordData::NS(_)
+            | RecordData::MD(_)
+            | RecordData::MF(_)
+            | RecordData::CName(_)
+            | RecordData::SOA(_)
+            | RecordData::MB(_)
+            | RecordData::MG(_)
+            | RecordData::MR(_)
+            | RecordData::PTR(_)
+            | RecordData::MInfo(_)
+            | RecordData::MX(_)
+            | RecordData::SRV(_)
+            | RecordData::NAPTR(_)
+            | RecordData::RRSIG(_) => crate::encoder::Encoder::with_lowercase_names(),
+            _ => crate::encoder::Encoder::new(),
+        };
+        self.encode(&mut encoder);
+        encoder.into_bytes()
+    }
+
+    /// Writes the RDATA. Only the names in record types defined by RFC 1035 are compressed, as
+    /// required by RFC 3597 §4.
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        match self {
+            RecordData::A(a) => encoder.put_slice(&a.address.octets()),
+            RecordData::NS(ns) => encoder.put_name(&ns.domain_name, true),
+            RecordData::MD(md) => encoder.put_name(&md.mail_agent_domain_name, true),
+            RecordData::MF(mf) => encoder.put_name(&mf.mail_agent_domain_name, true),
+            RecordData::CName(cname) => encoder.put_name(&cname.cname, true),
+            RecordData::SOA(soa) => {
+                encoder.put_name(&soa.primary_source_domain, true);
+                encoder.put_name(&soa.responsible_person_email, true);
+                encoder.put_u32(soa.serial);
+                encoder.put_u32(soa.refresh);
+                encoder.put_u32(soa.retry);
+                encoder.put_u32(soa.expire);
+                encoder.put_u32(soa.minimum);
+            }
+            RecordData::MB(mb) => encoder.put_name(&mb.mail_agent_domain_name, true),
+            RecordData::MG(mg) => encoder.put_name(&mg.mail_group_member_name, true),
+            RecordData::MR(mr) => encoder.put_name(&mr.new_name, true),
+            RecordData::Null(null) => encoder.put_slice(&null.bytes),
+            RecordData::WKS(wks) => {
+                encoder.put_slice(&wks.address.octets());
+                encoder.put_u8(u8::from(wks.protocol));
+                if let Some(highest) = wks.ports.iter().max() {
+                    let mut bitmap = vec![0_u8; *highest as usize / 8 + 1];
+                    for port in wks.ports.iter() {
+                        bitmap[*port as usize / 8] |= 0x80 >> (port % 8);
+                    }
+                    encoder.put_slice(&bitmap);
+                }
+            }
+            RecordData::PTR(ptr) => encoder.put_name(&ptr.pointer_domain_name, true),
+            RecordData::HostInfo(hinfo) => {
+                encoder.put_u8(hinfo.cpu.len() as u8);
+                encoder.put_slice(&hinfo.cpu);
+                encoder.put_u8(hinfo.os.len() as u8);
+                encoder.put_slice(&hinfo.os);
+            }
+            RecordData::MInfo(minfo) => {
+                encoder.put_name(&minfo.responsible_mailbox, true);
+                encoder.put_name(&minfo.error_mailbox, true);
+            }
+            RecordData::MX(mx) => {
+                encoder.put_u16(mx.preference);
+                encoder.put_name(&mx.exchange, true);
+            }
+            RecordData::TXT(txt) => {
+                for string in txt.strings.iter() {
+                    encoder.put_u8(string.len() as u8);
+                    encoder.put_slice(string);
+                }
+            }
+            RecordData::AAAA(aaaa) => encoder.put_slice(&aaaa.address.octets()),
+            RecordData::SRV(srv) => {
+                encoder.put_u16(srv.priority);
+                encoder.put_u16(srv.weight);
+                encoder.put_u16(srv.port);
+                encoder.put_name(&srv.target, false);
+            }
+            RecordData::NAPTR(naptr) => {
+                encoder.put_u16(naptr.order);
+                encoder.put_u16(naptr.preference);
+                for string in [&naptr.flags, &naptr.services, &naptr.regexp] {
+                    encoder.put_u8(string.len() as u8);
+                    encoder.put_slice(string);
+                }
+                encoder.put_name(&naptr.replacement, false);
+            }
+            RecordData::URI(uri) => {
+                encoder.put_u16(uri.priority);
+                encoder.put_u16(uri.weight);
+                encoder.put_slice(&uri.target);
+            }
+            RecordData::DS(ds) => ds.encode(encoder),
+            RecordData::RRSIG(rrsig) => rrsig.encode(encoder),
+            RecordData::NSEC(nsec) => nsec.encode(encoder),
+            RecordData::DNSKEY(dnskey) => dnskey.encode(encoder),
+            RecordData::NSEC3(nsec3) => nsec3.encode(encoder),
+            RecordData::NSEC3PARAM(nsec3param) => nsec3param.encode(encoder),
+            RecordData::SSHFP(sshfp) => sshfp.encode(encoder),
+            RecordData::TLSA(tlsa) => tlsa.encode(encoder),
+            RecordData::SMIMEA(smimea) => smimea.encode(encoder),
+            RecordData::SVCB(svcb) => svcb.encode(encoder),
+            RecordData::HTTPS(https) => https.encode(encoder),
+            RecordData::CAA(caa) => caa.encode(encoder),
+            RecordData::OPT(opt) => {
+                for option in opt.options.iter() {
+                    option.encode(encoder);
+                }
+            }
+            RecordData::Unknown(_, _, data) => encoder.put_slice(data),
+        }
+    }
+}
+
+impl TryFrom<&RecordData> for Vec<u8> {
+    type Error = EncodeError;
+
+    fn try_from(value: &RecordData) -> Result<Self, Self::Error> {
+        let mut encoder = crate::encoder::Encoder::new();
+        value.encode(&mut encoder);
+        encoder.finish()
+    }
+}
+
 #[derive(Clone, Debug, derive_more::Display)]
 pub struct CName {
     cname: DomainName,
@@ -69,22 +348,26 @@ impl CName {
 }
 
 #[derive(Clone, Debug, derive_more::Display)]
-#[display(fmt = "{} {}", cpu, os)]
+#[display(fmt = "{} {}", r#"quoted(cpu)"#, r#"quoted(os)"#)]
 pub struct HostInfo {
-    cpu: String,
-    os: String,
+    cpu: Vec<u8>,
+    os: Vec<u8>,
 }
 
 impl HostInfo {
-    pub fn new(cpu: String, os: String) -> Self {
-        Self { cpu, os }
+    /// Creates the record, failing if either string is too long for a character-string.
+    pub fn new(cpu: impl Into<Vec<u8>>, os: impl Into<Vec<u8>>) -> Result<Self, TextError> {
+        let (cpu, os) = (cpu.into(), os.into());
+        check_character_string("CPU", &cpu)?;
+        check_character_string("OS", &os)?;
+        Ok(Self { cpu, os })
     }
 
-    pub fn cpu(&self) -> &str {
+    pub fn cpu(&self) -> &[u8] {
         &self.cpu
     }
 
-    pub fn os(&self) -> &str {
+    pub fn os(&self) -> &[u8] {
         &self.os
     }
 }
@@ -220,8 +503,79 @@ impl MX {
     }
 }
 
+/// Naming Authority Pointer (RFC 3403). The flags, services and regexp are arbitrary bytes, and
+/// not necessarily UTF-8.
 #[derive(Clone, Debug, derive_more::Display)]
-#[display(fmt = "{:?}", "bytes")]
+#[display(
+    fmt = "{} {} {} {} {} {}",
+    order,
+    preference,
+    r#"quoted(flags)"#,
+    r#"quoted(services)"#,
+    r#"quoted(regexp)"#,
+    replacement
+)]
+pub struct NAPTR {
+    order: u16,
+    preference: u16,
+    flags: Vec<u8>,
+    services: Vec<u8>,
+    regexp: Vec<u8>,
+    replacement: DomainName,
+}
+
+impl NAPTR {
+    /// Creates the record, failing if the flags, services or regexp are too long for a
+    /// character-string.
+    pub fn new(
+        order: u16,
+        preference: u16,
+        flags: impl Into<Vec<u8>>,
+        services: impl Into<Vec<u8>>,
+        regexp: impl Into<Vec<u8>>,
+        replacement: DomainName,
+    ) -> Result<Self, TextError> {
+        let (flags, services, regexp) = (flags.into(), services.into(), regexp.into());
+        check_character_string("flags", &flags)?;
+        check_character_string("services", &services)?;
+        check_character_string("regexp", &regexp)?;
+        Ok(Self {
+            order,
+            preference,
+            flags,
+            services,
+            regexp,
+            replacement,
+        })
+    }
+
+    pub fn order(&self) -> u16 {
+        self.order
+    }
+
+    pub fn preference(&self) -> u16 {
+        self.preference
+    }
+
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+
+    pub fn services(&self) -> &[u8] {
+        &self.services
+    }
+
+    pub fn regexp(&self) -> &[u8] {
+        &self.regexp
+    }
+
+    pub fn replacement(&self) -> &DomainName {
+        &self.replacement
+    }
+}
+
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(fmt = "{}", r#"generic(bytes)"#)]
 pub struct Null {
     bytes: Vec<u8>,
 }
@@ -339,20 +693,133 @@ impl SOA {
     }
 }
 
+/// Service location (RFC 2782).
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(fmt = "{} {} {} {}", priority, weight, port, target)]
+pub struct SRV {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: DomainName,
+}
+
+impl SRV {
+    pub fn new(priority: u16, weight: u16, port: u16, target: DomainName) -> Self {
+        Self {
+            priority,
+            weight,
+            port,
+            target,
+        }
+    }
+
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn target(&self) -> &DomainName {
+        &self.target
+    }
+}
+
+/// Descriptive text, held as the sequence of character-strings it was sent as. The strings are
+/// arbitrary bytes, and not necessarily UTF-8.
 #[derive(Clone, Debug, derive_more::Display)]
+#[display(
+    fmt = "{}",
+    r#"itertools::join(strings.iter().map(|s| quoted(s)), " ")"#
+)]
 pub struct TXT {
-    text_data: String,
+    strings: Vec<Vec<u8>>,
 }
 
 impl TXT {
-    pub fn new(text_data: impl ToString) -> Self {
+    /// Creates the record from its character-strings. Any string longer than the 255 octets a
+    /// character-string can hold is split across several.
+    pub fn new<S: Into<Vec<u8>>>(strings: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            strings: strings
+                .into_iter()
+                .flat_map(|s| {
+                    let s: Vec<u8> = s.into();
+                    if s.is_empty() {
+                        vec![s]
+                    } else {
+                        s.chunks(255).map(|c| c.to_vec()).collect()
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    pub fn strings(&self) -> &[Vec<u8>] {
+        &self.strings
+    }
+
+    /// The strings joined together, which is how SPF and DKIM records are meant to be read.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.strings.concat()).into_owned()
+    }
+}
+
+/// Uniform Resource Identifier (RFC 7553). The target fills the rest of the RDATA, so it isn't
+/// limited to the length of a character-string, and isn't checked to be UTF-8.
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(fmt = "{} {} {}", priority, weight, r#"quoted(target)"#)]
+pub struct URI {
+    priority: u16,
+    weight: u16,
+    target: Vec<u8>,
+}
+
+impl URI {
+    pub fn new(priority: u16, weight: u16, target: impl Into<Vec<u8>>) -> Self {
         Self {
-            text_data: text_data.to_string(),
+            priority,
+            weight,
+            target: target.into(),
         }
     }
 
-    pub fn data(&self) -> &str {
-        &self.text_data
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    pub fn target(&self) -> &[u8] {
+        &self.target
+    }
+}
+
+/// The RDATA of the EDNS(0) OPT pseudo-record. The rest of the EDNS parameters are packed into
+/// the record's class and TTL fields; see [`crate::edns::Edns`] for the interpreted form.
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(
+    fmt = "{}",
+    r#"itertools::join(options.iter().map(|o| o.to_string()), "; ")"#
+)]
+pub struct OPT {
+    options: Vec<crate::edns::EdnsOption>,
+}
+
+impl OPT {
+    pub fn new(options: Vec<crate::edns::EdnsOption>) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &[crate::edns::EdnsOption] {
+        &self.options
     }
 }
 
@@ -371,6 +838,21 @@ impl A {
     }
 }
 
+#[derive(Clone, Debug, derive_more::Display)]
+pub struct AAAA {
+    address: Ipv6Addr,
+}
+
+impl AAAA {
+    pub fn new(address: Ipv6Addr) -> Self {
+        Self { address }
+    }
+
+    pub fn address(&self) -> &Ipv6Addr {
+        &self.address
+    }
+}
+
 impl WKS {
     pub fn new(address: Ipv4Addr, protocol: Protocol, ports: Vec<u16>) -> Self {
         Self {
@@ -402,7 +884,7 @@ pub struct WKS {
 }
 
 //noinspection ALL
-#[derive(Copy, Clone, Debug, derive_more::Display, derive_more::From)]
+#[derive(Copy, Clone, Debug, derive_more::Display)]
 #[repr(u8)]
 pub enum Protocol {
     ICMP = 1,
@@ -437,7 +919,7 @@ pub enum Protocol {
     #[display(fmt = "LEAF-1")]
     LEAF1 = 25,
     #[display(fmt = "LEAF-2")]
-    LEAF2 = 36,
+    LEAF2 = 26,
     RDP = 27,
     IRTP = 28,
     #[display(fmt = "ISO-TP4")]
@@ -467,95 +949,230 @@ pub enum Protocol {
     WBMON = 78,
     #[display(fmt = "WB-EXPAK")]
     WBEXPAK = 79,
-    #[display(fmt = "Unknown protocol {}", _0)]
+    #[display(fmt = "{}", _0)]
     Unknown(u8),
 }
 
+impl From<u8> for Protocol {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::ICMP,
+            2 => Self::IGMP,
+            3 => Self::GGP,
+            5 => Self::ST,
+            6 => Self::TCP,
+            7 => Self::UCL,
+            8 => Self::EGP,
+            9 => Self::IGP,
+            10 => Self::BBNRCCMON,
+            11 => Self::NVP2,
+            12 => Self::PUP,
+            13 => Self::ARGUS,
+            14 => Self::EMCON,
+            15 => Self::XNET,
+            16 => Self::CHAOS,
+            17 => Self::UDP,
+            18 => Self::MUX,
+            19 => Self::DCNMEAS,
+            20 => Self::HMP,
+            21 => Self::PRM,
+            22 => Self::XNSIDP,
+            23 => Self::TRUNK1,
+            24 => Self::TRUNK2,
+            25 => Self::LEAF1,
+            26 => Self::LEAF2,
+            27 => Self::RDP,
+            28 => Self::IRTP,
+            29 => Self::ISOTP4,
+            30 => Self::NETBLT,
+            31 => Self::MFENSP,
+            32 => Self::MERITINP,
+            33 => Self::SEP,
+            61 => Self::HostInternal,
+            62 => Self::CFPT,
+            63 => Self::LocalNetwork,
+            64 => Self::SATEXPAK,
+            65 => Self::MITSUBNET,
+            66 => Self::RDV,
+            67 => Self::IPPC,
+            68 => Self::DistributedFileSystem,
+            69 => Self::SATMON,
+            71 => Self::IPCV,
+            76 => Self::BRSATMON,
+            78 => Self::WBMON,
+            79 => Self::WBEXPAK,
+            n => Self::Unknown(n),
+        }
+    }
+}
+
+impl From<Protocol> for u8 {
+    fn from(value: Protocol) -> Self {
+        match value {
+            Protocol::ICMP => 1,
+            Protocol::IGMP => 2,
+            Protocol::GGP => 3,
+            Protocol::ST => 5,
+            Protocol::TCP => 6,
+            Protocol::UCL => 7,
+            Protocol::EGP => 8,
+            Protocol::IGP => 9,
+            Protocol::BBNRCCMON => 10,
+            Protocol::NVP2 => 11,
+            Protocol::PUP => 12,
+            Protocol::ARGUS => 13,
+            Protocol::EMCON => 14,
+            Protocol::XNET => 15,
+            Protocol::CHAOS => 16,
+            Protocol::UDP => 17,
+            Protocol::MUX => 18,
+            Protocol::DCNMEAS => 19,
+            Protocol::HMP => 20,
+            Protocol::PRM => 21,
+            Protocol::XNSIDP => 22,
+            Protocol::TRUNK1 => 23,
+            Protocol::TRUNK2 => 24,
+            Protocol::LEAF1 => 25,
+            Protocol::LEAF2 => 26,
+            Protocol::RDP => 27,
+            Protocol::IRTP => 28,
+            Protocol::ISOTP4 => 29,
+            Protocol::NETBLT => 30,
+            Protocol::MFENSP => 31,
+            Protocol::MERITINP => 32,
+            Protocol::SEP => 33,
+            Protocol::HostInternal => 61,
+            Protocol::CFPT => 62,
+            Protocol::LocalNetwork => 63,
+            Protocol::SATEXPAK => 64,
+            Protocol::MITSUBNET => 65,
+            Protocol::RDV => 66,
+            Protocol::IPPC => 67,
+            Protocol::DistributedFileSystem => 68,
+            Protocol::SATMON => 69,
+            Protocol::IPCV => 71,
+            Protocol::BRSATMON => 76,
+            Protocol::WBMON => 78,
+            Protocol::WBEXPAK => 79,
+            Protocol::Unknown(n) => n,
+        }
+    }
+}
+
 #[tracing::instrument(skip_all)]
 pub fn parse<'buf>(
     message: &'buf [u8],
-) -> impl Fn(&'buf [u8]) -> IResult<&'buf [u8], ResourceRecord> {
+) -> impl Fn(&'buf [u8]) -> IResult<&'buf [u8], ResourceRecord, ParseError<'buf>> {
     use crate::{domain_name::DomainName, Class, Type};
     move |i: &'buf [u8]| {
         let (remaining, name) = DomainName::parse(message)(i)?;
-        let (remaining, ty) = nom::number::streaming::be_u16(remaining)?;
-        let (remaining, class) = nom::number::streaming::be_u16(remaining)?;
-        let (remaining, ttl) = nom::number::streaming::be_i32(remaining)?;
-        let (remaining, data_length) = nom::number::streaming::be_u16(remaining)?;
-        let (remaining, data) = nom::bytes::streaming::take(data_length)(remaining)?;
+        let (remaining, ty) = nom::number::complete::be_u16(remaining)?;
+        let (remaining, class) = nom::number::complete::be_u16(remaining)?;
+        let (remaining, ttl) = nom::number::complete::be_i32(remaining)?;
+        let (remaining, data_length) = nom::number::complete::be_u16(remaining)?;
+        let (remaining, data) = nom::bytes::complete::take(data_length)(remaining)?;
 
         let ty: Type = ty.into();
         let class: Class = class.into();
 
-        let record_data = match (class, ty) {
-            (_, Type::CNAME) => {
-                let (_, cname) = DomainName::parse(message)(data)?;
-                RecordData::CName(CName::new(cname))
-            }
-            (_, Type::HINFO) => {
-                let (_, (cpu, os)) = nom::sequence::pair(
-                    nom::multi::length_count(
-                        nom::number::streaming::be_u8,
-                        nom::character::streaming::anychar,
-                    ),
-                    nom::multi::length_count(
-                        nom::number::streaming::be_u8,
-                        nom::character::streaming::anychar,
-                    ),
-                )(data)?;
-                RecordData::HostInfo(HostInfo::new(
-                    cpu.into_iter().collect(),
-                    os.into_iter().collect(),
-                ))
-            }
-            (_, Type::MB) => {
-                let (_, mail_agent_domain_name) = DomainName::parse(message)(data)?;
-                RecordData::MB(MB::new(mail_agent_domain_name))
-            }
-            (_, Type::MD) => {
-                let (_, mail_agent_domain_name) = DomainName::parse(message)(data)?;
-                RecordData::MD(MD::new(mail_agent_domain_name))
-            }
-            (_, Type::MF) => {
-                let (_, mail_agent_domain_name) = DomainName::parse(message)(data)?;
-                RecordData::MF(MF::new(mail_agent_domain_name))
-            }
-            (_, Type::MG) => {
-                let (_, mail_group_member_name) = DomainName::parse(message)(data)?;
-                RecordData::MG(MG::new(mail_group_member_name))
-            }
-            (_, Type::MINFO) => {
-                let (rem, responsible_mailbox) = DomainName::parse(message)(data)?;
-                let (_, error_mailbox) = DomainName::parse(message)(rem)?;
-                RecordData::MInfo(MInfo::new(responsible_mailbox, error_mailbox))
-            }
-            (_, Type::MR) => {
-                let (_, new_name) = DomainName::parse(message)(data)?;
-                RecordData::MR(MR::new(new_name))
-            }
-            (_, Type::MX) => {
-                let (rem, preference) = nom::number::streaming::be_u16(data)?;
-                let (_rem, domain_name) = DomainName::parse(message)(rem)?;
-                RecordData::MX(MX::new(preference, domain_name))
-            }
-            (_, Type::NULL) => RecordData::Null(Null::new(data.to_vec())),
-            (_, Type::NS) => {
-                let (_, name_server) = DomainName::parse(message)(data)?;
-                RecordData::NS(NS::new(name_server))
-            }
-            (_, Type::PTR) => {
-                let (_, domain_name) = DomainName::parse(message)(data)?;
-                RecordData::PTR(PTR::new(domain_name))
-            }
-            (_, Type::SOA) => {
-                let (rem, domain_name) = DomainName::parse(message)(data)?;
-                let (rem, responsible_mailbox) = DomainName::parse(message)(rem)?;
-                let (rem, serial) = nom::number::complete::be_u32(rem)?;
-                let (rem, refresh) = nom::number::complete::be_u32(rem)?;
-                let (rem, retry) = nom::number::complete::be_u32(rem)?;
-                let (rem, expire) = nom::number::complete::be_u32(rem)?;
-                let (_, minimum) = nom::number::complete::be_u32(rem)?;
+        let (rem, record_data) = parse_rdata(message, class, ty, data).map_err(|e| {
+            // Running out of RDATA means RDLENGTH was too short for what it claimed to hold.
+            e.map(|e| {
+                let offset = crate::error::offset_in(data, e.input());
+                if e.kind() == ParseErrorKind::Truncated && offset <= data.len() {
+                    ParseError::new(e.input(), ParseErrorKind::RdataLengthMismatch(data.len()))
+                } else {
+                    e
+                }
+            })
+        })?;
+        if !rem.is_empty() {
+            return Err(ParseError::failure(
+                rem,
+                ParseErrorKind::RdataLengthMismatch(data.len()),
+            ));
+        }
 
+        Ok((
+            remaining,
+            ResourceRecord::new(name, ty, class, ttl, record_data),
+        ))
+    }
+}
+
+/// Parses the RDATA of a record, returning whatever is left of `data` afterwards.
+fn parse_rdata<'buf>(
+    message: &'buf [u8],
+    class: crate::Class,
+    ty: crate::Type,
+    data: &'buf [u8],
+) -> IResult<&'buf [u8], RecordData, ParseError<'buf>> {
+    use crate::{Class, Type};
+    use nom::combinator::map;
+    use nom::number::complete::{be_u128, be_u16, be_u32, be_u8};
+
+    let name = || DomainName::parse(message);
+
+    match (class, ty) {
+        (_, Type::CNAME) => map(name(), |n| RecordData::CName(CName::new(n)))(data),
+        (_, Type::HINFO) => {
+            let (rem, (cpu, os)) =
+                nom::sequence::pair(parse_character_string, parse_character_string)(data)?;
+            Ok((rem, RecordData::HostInfo(HostInfo { cpu, os })))
+        }
+        (_, Type::MB) => map(name(), |n| RecordData::MB(MB::new(n)))(data),
+        (_, Type::MD) => map(name(), |n| RecordData::MD(MD::new(n)))(data),
+        (_, Type::MF) => map(name(), |n| RecordData::MF(MF::new(n)))(data),
+        (_, Type::MG) => map(name(), |n| RecordData::MG(MG::new(n)))(data),
+        (_, Type::MINFO) => {
+            let (rem, responsible_mailbox) = name()(data)?;
+            let (rem, error_mailbox) = name()(rem)?;
+            Ok((
+                rem,
+                RecordData::MInfo(MInfo::new(responsible_mailbox, error_mailbox)),
+            ))
+        }
+        (_, Type::MR) => map(name(), |n| RecordData::MR(MR::new(n)))(data),
+        (_, Type::MX) => {
+            let (rem, preference) = be_u16(data)?;
+            let (rem, domain_name) = name()(rem)?;
+            Ok((rem, RecordData::MX(MX::new(preference, domain_name))))
+        }
+        (_, Type::NAPTR) => {
+            let (rem, order) = be_u16(data)?;
+            let (rem, preference) = be_u16(rem)?;
+            let (rem, (flags, services, regexp)) = nom::sequence::tuple((
+                parse_character_string,
+                parse_character_string,
+                parse_character_string,
+            ))(rem)?;
+            let (rem, replacement) = name()(rem)?;
+            Ok((
+                rem,
+                RecordData::NAPTR(NAPTR {
+                    order,
+                    preference,
+                    flags,
+                    services,
+                    regexp,
+                    replacement,
+                }),
+            ))
+        }
+        (_, Type::NULL) => Ok((&[], RecordData::Null(Null::new(data.to_vec())))),
+        (_, Type::NS) => map(name(), |n| RecordData::NS(NS::new(n)))(data),
+        (_, Type::PTR) => map(name(), |n| RecordData::PTR(PTR::new(n)))(data),
+        (_, Type::SOA) => {
+            let (rem, domain_name) = name()(data)?;
+            let (rem, responsible_mailbox) = name()(rem)?;
+            let (rem, serial) = be_u32(rem)?;
+            let (rem, refresh) = be_u32(rem)?;
+            let (rem, retry) = be_u32(rem)?;
+            let (rem, expire) = be_u32(rem)?;
+            let (rem, minimum) = be_u32(rem)?;
+
+            Ok((
+                rem,
                 RecordData::SOA(SOA::new(
                     domain_name,
                     responsible_mailbox,
@@ -564,61 +1181,349 @@ pub fn parse<'buf>(
                     retry,
                     expire,
                     minimum,
-                ))
-            }
-            (_, Type::TXT) => {
-                let (_, text): (_, Vec<char>) = nom::multi::count(
-                    nom::character::complete::anychar,
-                    data_length as usize,
-                )(data)?;
-                let string: String = text.iter().collect();
-                RecordData::TXT(TXT::new(string))
-            }
-            (Class::Internet, Type::A) => {
-                let (_remaining, address_bytes) = nom::number::streaming::be_u32(data)?;
-                RecordData::A(A::new(Ipv4Addr::from(address_bytes)))
-            }
-            (Class::Internet, Type::WKS) => {
-                let (rem, address) =
-                    nom::combinator::map(nom::number::complete::be_u32, |a| Ipv4Addr::from(a))(
-                        data,
-                    )?;
-                let (rem, protocol) =
-                    nom::combinator::map(nom::number::complete::be_u8, |proto| {
-                        Protocol::from(proto)
-                    })(rem)?;
-                let ports: Vec<u16> = rem
-                    .iter()
-                    .map(|byte| {
-                        vec![
-                            byte & 0x80,
-                            byte & 0x40,
-                            byte & 0x20,
-                            byte & 0x10,
-                            byte & 0x08,
-                            byte & 0x04,
-                            byte & 0x02,
-                            byte & 0x01,
-                        ]
-                    })
-                    .flatten()
-                    .enumerate()
-                    .filter_map(|(index, bit)| {
-                        if bit != 0 {
-                            Some((index + 1) as u16)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
-                RecordData::WKS(WKS::new(address, protocol, ports))
+                )),
+            ))
+        }
+        (_, Type::SRV) => {
+            let (rem, priority) = be_u16(data)?;
+            let (rem, weight) = be_u16(rem)?;
+            let (rem, port) = be_u16(rem)?;
+            let (rem, target) = name()(rem)?;
+            Ok((
+                rem,
+                RecordData::SRV(SRV::new(priority, weight, port, target)),
+            ))
+        }
+        (_, Type::TXT) => map(
+            nom::multi::many0(nom::error::context(
+                "character-string",
+                nom::multi::length_data(be_u8),
+            )),
+            |strings| RecordData::TXT(TXT::new(strings)),
+        )(data),
+        (_, Type::URI) => {
+            let (rem, priority) = be_u16(data)?;
+            let (rem, weight) = be_u16(rem)?;
+            Ok((&[], RecordData::URI(URI::new(priority, weight, rem))))
+        }
+        (_, Type::DS) => map(dnssec::parse_ds, RecordData::DS)(data),
+        (_, Type::RRSIG) => {
+            dnssec::parse_rrsig(message, data).map(|(r, d)| (r, RecordData::RRSIG(d)))
+        }
+        (_, Type::NSEC) => dnssec::parse_nsec(message, data).map(|(r, d)| (r, RecordData::NSEC(d))),
+        (_, Type::DNSKEY) => map(dnssec::parse_dnskey, RecordData::DNSKEY)(data),
+        (_, Type::NSEC3) => map(dnssec::parse_nsec3, RecordData::NSEC3)(data),
+        (_, Type::NSEC3PARAM) => map(dnssec::parse_nsec3param, RecordData::NSEC3PARAM)(data),
+        (_, Type::SSHFP) => map(security::parse_sshfp, RecordData::SSHFP)(data),
+        (_, Type::TLSA) => map(security::parse_tlsa, RecordData::TLSA)(data),
+        (_, Type::SMIMEA) => map(security::parse_tlsa, RecordData::SMIMEA)(data),
+        (_, Type::SVCB) => svcb::parse_svcb(message, data).map(|(r, d)| (r, RecordData::SVCB(d))),
+        (_, Type::HTTPS) => svcb::parse_svcb(message, data).map(|(r, d)| (r, RecordData::HTTPS(d))),
+        (_, Type::CAA) => map(security::parse_caa, RecordData::CAA)(data),
+        (_, Type::OPT) => map(nom::multi::many0(crate::edns::parse_option), |options| {
+            RecordData::OPT(OPT::new(options))
+        })(data),
+        (Class::Internet, Type::A) => map(be_u32, |address| {
+            RecordData::A(A::new(Ipv4Addr::from(address)))
+        })(data),
+        (Class::Internet, Type::AAAA) => map(be_u128, |address| {
+            RecordData::AAAA(AAAA::new(Ipv6Addr::from(address)))
+        })(data),
+        (Class::Internet, Type::WKS) => {
+            let (rem, address) = map(be_u32, Ipv4Addr::from)(data)?;
+            let (rem, protocol) = map(be_u8, Protocol::from)(rem)?;
+            let ports: Vec<u16> = rem
+                .iter()
+                .flat_map(|byte| (0..8).map(move |bit| byte & (0x80 >> bit)))
+                .enumerate()
+                .filter_map(|(index, bit)| if bit != 0 { Some(index as u16) } else { None })
+                .collect();
+            Ok((&[], RecordData::WKS(WKS::new(address, protocol, ports))))
+        }
+        (class, ty) => Ok((&[], RecordData::Unknown(class, ty, data.to_vec()))),
+    }
+}
+
+/// Reads RDATA in presentation format. Any type can be given in the generic `\#` form of
+/// RFC 3597 §5, which is the only way to write types this library doesn't know the format of.
+fn parse_rdata_text(
+    class: crate::Class,
+    ty: crate::Type,
+    fields: &mut Fields,
+) -> Result<RecordData, TextError> {
+    use crate::{Class, Type};
+
+    if fields
+        .peek()
+        .is_some_and(|t| !t.is_quoted() && t.text() == "\\#")
+    {
+        fields.next("RDATA")?;
+        let length: u16 = fields.number("RDATA length")?;
+        let hex = if length == 0 {
+            String::new()
+        } else {
+            fields.rest("RDATA")?
+        };
+        let data = from_hex(&hex)
+            .filter(|d| d.len() == length as usize)
+            .ok_or_else(|| bad_field("RDATA", &hex))?;
+        return match parse_rdata(&data, class, ty, &data) {
+            Ok(([], rdata)) => Ok(rdata),
+            _ => Err(bad_field("RDATA", &hex)),
+        };
+    }
+
+    let rdata = match (class, ty) {
+        (_, Type::CNAME) => RecordData::CName(CName::new(fields.name("CNAME")?)),
+        (_, Type::HINFO) => {
+            let cpu = fields.character_string("CPU")?;
+            RecordData::HostInfo(HostInfo::new(cpu, fields.character_string("OS")?)?)
+        }
+        (_, Type::MB) => RecordData::MB(MB::new(fields.name("MADNAME")?)),
+        (_, Type::MD) => RecordData::MD(MD::new(fields.name("MADNAME")?)),
+        (_, Type::MF) => RecordData::MF(MF::new(fields.name("MADNAME")?)),
+        (_, Type::MG) => RecordData::MG(MG::new(fields.name("MGMNAME")?)),
+        (_, Type::MINFO) => {
+            RecordData::MInfo(MInfo::new(fields.name("RMAILBX")?, fields.name("EMAILBX")?))
+        }
+        (_, Type::MR) => RecordData::MR(MR::new(fields.name("NEWNAME")?)),
+        (_, Type::MX) => RecordData::MX(MX::new(
+            fields.number("preference")?,
+            fields.name("exchange")?,
+        )),
+        (_, Type::NAPTR) => RecordData::NAPTR(NAPTR::new(
+            fields.number("order")?,
+            fields.number("preference")?,
+            fields.character_string("flags")?,
+            fields.character_string("services")?,
+            fields.character_string("regexp")?,
+            fields.name("replacement")?,
+        )?),
+        (_, Type::NS) => RecordData::NS(NS::new(fields.name("NSDNAME")?)),
+        (_, Type::PTR) => RecordData::PTR(PTR::new(fields.name("PTRDNAME")?)),
+        (_, Type::SOA) => RecordData::SOA(SOA::new(
+            fields.name("MNAME")?,
+            fields.name("RNAME")?,
+            fields.number("serial")?,
+            fields.duration("refresh")?,
+            fields.duration("retry")?,
+            fields.duration("expire")?,
+            fields.duration("minimum")?,
+        )),
+        (_, Type::SRV) => RecordData::SRV(SRV::new(
+            fields.number("priority")?,
+            fields.number("weight")?,
+            fields.number("port")?,
+            fields.name("target")?,
+        )),
+        (_, Type::TXT) => {
+            let mut strings = vec![fields.character_string("character-string")?];
+            while !fields.is_empty() {
+                strings.push(fields.character_string("character-string")?);
             }
-            (class, ty) => RecordData::Unknown(class, ty, data.to_vec()),
+            RecordData::TXT(TXT::new(strings))
+        }
+        (_, Type::URI) => RecordData::URI(URI::new(
+            fields.number("priority")?,
+            fields.number("weight")?,
+            fields.octets("target")?,
+        )),
+        (_, Type::DS) => RecordData::DS(dnssec::parse_ds_text(fields)?),
+        (_, Type::RRSIG) => RecordData::RRSIG(dnssec::parse_rrsig_text(fields)?),
+        (_, Type::NSEC) => RecordData::NSEC(dnssec::parse_nsec_text(fields)?),
+        (_, Type::DNSKEY) => RecordData::DNSKEY(dnssec::parse_dnskey_text(fields)?),
+        (_, Type::NSEC3) => RecordData::NSEC3(dnssec::parse_nsec3_text(fields)?),
+        (_, Type::NSEC3PARAM) => RecordData::NSEC3PARAM(dnssec::parse_nsec3param_text(fields)?),
+        (_, Type::SSHFP) => RecordData::SSHFP(security::parse_sshfp_text(fields)?),
+        (_, Type::TLSA) => RecordData::TLSA(security::parse_tlsa_text(fields)?),
+        (_, Type::SMIMEA) => RecordData::SMIMEA(security::parse_tlsa_text(fields)?),
+        (_, Type::SVCB) => RecordData::SVCB(svcb::parse_svcb_text(fields)?),
+        (_, Type::HTTPS) => RecordData::HTTPS(svcb::parse_svcb_text(fields)?),
+        (_, Type::CAA) => RecordData::CAA(security::parse_caa_text(fields)?),
+        (Class::Internet, Type::A) => RecordData::A(A::new(fields.number("address")?)),
+        (Class::Internet, Type::AAAA) => RecordData::AAAA(AAAA::new(fields.number("address")?)),
+        (Class::Internet, Type::WKS) => {
+            let address = fields.number("address")?;
+            let protocol = fields.parse("protocol", |text| {
+                text.parse::<u8>().ok().map(Protocol::from).or_else(|| {
+                    (0..=u8::MAX)
+                        .map(Protocol::from)
+                        .find(|p| p.to_string().eq_ignore_ascii_case(text))
+                })
+            })?;
+            let ports = fields.all("port", |text| text.parse().ok())?;
+            RecordData::WKS(WKS::new(address, protocol, ports))
+        }
+        (_, ty) => return Err(TextError::UnsupportedType(ty.to_string())),
+    };
+    Ok(rdata)
+}
+
+/// Checks that `bytes` fits in a character-string, which has a one-octet length in front of it.
+pub(crate) fn check_character_string(field: &'static str, bytes: &[u8]) -> Result<(), TextError> {
+    match bytes.len() > u8::MAX as usize {
+        true => Err(TextError::StringTooLong(field)),
+        false => Ok(()),
+    }
+}
+
+fn parse_character_string(i: &[u8]) -> IResult<&[u8], Vec<u8>, ParseError<'_>> {
+    nom::error::context(
+        "character-string",
+        nom::combinator::map(
+            nom::multi::length_data(nom::number::complete::be_u8),
+            <[u8]>::to_vec,
+        ),
+    )(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn character_strings_longer_than_255_octets_are_rejected() {
+        let long = "x".repeat(256);
+        let name = DomainName::root();
+        let too_long = |field| Err::<(), _>(TextError::StringTooLong(field));
+
+        assert!(HostInfo::new("x".repeat(255), "").is_ok());
+        assert_eq!(HostInfo::new(long.clone(), "").map(|_| ()), too_long("CPU"));
+        assert_eq!(
+            NAPTR::new(1, 1, "", "", long.clone(), name.clone()).map(|_| ()),
+            too_long("regexp")
+        );
+        assert_eq!(
+            NSEC3PARAM::new(1, 0, 0, vec![0; 256]).map(|_| ()),
+            too_long("salt")
+        );
+        assert_eq!(
+            NSEC3::new(1, 0, 0, Vec::new(), vec![0; 256], Vec::new()).map(|_| ()),
+            too_long("next hashed owner name")
+        );
+        assert_eq!(
+            CAA::new(0, long.as_str(), Vec::new()).map(|_| ()),
+            too_long("CAA tag")
+        );
+        assert_eq!(
+            SVCB::new(
+                1,
+                name,
+                vec![SvcParam::Alpn(vec![long.clone().into_bytes()])]
+            )
+            .map(|_| ()),
+            too_long("ALPN id")
+        );
+    }
+
+    #[test]
+    fn strings_that_are_not_utf8_round_trip() {
+        let records = [
+            "example. 60 IN NAPTR 100 10 \"u\" \"E2U+sip\" \"!^\\255.*$!sip:\\255@example!\" .",
+            "example. 60 IN HINFO \"\\200\" \"Linux\"",
+            "example. 60 IN URI 10 1 \"https://example/\\255\"",
+        ];
+        for text in records {
+            let record: ResourceRecord = text.parse().unwrap();
+            assert_eq!(record.to_string(), text);
+
+            let message = crate::message::Message::builder()
+                .add_answer(record)
+                .build();
+            let bytes = Vec::try_from(&message).unwrap();
+            let parsed = crate::message::Message::try_from(&bytes[..]).unwrap();
+            assert_eq!(parsed.answers()[0].to_string(), text);
+            assert_eq!(Vec::try_from(&parsed).unwrap(), bytes);
+        }
+
+        let record: ResourceRecord = records[0].parse().unwrap();
+        let RecordData::NAPTR(naptr) = record.rdata() else {
+            panic!("{record}");
         };
+        assert_eq!(naptr.regexp(), b"!^\xff.*$!sip:\xff@example!");
+    }
 
-        Ok((
-            remaining,
-            ResourceRecord::new(name, ty, class, ttl, record_data),
-        ))
+    #[test]
+    fn uri_targets_are_not_limited_to_255_octets() {
+        let target = "x".repeat(300);
+        let text = format!("example. 60 IN URI 10 1 \"{target}\"");
+        let record: ResourceRecord = text.parse().unwrap();
+        let bytes = Vec::try_from(&record).unwrap();
+        let (_, parsed) = parse(&bytes)(&bytes).unwrap();
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn caa_values_are_not_limited_to_255_octets() {
+        let value = format!("ca.example; account={}", "x".repeat(300));
+        let text = format!("example. 60 IN CAA 0 issue \"{value}\"");
+        let record: ResourceRecord = text.parse().unwrap();
+        let bytes = Vec::try_from(&record).unwrap();
+        let (_, parsed) = parse(&bytes)(&bytes).unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(Vec::try_from(&parsed).unwrap(), bytes);
+    }
+
+    #[test]
+    fn caa_tags_that_are_not_alphanumeric_are_kept() {
+        let rdata = b"\x00\x05is\xffuea";
+        let mut message = crate::message::Message::builder()
+            .add_answer("example. 60 IN CAA 0 issue \"a\"".parse().unwrap())
+            .into_bytes()
+            .unwrap();
+        let length = message.len();
+        message.truncate(length - 10);
+        message.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        message.extend_from_slice(rdata);
+
+        let parsed = crate::message::Message::try_from(&message[..]).unwrap();
+        let record = &parsed.answers()[0];
+        let RecordData::CAA(caa) = record.rdata() else {
+            panic!("{record}");
+        };
+        assert_eq!(caa.tag(), b"is\xffue");
+        assert_eq!(
+            record.to_string(),
+            "example. 60 IN CAA \\# 8 00056973FF756561"
+        );
+
+        let reparsed: ResourceRecord = record.to_string().parse().unwrap();
+        assert_eq!(Vec::try_from(reparsed.rdata()).unwrap(), rdata);
+        assert!("example. 60 IN CAA 0 is-sue \"a\""
+            .parse::<ResourceRecord>()
+            .is_err());
+    }
+
+    #[test]
+    fn rdata_can_be_given_in_the_generic_format() {
+        let parse = |text: &str| text.parse::<ResourceRecord>();
+
+        let record = parse("example. 60 A \\# 4 C0000201").unwrap();
+        assert_eq!(record.to_string(), "example. 60 IN A 192.0.2.1");
+        // The hex can be split into several fields, and be in either case.
+        let record = parse("example. 60 TYPE65280 \\# 3 0a 0B0c").unwrap();
+        assert_eq!(record.rdata().to_string(), "\\# 3 0A0B0C");
+        let record = parse("example. 60 TYPE65280 \\# 0").unwrap();
+        assert_eq!(record.rdata().to_string(), "\\# 0");
+
+        let bad_rdata =
+            |text| matches!(parse(text), Err(TextError::BadField { field: "RDATA", .. }));
+        // The length has to match, the hex has to be hex, and the RDATA has to be valid.
+        assert!(bad_rdata("example. 60 TYPE65280 \\# 2 0A0B0C"));
+        assert!(bad_rdata("example. 60 TYPE65280 \\# 1 +1"));
+        assert!(bad_rdata("example. 60 TYPE65280 \\# 1 0"));
+        assert!(bad_rdata("example. 60 A \\# 3 C00002"));
+        assert!(matches!(
+            parse("example. 60 TYPE65280 \\# 1"),
+            Err(TextError::MissingField("RDATA"))
+        ));
+        // Quoted, it's just text.
+        assert_eq!(
+            parse("example. 60 TXT \"\\\\#\" \"1\"")
+                .unwrap()
+                .rdata()
+                .to_string(),
+            "\"\\\\#\" \"1\""
+        );
     }
 }
@@ -0,0 +1,628 @@
+use super::check_character_string;
+use crate::domain_name::DomainName;
+use crate::error::{ParseResult, TextError};
+use crate::presentation::{
+    base32hex, base64, from_base32hex, from_base64, from_hex, from_timestamp, hex, timestamp,
+    Fields,
+};
+use crate::Type;
+
+/// A public key used to verify RRSIGs (RFC 4034 §2).
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(
+    fmt = "{} {} {} {}",
+    flags,
+    protocol,
+    algorithm,
+    r#"base64(public_key)"#
+)]
+pub struct DNSKEY {
+    flags: u16,
+    protocol: u8,
+    algorithm: u8,
+    public_key: Vec<u8>,
+}
+
+impl DNSKEY {
+    pub fn new(flags: u16, protocol: u8, algorithm: u8, public_key: Vec<u8>) -> Self {
+        Self {
+            flags,
+            protocol,
+            algorithm,
+            public_key,
+        }
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    pub fn is_zone_key(&self) -> bool {
+        self.flags & 0x0100 != 0
+    }
+
+    pub fn is_secure_entry_point(&self) -> bool {
+        self.flags & 0x0001 != 0
+    }
+
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// The key tag that RRSIG and DS records use to refer to this key (RFC 4034 Appendix B).
+    pub fn key_tag(&self) -> u16 {
+        let mut rdata = Vec::with_capacity(4 + self.public_key.len());
+        rdata.extend_from_slice(&self.flags.to_be_bytes());
+        rdata.push(self.protocol);
+        rdata.push(self.algorithm);
+        rdata.extend_from_slice(&self.public_key);
+
+        let mut accumulator: u32 = 0;
+        for (index, byte) in rdata.iter().enumerate() {
+            if index % 2 == 0 {
+                accumulator += (*byte as u32) << 8;
+            } else {
+                accumulator += *byte as u32;
+            }
+        }
+        accumulator += (accumulator >> 16) & 0xFFFF;
+        (accumulator & 0xFFFF) as u16
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u16(self.flags);
+        encoder.put_u8(self.protocol);
+        encoder.put_u8(self.algorithm);
+        encoder.put_slice(&self.public_key);
+    }
+}
+
+/// Delegation signer (RFC 4034 §5).
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(fmt = "{} {} {} {}", key_tag, algorithm, digest_type, r#"hex(digest)"#)]
+pub struct DS {
+    key_tag: u16,
+    algorithm: u8,
+    digest_type: u8,
+    digest: Vec<u8>,
+}
+
+impl DS {
+    pub fn new(key_tag: u16, algorithm: u8, digest_type: u8, digest: Vec<u8>) -> Self {
+        Self {
+            key_tag,
+            algorithm,
+            digest_type,
+            digest,
+        }
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    pub fn digest_type(&self) -> u8 {
+        self.digest_type
+    }
+
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u16(self.key_tag);
+        encoder.put_u8(self.algorithm);
+        encoder.put_u8(self.digest_type);
+        encoder.put_slice(&self.digest);
+    }
+}
+
+/// A signature over an RRset (RFC 4034 §3).
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(
+    fmt = "{} {} {} {} {} {} {} {} {}",
+    type_covered,
+    algorithm,
+    labels,
+    original_ttl,
+    r#"timestamp(*signature_expiration)"#,
+    r#"timestamp(*signature_inception)"#,
+    key_tag,
+    signer_name,
+    r#"base64(signature)"#
+)]
+pub struct RRSIG {
+    type_covered: Type,
+    algorithm: u8,
+    labels: u8,
+    original_ttl: u32,
+    signature_expiration: u32,
+    signature_inception: u32,
+    key_tag: u16,
+    signer_name: DomainName,
+    signature: Vec<u8>,
+}
+
+impl RRSIG {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        type_covered: Type,
+        algorithm: u8,
+        labels: u8,
+        original_ttl: u32,
+        signature_expiration: u32,
+        signature_inception: u32,
+        key_tag: u16,
+        signer_name: DomainName,
+        signature: Vec<u8>,
+    ) -> Self {
+        Self {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            signature_expiration,
+            signature_inception,
+            key_tag,
+            signer_name,
+            signature,
+        }
+    }
+
+    pub fn type_covered(&self) -> Type {
+        self.type_covered
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    pub fn labels(&self) -> u8 {
+        self.labels
+    }
+
+    pub fn original_ttl(&self) -> u32 {
+        self.original_ttl
+    }
+
+    /// Seconds since the epoch, to be compared using serial number arithmetic.
+    pub fn signature_expiration(&self) -> u32 {
+        self.signature_expiration
+    }
+
+    /// Seconds since the epoch, to be compared using serial number arithmetic.
+    pub fn signature_inception(&self) -> u32 {
+        self.signature_inception
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    pub fn signer_name(&self) -> &DomainName {
+        &self.signer_name
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u16(self.type_covered.into());
+        encoder.put_u8(self.algorithm);
+        encoder.put_u8(self.labels);
+        encoder.put_u32(self.original_ttl);
+        encoder.put_u32(self.signature_expiration);
+        encoder.put_u32(self.signature_inception);
+        encoder.put_u16(self.key_tag);
+        encoder.put_name(&self.signer_name, false);
+        encoder.put_slice(&self.signature);
+    }
+}
+
+/// Authenticated denial of existence (RFC 4034 §4).
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(fmt = "{} {}", next_domain_name, r#"display_types(types)"#)]
+pub struct NSEC {
+    next_domain_name: DomainName,
+    types: Vec<Type>,
+}
+
+impl NSEC {
+    pub fn new(next_domain_name: DomainName, types: Vec<Type>) -> Self {
+        Self {
+            next_domain_name,
+            types,
+        }
+    }
+
+    pub fn next_domain_name(&self) -> &DomainName {
+        &self.next_domain_name
+    }
+
+    /// The types present at the owner name.
+    pub fn types(&self) -> &[Type] {
+        &self.types
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_name(&self.next_domain_name, false);
+        encode_type_bitmap(&self.types, encoder);
+    }
+}
+
+/// Hashed authenticated denial of existence (RFC 5155 §3).
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(
+    fmt = "{} {} {} {} {} {}",
+    hash_algorithm,
+    flags,
+    iterations,
+    r#"display_salt(salt)"#,
+    r#"base32hex(next_hashed_owner_name)"#,
+    r#"display_types(types)"#
+)]
+pub struct NSEC3 {
+    hash_algorithm: u8,
+    flags: u8,
+    iterations: u16,
+    salt: Vec<u8>,
+    next_hashed_owner_name: Vec<u8>,
+    types: Vec<Type>,
+}
+
+impl NSEC3 {
+    /// Creates the record, failing if the salt or hashed name is longer than its one-octet
+    /// length allows.
+    pub fn new(
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+        next_hashed_owner_name: Vec<u8>,
+        types: Vec<Type>,
+    ) -> Result<Self, TextError> {
+        check_character_string("salt", &salt)?;
+        check_character_string("next hashed owner name", &next_hashed_owner_name)?;
+        Ok(Self {
+            hash_algorithm,
+            flags,
+            iterations,
+            salt,
+            next_hashed_owner_name,
+            types,
+        })
+    }
+
+    pub fn hash_algorithm(&self) -> u8 {
+        self.hash_algorithm
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn opt_out(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    pub fn iterations(&self) -> u16 {
+        self.iterations
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub fn next_hashed_owner_name(&self) -> &[u8] {
+        &self.next_hashed_owner_name
+    }
+
+    /// The types present at the original owner name.
+    pub fn types(&self) -> &[Type] {
+        &self.types
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u8(self.hash_algorithm);
+        encoder.put_u8(self.flags);
+        encoder.put_u16(self.iterations);
+        encoder.put_u8(self.salt.len() as u8);
+        encoder.put_slice(&self.salt);
+        encoder.put_u8(self.next_hashed_owner_name.len() as u8);
+        encoder.put_slice(&self.next_hashed_owner_name);
+        encode_type_bitmap(&self.types, encoder);
+    }
+}
+
+/// The NSEC3 parameters an authoritative server uses for a zone (RFC 5155 §4).
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(
+    fmt = "{} {} {} {}",
+    hash_algorithm,
+    flags,
+    iterations,
+    r#"display_salt(salt)"#
+)]
+pub struct NSEC3PARAM {
+    hash_algorithm: u8,
+    flags: u8,
+    iterations: u16,
+    salt: Vec<u8>,
+}
+
+impl NSEC3PARAM {
+    /// Creates the record, failing if the salt is longer than its one-octet length allows.
+    pub fn new(
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+    ) -> Result<Self, TextError> {
+        check_character_string("salt", &salt)?;
+        Ok(Self {
+            hash_algorithm,
+            flags,
+            iterations,
+            salt,
+        })
+    }
+
+    pub fn hash_algorithm(&self) -> u8 {
+        self.hash_algorithm
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn iterations(&self) -> u16 {
+        self.iterations
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u8(self.hash_algorithm);
+        encoder.put_u8(self.flags);
+        encoder.put_u16(self.iterations);
+        encoder.put_u8(self.salt.len() as u8);
+        encoder.put_slice(&self.salt);
+    }
+}
+
+fn display_salt(salt: &[u8]) -> String {
+    if salt.is_empty() {
+        "-".to_string()
+    } else {
+        hex(salt)
+    }
+}
+
+fn display_types(types: &[Type]) -> String {
+    itertools::join(types.iter(), " ")
+}
+
+/// Writes the window blocks of a type bitmap (RFC 4034 §4.1.2). Types are sorted and deduplicated,
+/// and empty windows are left out.
+fn encode_type_bitmap(types: &[Type], encoder: &mut crate::encoder::Encoder) {
+    let mut types: Vec<u16> = types.iter().map(|t| u16::from(*t)).collect();
+    types.sort_unstable();
+    types.dedup();
+
+    for (window, types) in &itertools::Itertools::chunk_by(types.into_iter(), |t| t >> 8) {
+        let mut bitmap = [0_u8; 32];
+        let mut length = 0;
+        for ty in types {
+            let low = (ty & 0xFF) as usize;
+            bitmap[low / 8] |= 0x80 >> (low % 8);
+            length = low / 8 + 1;
+        }
+        encoder.put_u8(window as u8);
+        encoder.put_u8(length as u8);
+        encoder.put_slice(&bitmap[..length]);
+    }
+}
+
+fn parse_type_bitmap(i: &[u8]) -> ParseResult<'_, Vec<Type>> {
+    let (i, windows) = nom::error::context(
+        "type bitmap",
+        nom::combinator::all_consuming(nom::multi::many0(nom::sequence::pair(
+            nom::number::complete::be_u8,
+            nom::multi::length_data(nom::combinator::verify(nom::number::complete::be_u8, |l| {
+                (1..=32).contains(l)
+            })),
+        ))),
+    )(i)?;
+
+    let types = windows
+        .into_iter()
+        .flat_map(|(window, bitmap)| {
+            bitmap.iter().enumerate().flat_map(move |(index, byte)| {
+                (0..8_u16)
+                    .filter(move |bit| byte & (0x80 >> bit) != 0)
+                    .map(move |bit| Type::from(((window as u16) << 8) | (index as u16 * 8 + bit)))
+            })
+        })
+        .collect();
+
+    Ok((i, types))
+}
+
+pub(super) fn parse_dnskey(data: &[u8]) -> ParseResult<'_, DNSKEY> {
+    let (rem, flags) = nom::number::complete::be_u16(data)?;
+    let (rem, protocol) = nom::number::complete::be_u8(rem)?;
+    let (rem, algorithm) = nom::number::complete::be_u8(rem)?;
+    Ok((&[], DNSKEY::new(flags, protocol, algorithm, rem.to_vec())))
+}
+
+pub(super) fn parse_ds(data: &[u8]) -> ParseResult<'_, DS> {
+    let (rem, key_tag) = nom::number::complete::be_u16(data)?;
+    let (rem, algorithm) = nom::number::complete::be_u8(rem)?;
+    let (rem, digest_type) = nom::number::complete::be_u8(rem)?;
+    Ok((&[], DS::new(key_tag, algorithm, digest_type, rem.to_vec())))
+}
+
+pub(super) fn parse_rrsig<'buf>(message: &'buf [u8], data: &'buf [u8]) -> ParseResult<'buf, RRSIG> {
+    let (rem, type_covered) = nom::number::complete::be_u16(data)?;
+    let (rem, algorithm) = nom::number::complete::be_u8(rem)?;
+    let (rem, labels) = nom::number::complete::be_u8(rem)?;
+    let (rem, original_ttl) = nom::number::complete::be_u32(rem)?;
+    let (rem, signature_expiration) = nom::number::complete::be_u32(rem)?;
+    let (rem, signature_inception) = nom::number::complete::be_u32(rem)?;
+    let (rem, key_tag) = nom::number::complete::be_u16(rem)?;
+    let (rem, signer_name) = DomainName::parse(message)(rem)?;
+    Ok((
+        &[],
+        RRSIG::new(
+            type_covered.into(),
+            algorithm,
+            labels,
+            original_ttl,
+            signature_expiration,
+            signature_inception,
+            key_tag,
+            signer_name,
+            rem.to_vec(),
+        ),
+    ))
+}
+
+pub(super) fn parse_nsec<'buf>(message: &'buf [u8], data: &'buf [u8]) -> ParseResult<'buf, NSEC> {
+    let (rem, next_domain_name) = DomainName::parse(message)(data)?;
+    let (rem, types) = parse_type_bitmap(rem)?;
+    Ok((rem, NSEC::new(next_domain_name, types)))
+}
+
+pub(super) fn parse_nsec3(data: &[u8]) -> ParseResult<'_, NSEC3> {
+    let (rem, hash_algorithm) = nom::number::complete::be_u8(data)?;
+    let (rem, flags) = nom::number::complete::be_u8(rem)?;
+    let (rem, iterations) = nom::number::complete::be_u16(rem)?;
+    let (rem, salt) = nom::multi::length_data(nom::number::complete::be_u8)(rem)?;
+    let (rem, next_hashed_owner_name) = nom::multi::length_data(nom::number::complete::be_u8)(rem)?;
+    let (rem, types) = parse_type_bitmap(rem)?;
+    Ok((
+        rem,
+        NSEC3 {
+            hash_algorithm,
+            flags,
+            iterations,
+            salt: salt.to_vec(),
+            next_hashed_owner_name: next_hashed_owner_name.to_vec(),
+            types,
+        },
+    ))
+}
+
+pub(super) fn parse_nsec3param(data: &[u8]) -> ParseResult<'_, NSEC3PARAM> {
+    let (rem, hash_algorithm) = nom::number::complete::be_u8(data)?;
+    let (rem, flags) = nom::number::complete::be_u8(rem)?;
+    let (rem, iterations) = nom::number::complete::be_u16(rem)?;
+    let (rem, salt) = nom::error::context(
+        "NSEC3PARAM salt",
+        nom::multi::length_data(nom::number::complete::be_u8),
+    )(rem)?;
+    Ok((
+        rem,
+        NSEC3PARAM {
+            hash_algorithm,
+            flags,
+            iterations,
+            salt: salt.to_vec(),
+        },
+    ))
+}
+
+/// Reads a salt, which is written as `-` when it's empty.
+fn salt_from_text(text: &str) -> Option<Vec<u8>> {
+    match text {
+        "-" => Some(Vec::new()),
+        text => from_hex(text),
+    }
+}
+
+pub(super) fn parse_dnskey_text(fields: &mut Fields) -> Result<DNSKEY, TextError> {
+    Ok(DNSKEY::new(
+        fields.number("flags")?,
+        fields.number("protocol")?,
+        fields.number("algorithm")?,
+        from_base64_field(fields, "public key")?,
+    ))
+}
+
+pub(super) fn parse_ds_text(fields: &mut Fields) -> Result<DS, TextError> {
+    Ok(DS::new(
+        fields.number("key tag")?,
+        fields.number("algorithm")?,
+        fields.number("digest type")?,
+        from_hex_field(fields, "digest")?,
+    ))
+}
+
+pub(super) fn parse_rrsig_text(fields: &mut Fields) -> Result<RRSIG, TextError> {
+    Ok(RRSIG::new(
+        fields.parse("type covered", |t| t.parse().ok())?,
+        fields.number("algorithm")?,
+        fields.number("labels")?,
+        fields.number("original TTL")?,
+        fields.parse("signature expiration", from_timestamp)?,
+        fields.parse("signature inception", from_timestamp)?,
+        fields.number("key tag")?,
+        fields.name("signer's name")?,
+        from_base64_field(fields, "signature")?,
+    ))
+}
+
+pub(super) fn parse_nsec_text(fields: &mut Fields) -> Result<NSEC, TextError> {
+    Ok(NSEC::new(
+        fields.name("next domain name")?,
+        fields.all("type", |t| t.parse().ok())?,
+    ))
+}
+
+pub(super) fn parse_nsec3_text(fields: &mut Fields) -> Result<NSEC3, TextError> {
+    NSEC3::new(
+        fields.number("hash algorithm")?,
+        fields.number("flags")?,
+        fields.number("iterations")?,
+        fields.parse("salt", salt_from_text)?,
+        fields.parse("next hashed owner name", from_base32hex)?,
+        fields.all("type", |t| t.parse().ok())?,
+    )
+}
+
+pub(super) fn parse_nsec3param_text(fields: &mut Fields) -> Result<NSEC3PARAM, TextError> {
+    NSEC3PARAM::new(
+        fields.number("hash algorithm")?,
+        fields.number("flags")?,
+        fields.number("iterations")?,
+        fields.parse("salt", salt_from_text)?,
+    )
+}
+
+fn from_base64_field(fields: &mut Fields, field: &'static str) -> Result<Vec<u8>, TextError> {
+    let text = fields.rest(field)?;
+    from_base64(&text).ok_or(TextError::BadField { field, value: text })
+}
+
+pub(super) fn from_hex_field(
+    fields: &mut Fields,
+    field: &'static str,
+) -> Result<Vec<u8>, TextError> {
+    let text = fields.rest(field)?;
+    from_hex(&text).ok_or(TextError::BadField { field, value: text })
+}
@@ -0,0 +1,221 @@
+use std::fmt::{Display, Formatter};
+
+use super::check_character_string;
+use super::dnssec::from_hex_field;
+use crate::error::{ParseResult, TextError};
+use crate::presentation::{generic, hex, quoted, Fields};
+
+/// Certification Authority Authorization (RFC 8659). The tag is kept as the bytes it was sent as,
+/// so a record with a malformed one can still be read and passed on.
+#[derive(Clone, Debug)]
+pub struct CAA {
+    flags: u8,
+    tag: Vec<u8>,
+    value: Vec<u8>,
+}
+
+impl CAA {
+    /// Creates the record, failing if the tag is longer than its one-octet length allows.
+    pub fn new(flags: u8, tag: impl Into<Vec<u8>>, value: Vec<u8>) -> Result<Self, TextError> {
+        let tag = tag.into();
+        check_character_string("CAA tag", &tag)?;
+        Ok(Self { flags, tag, value })
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Whether a CA that doesn't understand this property's tag must refuse to issue.
+    pub fn is_critical(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    pub fn tag(&self) -> &[u8] {
+        &self.tag
+    }
+
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u8(self.flags);
+        encoder.put_u8(self.tag.len() as u8);
+        encoder.put_slice(&self.tag);
+        encoder.put_slice(&self.value);
+    }
+}
+
+impl Display for CAA {
+    /// Tags that aren't the letters and digits RFC 8659 §4.1.1 allows have no presentation form,
+    /// so such records are written in the generic format instead.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match std::str::from_utf8(&self.tag) {
+            Ok(tag) if is_valid_tag(tag) => {
+                write!(f, "{} {} {}", self.flags, tag, quoted(&self.value))
+            }
+            _ => {
+                let mut encoder = crate::encoder::Encoder::new();
+                self.encode(&mut encoder);
+                write!(f, "{}", generic(&encoder.into_bytes()))
+            }
+        }
+    }
+}
+
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty() && tag.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// A TLS certificate association (RFC 6698).
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(
+    fmt = "{} {} {} {}",
+    usage,
+    selector,
+    matching_type,
+    r#"hex(certificate_association_data)"#
+)]
+pub struct TLSA {
+    usage: u8,
+    selector: u8,
+    matching_type: u8,
+    certificate_association_data: Vec<u8>,
+}
+
+/// S/MIME certificate associations share their RDATA format with TLSA (RFC 8162 §2).
+pub type SMIMEA = TLSA;
+
+impl TLSA {
+    pub fn new(
+        usage: u8,
+        selector: u8,
+        matching_type: u8,
+        certificate_association_data: Vec<u8>,
+    ) -> Self {
+        Self {
+            usage,
+            selector,
+            matching_type,
+            certificate_association_data,
+        }
+    }
+
+    pub fn usage(&self) -> u8 {
+        self.usage
+    }
+
+    pub fn selector(&self) -> u8 {
+        self.selector
+    }
+
+    pub fn matching_type(&self) -> u8 {
+        self.matching_type
+    }
+
+    pub fn certificate_association_data(&self) -> &[u8] {
+        &self.certificate_association_data
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u8(self.usage);
+        encoder.put_u8(self.selector);
+        encoder.put_u8(self.matching_type);
+        encoder.put_slice(&self.certificate_association_data);
+    }
+}
+
+/// An SSH host key fingerprint (RFC 4255).
+#[derive(Clone, Debug, derive_more::Display)]
+#[display(fmt = "{} {} {}", algorithm, fingerprint_type, r#"hex(fingerprint)"#)]
+pub struct SSHFP {
+    algorithm: u8,
+    fingerprint_type: u8,
+    fingerprint: Vec<u8>,
+}
+
+impl SSHFP {
+    pub fn new(algorithm: u8, fingerprint_type: u8, fingerprint: Vec<u8>) -> Self {
+        Self {
+            algorithm,
+            fingerprint_type,
+            fingerprint,
+        }
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    pub fn fingerprint_type(&self) -> u8 {
+        self.fingerprint_type
+    }
+
+    pub fn fingerprint(&self) -> &[u8] {
+        &self.fingerprint
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u8(self.algorithm);
+        encoder.put_u8(self.fingerprint_type);
+        encoder.put_slice(&self.fingerprint);
+    }
+}
+
+pub(super) fn parse_caa(data: &[u8]) -> ParseResult<'_, CAA> {
+    let (rem, flags) = nom::number::complete::be_u8(data)?;
+    let (rem, tag) = nom::error::context(
+        "CAA tag",
+        nom::multi::length_data(nom::combinator::verify(nom::number::complete::be_u8, |l| {
+            *l > 0
+        })),
+    )(rem)?;
+    Ok((
+        &[],
+        CAA {
+            flags,
+            tag: tag.to_vec(),
+            value: rem.to_vec(),
+        },
+    ))
+}
+
+pub(super) fn parse_tlsa(data: &[u8]) -> ParseResult<'_, TLSA> {
+    let (rem, usage) = nom::number::complete::be_u8(data)?;
+    let (rem, selector) = nom::number::complete::be_u8(rem)?;
+    let (rem, matching_type) = nom::number::complete::be_u8(rem)?;
+    Ok((&[], TLSA::new(usage, selector, matching_type, rem.to_vec())))
+}
+
+pub(super) fn parse_sshfp(data: &[u8]) -> ParseResult<'_, SSHFP> {
+    let (rem, algorithm) = nom::number::complete::be_u8(data)?;
+    let (rem, fingerprint_type) = nom::number::complete::be_u8(rem)?;
+    Ok((&[], SSHFP::new(algorithm, fingerprint_type, rem.to_vec())))
+}
+
+pub(super) fn parse_caa_text(fields: &mut Fields) -> Result<CAA, TextError> {
+    let flags = fields.number("flags")?;
+    let tag = fields.parse("CAA tag", |t| {
+        Some(t.to_string()).filter(|t| is_valid_tag(t))
+    })?;
+    // The value runs to the end of the RDATA, so it isn't limited to 255 octets (RFC 8659 §4.1).
+    CAA::new(flags, tag, fields.octets("CAA value")?)
+}
+
+pub(super) fn parse_tlsa_text(fields: &mut Fields) -> Result<TLSA, TextError> {
+    Ok(TLSA::new(
+        fields.number("usage")?,
+        fields.number("selector")?,
+        fields.number("matching type")?,
+        from_hex_field(fields, "certificate association data")?,
+    ))
+}
+
+pub(super) fn parse_sshfp_text(fields: &mut Fields) -> Result<SSHFP, TextError> {
+    Ok(SSHFP::new(
+        fields.number("algorithm")?,
+        fields.number("fingerprint type")?,
+        from_hex_field(fields, "fingerprint")?,
+    ))
+}
@@ -0,0 +1,465 @@
+use std::fmt::{Display, Formatter};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use super::check_character_string;
+use crate::domain_name::DomainName;
+use crate::error::{ParseError, ParseErrorKind, ParseResult, TextError};
+use crate::presentation::{bad_field, base64, from_base64, quoted, unescape_string, Fields};
+
+/// Service binding (RFC 9460). A priority of zero makes this an alias to `target`, in which case
+/// there are no parameters.
+#[derive(Clone, Debug)]
+pub struct SVCB {
+    priority: u16,
+    target: DomainName,
+    params: Vec<SvcParam>,
+}
+
+/// HTTPS records are SVCB records for the `https` scheme, and share the same RDATA format
+/// (RFC 9460 §9).
+pub type HTTPS = SVCB;
+
+impl SVCB {
+    /// Creates the record, putting `params` into the increasing key order required on the wire,
+    /// and failing if a key appears twice. ALPN ids have a one-octet length, so empty ones and
+    /// any longer than 255 octets are rejected.
+    pub fn new(
+        priority: u16,
+        target: DomainName,
+        mut params: Vec<SvcParam>,
+    ) -> Result<Self, TextError> {
+        for param in params.iter() {
+            if let SvcParam::Alpn(ids) = param {
+                for id in ids.iter() {
+                    if id.is_empty() {
+                        return Err(bad_field("ALPN id", ""));
+                    }
+                    check_character_string("ALPN id", id)?;
+                }
+            }
+        }
+        params.sort_by_key(|p| p.key());
+        if params.windows(2).any(|pair| pair[0].key() == pair[1].key()) {
+            return Err(bad_field("SvcParam", "duplicate key"));
+        }
+        Ok(Self {
+            priority,
+            target,
+            params,
+        })
+    }
+
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    pub fn is_alias(&self) -> bool {
+        self.priority == 0
+    }
+
+    pub fn target(&self) -> &DomainName {
+        &self.target
+    }
+
+    pub fn params(&self) -> &[SvcParam] {
+        &self.params
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u16(self.priority);
+        encoder.put_name(&self.target, false);
+        for param in self.params.iter() {
+            param.encode(encoder);
+        }
+    }
+}
+
+impl Display for SVCB {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.priority, self.target)?;
+        for param in self.params.iter() {
+            write!(f, " {param}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single SvcParam. Keys this library doesn't interpret are kept as their raw number and value.
+/// ALPN ids are octet strings, and not necessarily UTF-8.
+#[derive(Clone, Debug)]
+pub enum SvcParam {
+    Mandatory(Vec<u16>),
+    Alpn(Vec<Vec<u8>>),
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    Ech(Vec<u8>),
+    Ipv6Hint(Vec<Ipv6Addr>),
+    Unknown(u16, Vec<u8>),
+}
+
+impl SvcParam {
+    pub fn key(&self) -> u16 {
+        match self {
+            SvcParam::Mandatory(_) => 0,
+            SvcParam::Alpn(_) => 1,
+            SvcParam::NoDefaultAlpn => 2,
+            SvcParam::Port(_) => 3,
+            SvcParam::Ipv4Hint(_) => 4,
+            SvcParam::Ech(_) => 5,
+            SvcParam::Ipv6Hint(_) => 6,
+            SvcParam::Unknown(key, _) => *key,
+        }
+    }
+
+    fn encode(&self, encoder: &mut crate::encoder::Encoder) {
+        encoder.put_u16(self.key());
+        let start = encoder.start_length();
+
+        match self {
+            SvcParam::Mandatory(keys) => {
+                for key in keys.iter() {
+                    encoder.put_u16(*key);
+                }
+            }
+            SvcParam::Alpn(ids) => {
+                for id in ids.iter() {
+                    encoder.put_u8(id.len() as u8);
+                    encoder.put_slice(id);
+                }
+            }
+            SvcParam::NoDefaultAlpn => {}
+            SvcParam::Port(port) => encoder.put_u16(*port),
+            SvcParam::Ipv4Hint(addresses) => {
+                for address in addresses.iter() {
+                    encoder.put_slice(&address.octets());
+                }
+            }
+            SvcParam::Ech(config) | SvcParam::Unknown(_, config) => encoder.put_slice(config),
+            SvcParam::Ipv6Hint(addresses) => {
+                for address in addresses.iter() {
+                    encoder.put_slice(&address.octets());
+                }
+            }
+        }
+
+        encoder.end_length("SvcParam length", start);
+    }
+}
+
+impl Display for SvcParam {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SvcParam::Mandatory(keys) => write!(
+                f,
+                "mandatory={}",
+                itertools::join(keys.iter().map(|k| key_name(*k)), ",")
+            ),
+            SvcParam::Alpn(ids) => write!(
+                f,
+                "alpn={}",
+                itertools::join(ids.iter().map(|id| escape_list_item(id)), ",")
+            ),
+            SvcParam::NoDefaultAlpn => write!(f, "no-default-alpn"),
+            SvcParam::Port(port) => write!(f, "port={port}"),
+            SvcParam::Ipv4Hint(addresses) => {
+                write!(f, "ipv4hint={}", itertools::join(addresses.iter(), ","))
+            }
+            SvcParam::Ech(config) => write!(f, "ech={}", base64(config)),
+            SvcParam::Ipv6Hint(addresses) => {
+                write!(f, "ipv6hint={}", itertools::join(addresses.iter(), ","))
+            }
+            SvcParam::Unknown(key, value) => {
+                write!(f, "{}={}", key_name(*key), quoted(value))
+            }
+        }
+    }
+}
+
+/// Escapes an item of a comma-separated value list so that it reads back as one item of one field.
+/// Commas and backslashes get a backslash for the list, and that backslash gets another for the
+/// field (RFC 9460 Appendix A.1), so `a,b` is written `a\\,b`. Anything that would end the field
+/// is written as `\DDD`.
+fn escape_list_item(item: &[u8]) -> String {
+    let mut output = String::with_capacity(item.len());
+    for byte in item {
+        match byte {
+            b',' => output.push_str("\\\\,"),
+            b'\\' => output.push_str("\\\\\\\\"),
+            b'"' | b';' | b'(' | b')' => output.push_str(&format!("\\{byte:03}")),
+            0x21..=0x7E => output.push(*byte as char),
+            _ => output.push_str(&format!("\\{byte:03}")),
+        }
+    }
+    output
+}
+
+/// The presentation name of a SvcParamKey (RFC 9460 §14.3.2).
+pub(crate) fn key_name(key: u16) -> String {
+    match key {
+        0 => "mandatory".to_string(),
+        1 => "alpn".to_string(),
+        2 => "no-default-alpn".to_string(),
+        3 => "port".to_string(),
+        4 => "ipv4hint".to_string(),
+        5 => "ech".to_string(),
+        6 => "ipv6hint".to_string(),
+        key => format!("key{key}"),
+    }
+}
+
+/// The SvcParamKey for a presentation name, the inverse of [`key_name`].
+fn key_number(name: &str) -> Option<u16> {
+    match name {
+        "mandatory" => Some(0),
+        "alpn" => Some(1),
+        "no-default-alpn" => Some(2),
+        "port" => Some(3),
+        "ipv4hint" => Some(4),
+        "ech" => Some(5),
+        "ipv6hint" => Some(6),
+        name => name.strip_prefix("key")?.parse().ok(),
+    }
+}
+
+/// Splits a comma-separated value list. The value is unescaped as a character-string first, and
+/// what's left is split at commas that don't have a backslash in front of them (RFC 9460
+/// Appendix A.1).
+fn split_list(value: &str) -> Option<Vec<Vec<u8>>> {
+    let mut items = vec![Vec::new()];
+    let value = unescape_string(value)?;
+    let mut bytes = value.iter();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\\' => items.last_mut()?.push(*bytes.next()?),
+            b',' => items.push(Vec::new()),
+            _ => items.last_mut()?.push(*byte),
+        }
+    }
+    Some(items).filter(|items| items.iter().all(|i| !i.is_empty()))
+}
+
+fn param_from_text(text: &str) -> Option<SvcParam> {
+    let (key, value) = match text.split_once('=') {
+        Some((key, value)) => {
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            (key, Some(value))
+        }
+        None => (text, None),
+    };
+    let utf8 = |item: Vec<u8>| String::from_utf8(item).ok();
+
+    let param = match (key_number(key)?, value) {
+        (0, Some(value)) => SvcParam::Mandatory(
+            split_list(value)?
+                .into_iter()
+                .map(|k| key_number(&utf8(k)?))
+                .collect::<Option<_>>()?,
+        ),
+        (1, Some(value)) => SvcParam::Alpn(split_list(value)?),
+        (2, None) => SvcParam::NoDefaultAlpn,
+        (3, Some(value)) => SvcParam::Port(value.parse().ok()?),
+        (4, Some(value)) => SvcParam::Ipv4Hint(
+            value
+                .split(',')
+                .map(|a| a.parse().ok())
+                .collect::<Option<_>>()?,
+        ),
+        (5, Some(value)) => SvcParam::Ech(from_base64(value)?),
+        (6, Some(value)) => SvcParam::Ipv6Hint(
+            value
+                .split(',')
+                .map(|a| a.parse().ok())
+                .collect::<Option<_>>()?,
+        ),
+        (key @ 7.., value) => SvcParam::Unknown(key, unescape_string(value.unwrap_or(""))?),
+        _ => return None,
+    };
+    Some(param)
+}
+
+pub(super) fn parse_svcb_text(fields: &mut Fields) -> Result<SVCB, TextError> {
+    let priority = fields.number("priority")?;
+    let target = fields.name("target")?;
+    let params = fields.all("SvcParam", param_from_text)?;
+    SVCB::new(priority, target, params)
+}
+
+fn parse_param(i: &[u8]) -> ParseResult<'_, SvcParam> {
+    use nom::combinator::all_consuming;
+    use nom::number::complete::{be_u128, be_u16, be_u32, be_u8};
+
+    let (remaining, key) = be_u16(i)?;
+    let (remaining, value) =
+        nom::error::context("SvcParam", nom::multi::length_data(be_u16))(remaining)?;
+
+    let (_, param) = nom::error::context("SvcParam", |value| match key {
+        0 => all_consuming(nom::combinator::map(
+            nom::multi::many1(be_u16),
+            SvcParam::Mandatory,
+        ))(value),
+        1 => all_consuming(nom::combinator::map(
+            nom::multi::many1(nom::combinator::map(
+                nom::multi::length_data(nom::combinator::verify(be_u8, |l| *l > 0)),
+                <[u8]>::to_vec,
+            )),
+            SvcParam::Alpn,
+        ))(value),
+        2 => all_consuming(nom::combinator::success(SvcParam::NoDefaultAlpn))(value),
+        3 => all_consuming(nom::combinator::map(be_u16, SvcParam::Port))(value),
+        4 => all_consuming(nom::combinator::map(
+            nom::multi::many1(nom::combinator::map(be_u32, Ipv4Addr::from)),
+            SvcParam::Ipv4Hint,
+        ))(value),
+        5 => Ok((&value[value.len()..], SvcParam::Ech(value.to_vec()))),
+        6 => all_consuming(nom::combinator::map(
+            nom::multi::many1(nom::combinator::map(be_u128, Ipv6Addr::from)),
+            SvcParam::Ipv6Hint,
+        ))(value),
+        key => Ok((
+            &value[value.len()..],
+            SvcParam::Unknown(key, value.to_vec()),
+        )),
+    })(value)?;
+
+    Ok((remaining, param))
+}
+
+pub(super) fn parse_svcb<'buf>(message: &'buf [u8], data: &'buf [u8]) -> ParseResult<'buf, SVCB> {
+    let (rem, priority) = nom::number::complete::be_u16(data)?;
+    let (rem, target) = DomainName::parse(message)(rem)?;
+    let (rem, params) = nom::combinator::all_consuming(nom::multi::many0(parse_param))(rem)?;
+
+    // Keys must be strictly increasing (RFC 9460 §2.2).
+    if params.windows(2).any(|pair| pair[0].key() >= pair[1].key()) {
+        return Err(ParseError::failure(
+            data,
+            ParseErrorKind::BadField("SvcParam order"),
+        ));
+    }
+
+    Ok((
+        rem,
+        SVCB {
+            priority,
+            target,
+            params,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource_record::{RecordData, ResourceRecord};
+
+    fn rdata(record: &str) -> Vec<u8> {
+        let record: ResourceRecord = record.parse().unwrap();
+        Vec::try_from(record.rdata()).unwrap()
+    }
+
+    /// Parses the RDATA of a SVCB record from the wire and writes it back out as text.
+    fn from_wire(data: &[u8]) -> Result<String, ()> {
+        match parse_svcb(data, data) {
+            Ok(([], svcb)) => Ok(svcb.to_string()),
+            _ => Err(()),
+        }
+    }
+
+    #[test]
+    fn mandatory_keys_round_trip() {
+        let text = "1 svc.example. mandatory=alpn,port alpn=h2 port=8443";
+        let data = rdata(&format!("example. 60 IN SVCB {text}"));
+        assert_eq!(
+            data[15..],
+            [
+                0x00, 0x00, 0x00, 0x04, 0x00, 0x01, 0x00, 0x03, // mandatory=alpn,port
+                0x00, 0x01, 0x00, 0x03, 0x02, b'h', b'2', // alpn=h2
+                0x00, 0x03, 0x00, 0x02, 0x20, 0xFB, // port=8443
+            ]
+        );
+        assert_eq!(from_wire(&data).unwrap(), text);
+    }
+
+    #[test]
+    fn no_default_alpn_has_an_empty_value() {
+        let text = "1 . alpn=h3 no-default-alpn";
+        let data = rdata(&format!("example. 60 IN HTTPS {text}"));
+        assert_eq!(data[data.len() - 4..], [0x00, 0x02, 0x00, 0x00]);
+        assert_eq!(from_wire(&data).unwrap(), text);
+
+        assert!("example. 60 IN HTTPS 1 . no-default-alpn=x"
+            .parse::<ResourceRecord>()
+            .is_err());
+        let with_value = [&data[..data.len() - 1], &[0x01, b'x']].concat();
+        assert!(from_wire(&with_value).is_err());
+    }
+
+    #[test]
+    fn alpn_ids_can_hold_commas_and_backslashes() {
+        // The example from RFC 9460 Appendix D.2, figure 8.
+        let record = r#"example.com. 60 IN SVCB 16 foo.example.org. alpn="f\\\\oo\\,bar,h2""#;
+        let data = rdata(record);
+        assert_eq!(
+            data[19..],
+            [
+                0x00, 0x01, 0x00, 0x0C, // key and length
+                0x08, b'f', b'\\', b'o', b'o', b',', b'b', b'a', b'r', // f\oo,bar
+                0x02, b'h', b'2', // h2
+            ]
+        );
+
+        let text = from_wire(&data).unwrap();
+        assert_eq!(text, r"16 foo.example.org. alpn=f\\\\oo\\,bar,h2");
+        assert_eq!(rdata(&format!("example.com. 60 IN SVCB {text}")), data);
+    }
+
+    #[test]
+    fn alpn_ids_need_not_be_utf8() {
+        let data = rdata(r"example. 60 IN HTTPS 1 . alpn=h2,\255x");
+        let text = format!("example. 60 IN HTTPS {}", from_wire(&data).unwrap());
+        let parsed: ResourceRecord = text.parse().unwrap();
+        let RecordData::HTTPS(https) = parsed.rdata() else {
+            panic!("{parsed}");
+        };
+        let SvcParam::Alpn(ids) = &https.params()[0] else {
+            panic!("{parsed}");
+        };
+        assert_eq!(ids, &[b"h2".to_vec(), b"\xffx".to_vec()]);
+    }
+
+    #[test]
+    fn keys_are_sorted_and_must_not_repeat() {
+        let target = DomainName::root();
+        let svcb = SVCB::new(
+            1,
+            target.clone(),
+            vec![SvcParam::Port(443), SvcParam::Alpn(vec![b"h2".to_vec()])],
+        )
+        .unwrap();
+        assert_eq!(svcb.to_string(), "1 . alpn=h2 port=443");
+
+        let duplicate = SVCB::new(1, target, vec![SvcParam::Port(443), SvcParam::Port(853)]);
+        assert!(duplicate.is_err());
+        assert!("example. 60 IN SVCB 1 . port=443 port=853"
+            .parse::<ResourceRecord>()
+            .is_err());
+    }
+
+    #[test]
+    fn keys_out_of_order_on_the_wire_are_rejected() {
+        let header = [0x00, 0x01, 0x00];
+        let alpn = [0x00, 0x01, 0x00, 0x03, 0x02, b'h', b'2'];
+        let port = [0x00, 0x03, 0x00, 0x02, 0x01, 0xBB];
+
+        let sorted = [&header[..], &alpn, &port].concat();
+        assert_eq!(from_wire(&sorted).unwrap(), "1 . alpn=h2 port=443");
+        let unsorted = [&header[..], &port, &alpn].concat();
+        assert!(from_wire(&unsorted).is_err());
+        let duplicate = [&header[..], &port, &port].concat();
+        assert!(from_wire(&duplicate).is_err());
+    }
+}
@@ -0,0 +1,448 @@
+//! Reading and writing master zone files (RFC 1035 §5).
+
+use std::path::{Path, PathBuf};
+
+use crate::domain_name::DomainName;
+use crate::error::{TextError, ZoneError, ZoneErrorKind};
+use crate::presentation::{Entry, Fields, Tokenizer};
+use crate::resource_record::ResourceRecord;
+use crate::Class;
+
+/// How deeply `$INCLUDE`s can be nested, which stops a file that includes itself going on forever.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Reads the records out of zone files.
+///
+/// Besides records, the `$ORIGIN`, `$TTL` (RFC 2308 §4) and `$INCLUDE` directives are understood.
+/// Records may leave out their owner, TTL or class, in which case they're taken from the record
+/// before, and the TTL from `$TTL` if one has been set.
+#[derive(Clone, Debug)]
+pub struct ZoneReader {
+    origin: DomainName,
+    default_ttl: Option<u32>,
+}
+
+impl ZoneReader {
+    /// Creates a reader that resolves relative names against `origin` until a `$ORIGIN` says
+    /// otherwise.
+    pub fn new(origin: DomainName) -> Self {
+        Self {
+            origin,
+            default_ttl: None,
+        }
+    }
+
+    /// Sets the TTL of records that don't have one, as if the zone started with `$TTL`.
+    pub fn with_default_ttl(mut self, ttl: u32) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Reads a zone file. Files named by `$INCLUDE` are looked for relative to the directory the
+    /// including file is in.
+    pub fn read_file(&self, path: impl AsRef<Path>) -> Result<Vec<ResourceRecord>, ZoneError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| ZoneError {
+            file: None,
+            line: 0,
+            kind: ZoneErrorKind::Io {
+                path: path.to_path_buf(),
+                source,
+            },
+        })?;
+
+        let mut state = State::new(self);
+        state.read(&text, Some(path), self.origin.clone(), 0)?;
+        Ok(state.records)
+    }
+
+    /// Reads a zone from a string. Files named by `$INCLUDE` are looked for relative to the current
+    /// directory.
+    pub fn read_str(&self, text: &str) -> Result<Vec<ResourceRecord>, ZoneError> {
+        let mut state = State::new(self);
+        state.read(text, None, self.origin.clone(), 0)?;
+        Ok(state.records)
+    }
+}
+
+/// Writes records out as a zone file that [`ZoneReader`] and other DNS software can read back.
+#[derive(Clone, Debug, Default)]
+pub struct ZoneWriter {
+    origin: Option<DomainName>,
+}
+
+impl ZoneWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes owner names relative to `origin`, with a `$ORIGIN` at the top. Names in RDATA are
+    /// still written in full.
+    pub fn with_origin(mut self, origin: DomainName) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Writes the records one per line in canonical order (RFC 4034 §6.3), with the owner, TTL,
+    /// class and type columns lined up.
+    pub fn write(&self, records: &[ResourceRecord]) -> String {
+        let mut records: Vec<&ResourceRecord> = records.iter().collect();
+        records.sort_by(|a, b| {
+            a.name()
+                .cmp_canonical(b.name())
+                .then_with(|| u16::from(a.class()).cmp(&u16::from(b.class())))
+                .then_with(|| u16::from(a.record_type()).cmp(&u16::from(b.record_type())))
+                .then_with(|| {
+                    a.rdata()
+                        .to_canonical_bytes()
+                        .cmp(&b.rdata().to_canonical_bytes())
+                })
+        });
+
+        let rows: Vec<[String; 5]> = records
+            .iter()
+            .map(|r| {
+                let owner = match &self.origin {
+                    Some(origin) => r.name().to_string_relative(origin),
+                    None => r.name().to_string(),
+                };
+                [
+                    owner,
+                    (r.ttl() as u32).to_string(),
+                    r.class().to_string(),
+                    r.record_type().to_string(),
+                    r.rdata().to_string(),
+                ]
+            })
+            .collect();
+        let mut widths = [0; 4];
+        for row in rows.iter() {
+            for (width, column) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(column.len());
+            }
+        }
+
+        let mut output = String::new();
+        if let Some(origin) = &self.origin {
+            output.push_str(&format!("$ORIGIN {origin}\n"));
+        }
+        for [owner, ttl, class, ty, rdata] in rows {
+            let line = format!(
+                "{owner:<0$} {ttl:<1$} {class:<2$} {ty:<3$} {rdata}",
+                widths[0], widths[1], widths[2], widths[3]
+            );
+            output.push_str(line.trim_end());
+            output.push('\n');
+        }
+        output
+    }
+}
+
+/// What carries over from one entry to the next, including across `$INCLUDE`s.
+struct State {
+    records: Vec<ResourceRecord>,
+    default_ttl: Option<u32>,
+    last_owner: Option<DomainName>,
+    last_ttl: Option<u32>,
+    last_class: Class,
+}
+
+impl State {
+    fn new(reader: &ZoneReader) -> Self {
+        Self {
+            records: Vec::new(),
+            default_ttl: reader.default_ttl,
+            last_owner: None,
+            last_ttl: None,
+            last_class: Class::Internet,
+        }
+    }
+
+    /// Reads the entries of one file. The origin starts as `origin`, and any `$ORIGIN` in the
+    /// file only lasts until the end of it (RFC 1035 §5.1).
+    fn read(
+        &mut self,
+        text: &str,
+        file: Option<&Path>,
+        mut origin: DomainName,
+        depth: usize,
+    ) -> Result<(), ZoneError> {
+        let mut entries = Tokenizer::new(text);
+        while let Some(entry) = entries.next() {
+            let error = |kind: ZoneErrorKind| ZoneError {
+                file: file.map(Path::to_path_buf),
+                line: entries.entry_line(),
+                kind,
+            };
+
+            let include = entry
+                .map_err(ZoneErrorKind::from)
+                .and_then(|entry| self.entry(&entry, &mut origin))
+                .map_err(error)?;
+            if let Some((name, include_origin)) = include {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(error(ZoneErrorKind::IncludeTooDeep));
+                }
+                let path = match file.and_then(Path::parent) {
+                    Some(directory) => directory.join(name),
+                    None => PathBuf::from(name),
+                };
+                let text = std::fs::read_to_string(&path).map_err(|source| {
+                    error(ZoneErrorKind::Io {
+                        path: path.clone(),
+                        source,
+                    })
+                })?;
+                self.read(&text, Some(&path), include_origin, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a record or directive, returning the file and origin to read next if it's an
+    /// `$INCLUDE`.
+    fn entry(
+        &mut self,
+        entry: &Entry,
+        origin: &mut DomainName,
+    ) -> Result<Option<(String, DomainName)>, ZoneErrorKind> {
+        let mut fields = Fields::new(&entry.tokens, origin);
+        let directive = match entry.tokens.first() {
+            Some(token) if !entry.blank_owner && token.text().starts_with('$') => {
+                fields.next("directive")?.text()
+            }
+            _ => {
+                let record = self.record(entry.blank_owner, &mut fields)?;
+                fields.finish()?;
+                self.last_owner = Some(record.name().clone());
+                self.last_ttl = Some(record.ttl() as u32);
+                self.last_class = record.class();
+                self.records.push(record);
+                return Ok(None);
+            }
+        };
+
+        match directive.to_ascii_uppercase().as_str() {
+            "$ORIGIN" => {
+                let new_origin = fields.name("origin")?;
+                fields.finish()?;
+                *origin = new_origin;
+                Ok(None)
+            }
+            "$TTL" => {
+                self.default_ttl = Some(fields.duration("TTL")?);
+                fields.finish()?;
+                Ok(None)
+            }
+            "$INCLUDE" => {
+                let name = fields.string("file name")?;
+                let include_origin = match fields.is_empty() {
+                    true => origin.clone(),
+                    false => fields.name("origin")?,
+                };
+                fields.finish()?;
+                Ok(Some((name, include_origin)))
+            }
+            _ => Err(ZoneErrorKind::UnknownDirective(directive.to_string())),
+        }
+    }
+
+    fn record(&self, blank_owner: bool, fields: &mut Fields) -> Result<ResourceRecord, TextError> {
+        let owner = match blank_owner {
+            true => self
+                .last_owner
+                .clone()
+                .ok_or(TextError::MissingField("owner"))?,
+            false => fields.name("owner")?,
+        };
+        let default_ttl = self.default_ttl.or(self.last_ttl);
+        ResourceRecord::from_fields(owner, fields, default_ttl, self.last_class)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata/zones")
+            .join(name)
+    }
+
+    fn read_str(text: &str) -> Result<Vec<String>, ZoneError> {
+        let records = ZoneReader::new("example.".parse().unwrap()).read_str(text)?;
+        Ok(records.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn reads_every_feature() {
+        let records = ZoneReader::new(DomainName::root())
+            .read_file(fixture("example.zone"))
+            .unwrap();
+        let records: Vec<String> = records.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            records,
+            [
+                "example. 3600 IN SOA ns1.example. hostmaster.example. 2024010101 7200 3600 1209600 300",
+                "example. 3600 IN NS ns1.example.",
+                "example. 3600 IN NS ns2.example.net.",
+                "ns1.example. 300 IN A 192.0.2.53",
+                "ns1.example. 3600 IN AAAA 2001:db8::53",
+                "txt.example. 3600 CH TXT \"a;b\"",
+                "txt.example. 3600 CH TXT \"split\" \"over lines\"",
+                "sub.example. 3600 IN A 192.0.2.2",
+                "x.other.example. 3600 IN A 192.0.2.3",
+                "mail.example. 3600 IN MX 10 example.",
+                "blob.example. 3600 IN TYPE65280 \\# 3 010203",
+            ]
+        );
+    }
+
+    #[test]
+    fn written_zones_read_back_the_same() {
+        let origin: DomainName = "example.".parse().unwrap();
+        let mut records = ZoneReader::new(DomainName::root())
+            .read_file(fixture("example.zone"))
+            .unwrap();
+        // RFC 2181 §8 says TTLs with the top bit set mean zero, but they still have to survive.
+        records.push("big.example. 2147483648 IN A 192.0.2.1".parse().unwrap());
+        records.push("big.example. 4294967295 IN A 192.0.2.2".parse().unwrap());
+
+        let text = ZoneWriter::new()
+            .with_origin(origin.clone())
+            .write(&records);
+        assert!(text.contains(" 4294967295 "), "{text}");
+        let read = ZoneReader::new(origin).read_str(&text).unwrap();
+
+        let encode = |records: &[ResourceRecord]| {
+            let mut records: Vec<Vec<u8>> =
+                records.iter().map(|r| Vec::try_from(r).unwrap()).collect();
+            records.sort();
+            records
+        };
+        assert_eq!(encode(&read), encode(&records));
+    }
+
+    #[test]
+    fn records_are_sorted_by_their_canonical_rdata() {
+        let records: Vec<ResourceRecord> = [
+            "example. 60 IN NS B.example.",
+            "example. 60 IN NS a.example.",
+            "example. 60 IN MX 10 C.example.",
+            "example. 60 IN MX 10 b.example.",
+            // NSEC's next name isn't lowercased in canonical form (RFC 6840 §5.1).
+            "example. 60 IN NSEC a.example. A",
+            "example. 60 IN NSEC B.example. A",
+        ]
+        .iter()
+        .map(|r| r.parse().unwrap())
+        .collect();
+
+        let text = ZoneWriter::new().write(&records);
+        let rdata: Vec<String> = text
+            .lines()
+            .map(|l| itertools::join(l.split_whitespace().skip(3), " "))
+            .collect();
+        assert_eq!(
+            rdata,
+            [
+                "NS a.example.",
+                "NS B.example.",
+                "MX 10 b.example.",
+                "MX 10 C.example.",
+                "NSEC B.example. A",
+                "NSEC a.example. A",
+            ]
+        );
+    }
+
+    #[test]
+    fn ttl_and_class_carry_over_without_a_default() {
+        let records = read_str("a 300 CH TXT \"1\"\nb TXT \"2\"\n  600 TXT \"3\"\n").unwrap();
+        assert_eq!(
+            records,
+            [
+                "a.example. 300 CH TXT \"1\"",
+                "b.example. 300 CH TXT \"2\"",
+                "b.example. 600 CH TXT \"3\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn default_ttl_comes_before_the_last_one() {
+        let records = ZoneReader::new("example.".parse().unwrap())
+            .with_default_ttl(60)
+            .read_str("a 300 A 192.0.2.1\nb A 192.0.2.2\n")
+            .unwrap();
+        assert_eq!(records[1].ttl(), 60);
+    }
+
+    #[test]
+    fn first_record_needs_a_ttl_and_owner() {
+        let error = read_str("a A 192.0.2.1\n").unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ZoneErrorKind::Text(TextError::MissingField("TTL"))
+        ));
+        let error = read_str("  300 A 192.0.2.1\n").unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ZoneErrorKind::Text(TextError::MissingField("owner"))
+        ));
+    }
+
+    #[test]
+    fn origin_applies_to_later_names() {
+        let records = read_str("$ORIGIN sub.example.\na 60 A 192.0.2.1\n").unwrap();
+        assert_eq!(records, ["a.sub.example. 60 IN A 192.0.2.1"]);
+    }
+
+    #[test]
+    fn errors_give_the_line_the_entry_starts_on() {
+        let error = read_str("$TTL 60\n\n; comment\nwww ( A\n  192.0.2.256 )\n").unwrap_err();
+        assert_eq!(error.file(), None);
+        assert_eq!(error.line(), 4);
+        assert!(error.to_string().starts_with("line 4: "), "{error}");
+
+        let error = read_str("$TTL 60\n$GENERATE 1-2 a A 192.0.2.$\n").unwrap_err();
+        assert_eq!(error.line(), 2);
+        assert!(matches!(error.kind(), ZoneErrorKind::UnknownDirective(_)));
+    }
+
+    #[test]
+    fn errors_give_the_file_and_line() {
+        let path = fixture("bad.zone");
+        let error = ZoneReader::new(DomainName::root())
+            .read_file(&path)
+            .unwrap_err();
+        assert_eq!(error.file(), Some(path.as_path()));
+        assert_eq!(error.line(), 4);
+        assert!(error
+            .to_string()
+            .starts_with(&format!("{}:4: ", path.display())));
+    }
+
+    #[test]
+    fn includes_are_relative_to_the_including_file() {
+        let error = ZoneReader::new(DomainName::root())
+            .read_file(fixture("missing.zone"))
+            .unwrap_err();
+        assert_eq!(error.file(), Some(fixture("missing.zone").as_path()));
+        assert_eq!(error.line(), 2);
+        assert!(matches!(
+            error.kind(),
+            ZoneErrorKind::Io { path, .. } if *path == fixture("does-not-exist.zone")
+        ));
+    }
+
+    #[test]
+    fn includes_that_nest_too_deeply_are_rejected() {
+        let error = ZoneReader::new(DomainName::root())
+            .read_file(fixture("loop.zone"))
+            .unwrap_err();
+        assert!(matches!(error.kind(), ZoneErrorKind::IncludeTooDeep));
+        assert_eq!(error.line(), 1);
+    }
+}